@@ -0,0 +1,671 @@
+use colors::bundles::multifile_building::ColorBundleMultifileBuilding;
+use colors::colors_manager::ColorsManager;
+use colors::non_colored::NonColoredManager;
+use config::{FLUSH_QUEUE_FACTOR, KEEP_FILES, PREFER_MEMORY};
+use hashes::cn_nthash::CanonicalNtHashIteratorFactory;
+use hashes::fw_nthash::ForwardNtHashIteratorFactory;
+use hashes::MinimizerHashFunctionFactory;
+use io::sequences_reader::SequencesReader;
+use parallel_processor::memory_data_size::MemoryDataSize;
+use parallel_processor::memory_fs::MemoryFs;
+use parking_lot::{const_mutex, Mutex};
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use static_dispatch::StaticDispatch;
+use std::cmp::max;
+use std::fs::create_dir_all;
+use std::path::PathBuf;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use utils::compute_best_m;
+
+pub use assembler::{GraphCleaning, OutputFileFormat, QualityFilter};
+pub use io::errors::GGCATError;
+
+#[derive(Copy, Clone, Debug, PartialOrd, PartialEq)]
+pub enum HashType {
+    Auto = 0,
+    SeqHash = 1,
+    RabinKarp32 = 2,
+    RabinKarp64 = 3,
+    RabinKarp128 = 4,
+}
+
+/// Configuration of a GGCAT instance, created with `GGCATConfig::new()` and customized with the builder methods
+#[derive(Clone, Debug)]
+pub struct GGCATConfig {
+    /// Directory for temporary files
+    pub temp_dir: PathBuf,
+    /// Keep intermediate temporary files for debugging purposes
+    pub keep_temp_files: bool,
+    /// Maximum memory usage (GB)
+    pub memory: f64,
+    /// Use all the given memory before writing to disk
+    pub prefer_memory: bool,
+    pub threads_count: usize,
+
+    /// The k-mers length
+    pub kmer_length: usize,
+    /// The m-mers (minimizers) length, if None it is computed from the k-mers length
+    pub minimizer_length: Option<usize>,
+    /// Hash type used to identify kmers
+    pub hash_type: HashType,
+    /// Treats reverse complementary kmers as different
+    pub forward_only: bool,
+    /// Build a colored graph, using a different color for each input file
+    pub colors: bool,
+    /// Minimum multiplicity required to keep a kmer
+    pub min_multiplicity: usize,
+    /// Maximum multiplicity allowed to keep a kmer, if None there is no upper bound
+    pub max_multiplicity: Option<usize>,
+    /// Quality filter applied to the fastq input reads
+    pub quality_filter: QualityFilter,
+    /// The log2 of the number of buckets, if None it is computed from the input files sizes
+    pub buckets_count_log: Option<usize>,
+    /// Format of the output graph
+    pub output_format: OutputFileFormat,
+    /// Annotate the unitigs with BCALM2-style links and k-mer abundance
+    pub bcalm_headers: bool,
+    /// Write the total, mean and median k-mer abundance of each unitig
+    pub abundance_stats: bool,
+    /// Tips clipping and bubbles popping applied to the output graph
+    pub graph_cleaning: GraphCleaning,
+}
+
+impl Default for GGCATConfig {
+    fn default() -> Self {
+        Self {
+            temp_dir: PathBuf::from(".temp_files"),
+            keep_temp_files: false,
+            memory: 2.0,
+            prefer_memory: false,
+            threads_count: 16,
+            kmer_length: 32,
+            minimizer_length: None,
+            hash_type: HashType::Auto,
+            forward_only: false,
+            colors: false,
+            min_multiplicity: 2,
+            max_multiplicity: None,
+            quality_filter: QualityFilter::default(),
+            buckets_count_log: None,
+            output_format: OutputFileFormat::Fasta,
+            bcalm_headers: false,
+            abundance_stats: false,
+            graph_cleaning: GraphCleaning::default(),
+        }
+    }
+}
+
+impl GGCATConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn temp_dir(mut self, temp_dir: impl Into<PathBuf>) -> Self {
+        self.temp_dir = temp_dir.into();
+        self
+    }
+
+    pub fn keep_temp_files(mut self, keep_temp_files: bool) -> Self {
+        self.keep_temp_files = keep_temp_files;
+        self
+    }
+
+    pub fn memory(mut self, memory_gb: f64) -> Self {
+        self.memory = memory_gb;
+        self
+    }
+
+    pub fn prefer_memory(mut self, prefer_memory: bool) -> Self {
+        self.prefer_memory = prefer_memory;
+        self
+    }
+
+    pub fn threads_count(mut self, threads_count: usize) -> Self {
+        self.threads_count = threads_count;
+        self
+    }
+
+    pub fn kmer_length(mut self, k: usize) -> Self {
+        self.kmer_length = k;
+        self
+    }
+
+    pub fn minimizer_length(mut self, m: usize) -> Self {
+        self.minimizer_length = Some(m);
+        self
+    }
+
+    pub fn hash_type(mut self, hash_type: HashType) -> Self {
+        self.hash_type = hash_type;
+        self
+    }
+
+    pub fn forward_only(mut self, forward_only: bool) -> Self {
+        self.forward_only = forward_only;
+        self
+    }
+
+    pub fn colors(mut self, colors: bool) -> Self {
+        self.colors = colors;
+        self
+    }
+
+    pub fn min_multiplicity(mut self, min_multiplicity: usize) -> Self {
+        self.min_multiplicity = min_multiplicity;
+        self
+    }
+
+    pub fn max_multiplicity(mut self, max_multiplicity: usize) -> Self {
+        self.max_multiplicity = Some(max_multiplicity);
+        self
+    }
+
+    pub fn min_base_quality(mut self, min_base_quality: u8) -> Self {
+        self.quality_filter.min_base_quality = Some(min_base_quality);
+        self
+    }
+
+    pub fn min_kmer_probability(mut self, min_kmer_probability: f64) -> Self {
+        self.quality_filter.min_kmer_probability = Some(min_kmer_probability);
+        self
+    }
+
+    pub fn buckets_count_log(mut self, buckets_count_log: usize) -> Self {
+        self.buckets_count_log = Some(buckets_count_log);
+        self
+    }
+
+    pub fn output_format(mut self, output_format: OutputFileFormat) -> Self {
+        self.output_format = output_format;
+        self
+    }
+
+    pub fn bcalm_headers(mut self, bcalm_headers: bool) -> Self {
+        self.bcalm_headers = bcalm_headers;
+        self
+    }
+
+    pub fn abundance_stats(mut self, abundance_stats: bool) -> Self {
+        self.abundance_stats = abundance_stats;
+        self
+    }
+
+    pub fn clip_tips(mut self, tips_max_kmers: usize) -> Self {
+        self.graph_cleaning.tips_max_kmers = Some(tips_max_kmers);
+        self
+    }
+
+    pub fn pop_bubbles(mut self, bubbles_coverage_ratio: f64) -> Self {
+        self.graph_cleaning.bubbles_coverage_ratio = Some(bubbles_coverage_ratio);
+        self
+    }
+
+    fn get_m(&self) -> usize {
+        self.minimizer_length
+            .unwrap_or(compute_best_m(self.kmer_length))
+    }
+
+    fn validate(&self) -> Result<(), GGCATError> {
+        let k = self.kmer_length;
+        let m = self.get_m();
+
+        if k < 2 {
+            return Err(GGCATError::InvalidConfig(format!(
+                "k-mers length must be at least 2, found {}",
+                k
+            )));
+        }
+        if m == 0 || m >= k {
+            return Err(GGCATError::InvalidConfig(format!(
+                "minimizer length must be in the range [1, {}), found {}",
+                k, m
+            )));
+        }
+        if self.hash_type == HashType::SeqHash && k > 64 {
+            return Err(GGCATError::InvalidConfig(
+                "cannot use sequence hash for k > 64".to_string(),
+            ));
+        }
+        if self.threads_count == 0 {
+            return Err(GGCATError::InvalidConfig(
+                "threads count must be at least 1".to_string(),
+            ));
+        }
+        if !(self.memory > 0.0) {
+            return Err(GGCATError::InvalidConfig(format!(
+                "memory must be positive, found {}",
+                self.memory
+            )));
+        }
+        if self.min_multiplicity == 0 {
+            return Err(GGCATError::InvalidConfig(
+                "min multiplicity must be at least 1".to_string(),
+            ));
+        }
+        // The settings depending on each other are checked when the graph is built
+        Ok(())
+    }
+}
+
+/// Settings that are shared by all the instances of the current process
+#[derive(Clone, PartialEq)]
+struct GlobalSettings {
+    temp_dir: PathBuf,
+    keep_temp_files: bool,
+    memory: f64,
+    prefer_memory: bool,
+    threads_count: usize,
+}
+
+impl GlobalSettings {
+    fn from_config(config: &GGCATConfig) -> Self {
+        Self {
+            temp_dir: config.temp_dir.clone(),
+            keep_temp_files: config.keep_temp_files,
+            memory: config.memory,
+            prefer_memory: config.prefer_memory,
+            threads_count: config.threads_count,
+        }
+    }
+}
+
+static GLOBAL_SETTINGS: Mutex<Option<(GlobalSettings, Arc<ThreadPool>)>> = const_mutex(None);
+
+// The pipeline phases share global state, so only one graph can be built at a time
+static BUILD_LOCK: Mutex<()> = const_mutex(());
+
+/// A handle to the GGCAT pipeline, to be used to build graphs without running the command line tool.
+/// The memory, threads and temp files settings are global to the process, so all the instances
+/// created by the same process must share them.
+pub struct GGCATInstance {
+    config: GGCATConfig,
+    thread_pool: Arc<ThreadPool>,
+}
+
+impl GGCATInstance {
+    pub fn create(config: GGCATConfig) -> Result<Self, GGCATError> {
+        config.validate()?;
+
+        let settings = GlobalSettings::from_config(&config);
+        let mut global_settings = GLOBAL_SETTINGS.lock();
+
+        let thread_pool = match global_settings.as_ref() {
+            Some((current, thread_pool)) => {
+                if *current != settings {
+                    return Err(GGCATError::InvalidConfig(
+                        "temp dir, memory and threads settings cannot change once initialized"
+                            .to_string(),
+                    ));
+                }
+                thread_pool.clone()
+            }
+            None => {
+                let thread_pool = Arc::new(Self::initialize_globals(&settings)?);
+                *global_settings = Some((settings, thread_pool.clone()));
+                thread_pool
+            }
+        };
+
+        Ok(Self {
+            config,
+            thread_pool,
+        })
+    }
+
+    /// Initializes the process global state. The graphs are built in a dedicated thread pool,
+    /// as the global one may be already used by the host application
+    fn initialize_globals(settings: &GlobalSettings) -> Result<ThreadPool, GGCATError> {
+        // Increase the maximum allowed number of open files
+        fdlimit::raise_fd_limit();
+
+        let thread_pool = ThreadPoolBuilder::new()
+            .num_threads(settings.threads_count)
+            .thread_name(|i| format!("ggcat-thread-{}", i))
+            .build()
+            .map_err(|err| GGCATError::InvalidConfig(err.to_string()))?;
+
+        create_dir_all(&settings.temp_dir)?;
+
+        // The global settings are changed only after all the fallible steps succeeded,
+        // so that a failed initialization can be retried
+        KEEP_FILES.store(settings.keep_temp_files, Ordering::Relaxed);
+        PREFER_MEMORY.store(settings.prefer_memory, Ordering::Relaxed);
+
+        MemoryFs::init(
+            MemoryDataSize::from_bytes(
+                (settings.memory * (MemoryDataSize::OCTET_GIBIOCTET_FACTOR as f64)) as usize,
+            ),
+            FLUSH_QUEUE_FACTOR * settings.threads_count,
+            max(1, settings.threads_count / 4),
+            32768,
+        );
+
+        Ok(thread_pool)
+    }
+
+    pub fn config(&self) -> &GGCATConfig {
+        &self.config
+    }
+
+    /// Builds the compacted De Bruijn graph of the input files, returning the path of the output file.
+    /// If colors are enabled, the colormap is saved alongside the graph with the `colors.dat` extension
+    pub fn build_graph(
+        &self,
+        inputs: Vec<PathBuf>,
+        output_file: PathBuf,
+    ) -> Result<PathBuf, GGCATError> {
+        self.build_graph_with_color_names(
+            inputs.into_iter().map(|input| (input, None)).collect(),
+            output_file,
+        )
+    }
+
+    /// Same as `build_graph`, with an optional color name for each input file. The files with the
+    /// same color name share the same color, while each file without a name has its own color
+    pub fn build_graph_with_color_names(
+        &self,
+        inputs: Vec<(PathBuf, Option<String>)>,
+        output_file: PathBuf,
+    ) -> Result<PathBuf, GGCATError> {
+        if inputs.is_empty() {
+            return Err(GGCATError::NoInputFiles);
+        }
+
+        for (input, _) in inputs.iter() {
+            SequencesReader::check_input_file(input)?;
+        }
+
+        let _build_guard = BUILD_LOCK.lock();
+
+        // The temp directory is removed at the end of each build
+        create_dir_all(&self.config.temp_dir)?;
+
+        let k = self.config.kmer_length;
+
+        self.thread_pool.install(|| {
+            assembler::dynamic_dispatch::run_assembler(
+                (
+                    get_bucketing_hash_static_id(self.config.forward_only),
+                    get_hash_static_id(self.config.hash_type, k, self.config.forward_only),
+                    if self.config.colors {
+                        ColorBundleMultifileBuilding::STATIC_DISPATCH_ID
+                    } else {
+                        NonColoredManager::STATIC_DISPATCH_ID
+                    },
+                ),
+                k,
+                self.config.get_m(),
+                assembler::AssemblerStartingStep::MinimizerBucketing,
+                assembler::AssemblerStartingStep::BuildUnitigs,
+                inputs,
+                Vec::new(),
+                false,
+                output_file.clone(),
+                self.config.temp_dir.clone(),
+                self.config.threads_count,
+                self.config.min_multiplicity,
+                self.config.max_multiplicity,
+                self.config.quality_filter,
+                self.config.buckets_count_log,
+                Some(0),
+                false,
+                self.config.output_format,
+                self.config.bcalm_headers,
+                self.config.abundance_stats,
+                self.config.graph_cleaning,
+            )
+        })?;
+
+        Ok(output_file)
+    }
+
+    /// Merges colored graphs built with the same k in a single colored graph, returning the path
+    /// of the output file. The colors of each graph follow the ones of the previous graphs, if
+    /// `merge_same_name_colors` is set the colors with the same name in different graphs are
+    /// merged in a single color
+    pub fn merge_graphs(
+        &self,
+        graphs: Vec<PathBuf>,
+        merge_same_name_colors: bool,
+        output_file: PathBuf,
+    ) -> Result<PathBuf, GGCATError> {
+        if graphs.is_empty() {
+            return Err(GGCATError::NoInputFiles);
+        }
+
+        for graph in graphs.iter() {
+            SequencesReader::check_input_file(graph)?;
+        }
+
+        let _build_guard = BUILD_LOCK.lock();
+
+        create_dir_all(&self.config.temp_dir)?;
+
+        let k = self.config.kmer_length;
+
+        self.thread_pool.install(|| {
+            assembler::dynamic_dispatch::run_assembler(
+                (
+                    get_bucketing_hash_static_id(self.config.forward_only),
+                    get_hash_static_id(self.config.hash_type, k, self.config.forward_only),
+                    ColorBundleMultifileBuilding::STATIC_DISPATCH_ID,
+                ),
+                k,
+                self.config.get_m(),
+                assembler::AssemblerStartingStep::MinimizerBucketing,
+                assembler::AssemblerStartingStep::BuildUnitigs,
+                Vec::new(),
+                graphs,
+                merge_same_name_colors,
+                output_file.clone(),
+                self.config.temp_dir.clone(),
+                self.config.threads_count,
+                // The kmers of the graphs are always kept, there are no other inputs to filter
+                1,
+                None,
+                QualityFilter::default(),
+                self.config.buckets_count_log,
+                Some(0),
+                false,
+                self.config.output_format,
+                false,
+                false,
+                GraphCleaning::default(),
+            )
+        })?;
+
+        Ok(output_file)
+    }
+}
+
+pub fn get_bucketing_hash_static_id(forward_only: bool) -> StaticDispatch<()> {
+    if forward_only {
+        <ForwardNtHashIteratorFactory as MinimizerHashFunctionFactory>::STATIC_DISPATCH_ID
+    } else {
+        <CanonicalNtHashIteratorFactory as MinimizerHashFunctionFactory>::STATIC_DISPATCH_ID
+    }
+}
+
+pub fn get_hash_static_id(hash_type: HashType, k: usize, forward_only: bool) -> StaticDispatch<()> {
+    let hash_type = match hash_type {
+        HashType::Auto => {
+            if k <= 64 {
+                HashType::SeqHash
+            } else {
+                HashType::RabinKarp128
+            }
+        }
+        x => x,
+    };
+
+    use hashes::*;
+
+    match hash_type {
+        HashType::SeqHash => {
+            if k <= 8 {
+                if forward_only {
+                    fw_seqhash::u16::ForwardSeqHashFactory::STATIC_DISPATCH_ID
+                } else {
+                    cn_seqhash::u16::CanonicalSeqHashFactory::STATIC_DISPATCH_ID
+                }
+            } else if k <= 16 {
+                if forward_only {
+                    fw_seqhash::u32::ForwardSeqHashFactory::STATIC_DISPATCH_ID
+                } else {
+                    cn_seqhash::u32::CanonicalSeqHashFactory::STATIC_DISPATCH_ID
+                }
+            } else if k <= 32 {
+                if forward_only {
+                    fw_seqhash::u64::ForwardSeqHashFactory::STATIC_DISPATCH_ID
+                } else {
+                    cn_seqhash::u64::CanonicalSeqHashFactory::STATIC_DISPATCH_ID
+                }
+            } else if k <= 64 {
+                if forward_only {
+                    fw_seqhash::u128::ForwardSeqHashFactory::STATIC_DISPATCH_ID
+                } else {
+                    cn_seqhash::u128::CanonicalSeqHashFactory::STATIC_DISPATCH_ID
+                }
+            } else {
+                panic!("Cannot use sequence hash for k > 64!");
+            }
+        }
+        HashType::RabinKarp32 => {
+            if forward_only {
+                fw_rkhash::u32::ForwardRabinKarpHashFactory::STATIC_DISPATCH_ID
+            } else {
+                cn_rkhash::u32::CanonicalRabinKarpHashFactory::STATIC_DISPATCH_ID
+            }
+        }
+        HashType::RabinKarp64 => {
+            if forward_only {
+                fw_rkhash::u64::ForwardRabinKarpHashFactory::STATIC_DISPATCH_ID
+            } else {
+                cn_rkhash::u64::CanonicalRabinKarpHashFactory::STATIC_DISPATCH_ID
+            }
+        }
+        HashType::RabinKarp128 => {
+            if forward_only {
+                fw_rkhash::u128::ForwardRabinKarpHashFactory::STATIC_DISPATCH_ID
+            } else {
+                cn_rkhash::u128::CanonicalRabinKarpHashFactory::STATIC_DISPATCH_ID
+            }
+        }
+        HashType::Auto => {
+            unreachable!()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{GGCATConfig, GGCATError, GGCATInstance, HashType};
+    use io::test_utils::TestTempDir;
+    use rayon::ThreadPoolBuilder;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    fn test_instance(config: GGCATConfig) -> GGCATInstance {
+        GGCATInstance {
+            config,
+            thread_pool: Arc::new(ThreadPoolBuilder::new().num_threads(1).build().unwrap()),
+        }
+    }
+
+    fn assert_invalid(config: GGCATConfig) {
+        assert!(
+            matches!(config.validate(), Err(GGCATError::InvalidConfig(_))),
+            "{:?}",
+            config
+        );
+    }
+
+    #[test]
+    fn default_config_valid() {
+        assert!(GGCATConfig::new().validate().is_ok());
+        assert!(GGCATConfig::new()
+            .kmer_length(63)
+            .minimizer_length(12)
+            .validate()
+            .is_ok());
+        // Large k use the rabin karp hash
+        assert!(GGCATConfig::new().kmer_length(101).validate().is_ok());
+    }
+
+    #[test]
+    fn invalid_lengths_rejected() {
+        assert_invalid(GGCATConfig::new().kmer_length(1));
+        assert_invalid(GGCATConfig::new().minimizer_length(0));
+        assert_invalid(GGCATConfig::new().kmer_length(15).minimizer_length(15));
+        assert_invalid(
+            GGCATConfig::new()
+                .kmer_length(65)
+                .hash_type(HashType::SeqHash),
+        );
+    }
+
+    #[test]
+    fn invalid_settings_rejected() {
+        assert_invalid(GGCATConfig::new().threads_count(0));
+        assert_invalid(GGCATConfig::new().memory(0.0));
+        assert_invalid(GGCATConfig::new().memory(f64::NAN));
+        assert_invalid(GGCATConfig::new().min_multiplicity(0));
+        assert!(GGCATConfig::new()
+            .min_multiplicity(2)
+            .max_multiplicity(2)
+            .validate()
+            .is_ok());
+    }
+
+    #[test]
+    fn errors_propagated() {
+        // The configuration is checked before initializing the global state
+        assert!(matches!(
+            GGCATInstance::create(GGCATConfig::new().threads_count(0)),
+            Err(GGCATError::InvalidConfig(_))
+        ));
+
+        let instance = test_instance(GGCATConfig::new());
+        let temp_dir = TestTempDir::new("api-errors");
+        let output_file = temp_dir.file("output.fasta");
+        assert!(matches!(
+            instance.build_graph(vec![], output_file.clone()),
+            Err(GGCATError::NoInputFiles)
+        ));
+        assert!(matches!(
+            instance.build_graph(
+                vec![PathBuf::from("/nonexistent/ggcat-test-input.fa")],
+                output_file.clone()
+            ),
+            Err(GGCATError::FileOpen { .. })
+        ));
+        assert!(matches!(
+            instance.merge_graphs(vec![], false, output_file),
+            Err(GGCATError::NoInputFiles)
+        ));
+    }
+
+    #[test]
+    fn assembler_errors_forwarded() {
+        let temp_dir = TestTempDir::new("api-assembler-errors");
+        let input_file = temp_dir.file("reads.fa");
+        std::fs::write(&input_file, ">read\nACGTACGTACGTACGTACGTACGTACGTACGTACGT\n").unwrap();
+
+        // The settings depending on each other are checked by the assembler
+        let configs = [
+            GGCATConfig::new().min_multiplicity(3).max_multiplicity(2),
+            GGCATConfig::new().colors(true).bcalm_headers(true),
+            GGCATConfig::new().colors(true).abundance_stats(true),
+            GGCATConfig::new().colors(true).clip_tips(62),
+        ];
+        for config in configs {
+            assert!(config.validate().is_ok());
+            let instance = test_instance(config.temp_dir(temp_dir.file("temp")));
+            assert!(matches!(
+                instance.build_graph(vec![input_file.clone()], temp_dir.file("output.fasta")),
+                Err(GGCATError::InvalidConfig(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,180 @@
+use colors::parsers::graph::for_each_color_tag;
+use colors::storage::indexed_reader::ColorsIndexedReader;
+use config::ColorIndexType;
+use io::errors::GGCATError;
+use io::sequences_reader::SequencesReader;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use structopt::clap::arg_enum;
+use structopt::StructOpt;
+
+arg_enum! {
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    pub enum ColorsMatrixFormat {
+        Csv,
+        MatrixMarket,
+    }
+}
+
+#[derive(StructOpt, Debug)]
+pub struct CmdDumpColorsArgs {
+    /// The input colored graph
+    pub input_graph: PathBuf,
+
+    /// The colormap of the graph, defaults to the graph file with the .colors.dat extension
+    #[structopt(short = "c", long = "colormap")]
+    pub colormap: Option<PathBuf>,
+
+    #[structopt(short = "o", long = "output-file")]
+    pub output_file: PathBuf,
+
+    /// Format of the unitigs by colors matrix, Csv or MatrixMarket
+    #[structopt(short = "f", long = "format", default_value = "Csv")]
+    pub format: ColorsMatrixFormat,
+}
+
+/// Width reserved for the size line of the Matrix Market output, that is known only at the end
+const MATRIX_SIZE_LINE_WIDTH: usize = 64;
+
+fn csv_field(field: &str) -> String {
+    if field.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Writes the sparse matrix of the kmers of each unitig present in each color, as csv triplets
+/// `unitig,color,kmers` or in the Matrix Market coordinate format
+pub fn cmd_dump_colors(args: CmdDumpColorsArgs) -> Result<(), GGCATError> {
+    let colormap_file = args
+        .colormap
+        .unwrap_or_else(|| args.input_graph.with_extension("colors.dat"));
+
+    let mut colormap = ColorsIndexedReader::new(&colormap_file)?;
+    let colors_count = colormap.color_names().len();
+
+    let mut output =
+        BufWriter::new(
+            File::create(&args.output_file).map_err(|error| GGCATError::FileOpen {
+                file: args.output_file.clone(),
+                error,
+            })?,
+        );
+
+    let mut size_line_position = 0;
+    match args.format {
+        ColorsMatrixFormat::Csv => {
+            writeln!(output, "unitig,color,kmers")?;
+        }
+        ColorsMatrixFormat::MatrixMarket => {
+            writeln!(output, "%%MatrixMarket matrix coordinate integer general")?;
+            writeln!(
+                output,
+                "% Rows are the unitigs in the graph order, columns are the colors:"
+            )?;
+            for (index, name) in colormap.color_names().iter().enumerate() {
+                writeln!(output, "% {} {}", index + 1, name)?;
+            }
+            size_line_position = output.stream_position()?;
+            writeln!(output, "{:width$}", "", width = MATRIX_SIZE_LINE_WIDTH)?;
+        }
+    }
+
+    let mut unitigs_count = 0;
+    let mut entries_count = 0;
+    let mut unitig_colors: BTreeMap<ColorIndexType, u64> = BTreeMap::new();
+    let mut write_result = Ok(());
+
+    SequencesReader::process_file_extended(
+        &args.input_graph,
+        |seq| {
+            if write_result.is_err() {
+                return;
+            }
+
+            unitig_colors.clear();
+            let mut subset_error = None;
+            for_each_color_tag(seq.ident, |subset, kmers_count| {
+                if subset_error.is_some() {
+                    return;
+                }
+                match colormap.get(subset) {
+                    Ok(Some(colors)) => {
+                        for color in colors {
+                            *unitig_colors.entry(*color).or_insert(0) += kmers_count as u64;
+                        }
+                    }
+                    Ok(None) => {
+                        subset_error = Some(GGCATError::InvalidInput {
+                            file: args.input_graph.clone(),
+                            line: None,
+                            reason: format!(
+                                "color subset {:x} not found in the colormap '{}'",
+                                subset,
+                                colormap_file.display()
+                            ),
+                        })
+                    }
+                    Err(error) => subset_error = Some(error),
+                }
+            });
+
+            if let Some(error) = subset_error {
+                write_result = Err(error);
+                return;
+            }
+
+            let unitig_name = seq.ident[1..]
+                .split(|c| *c == b' ')
+                .next()
+                .map(String::from_utf8_lossy)
+                .unwrap_or_default();
+
+            write_result = unitig_colors.iter().try_for_each(|(color, kmers)| {
+                entries_count += 1;
+                match args.format {
+                    ColorsMatrixFormat::Csv => writeln!(
+                        output,
+                        "{},{},{}",
+                        csv_field(&unitig_name),
+                        csv_field(colormap.color_name(*color).unwrap_or_default()),
+                        kmers
+                    ),
+                    ColorsMatrixFormat::MatrixMarket => {
+                        writeln!(output, "{} {} {}", unitigs_count + 1, color + 1, kmers)
+                    }
+                }
+                .map_err(GGCATError::from)
+            });
+            unitigs_count += 1;
+        },
+        None,
+        true,
+        false,
+        false,
+    )?;
+    write_result?;
+
+    if args.format == ColorsMatrixFormat::MatrixMarket {
+        output.seek(SeekFrom::Start(size_line_position))?;
+        write!(
+            output,
+            "{} {} {}",
+            unitigs_count, colors_count, entries_count
+        )?;
+    }
+    output.flush()?;
+
+    println!(
+        "Written {} entries for {} unitigs and {} colors to: {}",
+        entries_count,
+        unitigs_count,
+        colors_count,
+        args.output_file.display()
+    );
+
+    Ok(())
+}
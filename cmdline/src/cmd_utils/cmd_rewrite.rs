@@ -1,3 +1,4 @@
+use io::errors::GGCATError;
 use io::reads_writer::ReadsWriter;
 use io::sequences_reader::SequencesReader;
 use rayon::prelude::*;
@@ -19,18 +20,24 @@ pub struct CmdRewriteArgs {
     pub output_path: PathBuf,
 }
 
-pub fn cmd_rewrite(args: CmdRewriteArgs) {
+pub fn cmd_rewrite(args: CmdRewriteArgs) -> Result<(), GGCATError> {
     let mut files_list = Vec::new();
 
     files_list.extend(args.input.into_iter());
 
     for list in args.input_lists {
-        let file_list = BufReader::new(File::open(list).unwrap());
-
-        files_list.extend(file_list.lines().map(|l| PathBuf::from(l.unwrap())));
+        let file_list =
+            BufReader::new(File::open(&list).map_err(|error| GGCATError::FileOpen {
+                file: list.clone(),
+                error,
+            })?);
+
+        for line in file_list.lines() {
+            files_list.push(PathBuf::from(line?));
+        }
     }
 
-    files_list.par_iter().for_each(|x| {
+    files_list.par_iter().try_for_each(|x| {
         let new_file_path = args.output_path.join(x.file_name().unwrap());
 
         let mut writer = ReadsWriter::new_compressed_gzip(new_file_path, 9);
@@ -43,6 +50,7 @@ pub fn cmd_rewrite(args: CmdRewriteArgs) {
             None,
             true,
             false,
+            false,
         )
-    });
+    })
 }
@@ -1,17 +1,30 @@
+mod cmd_dump_colors;
 mod cmd_rewrite;
 
+use crate::cmd_utils::cmd_dump_colors::{cmd_dump_colors, CmdDumpColorsArgs};
 use crate::cmd_utils::cmd_rewrite::{cmd_rewrite, CmdRewriteArgs};
+use std::process::exit;
 use structopt::StructOpt;
 
 #[derive(StructOpt, Debug)]
 pub enum CmdUtilsArgs {
     Rewrite(CmdRewriteArgs),
+    DumpColors(CmdDumpColorsArgs),
 }
 
 pub fn process_cmdutils(args: CmdUtilsArgs) {
     match args {
         CmdUtilsArgs::Rewrite(args) => {
-            cmd_rewrite(args);
+            if let Err(err) = cmd_rewrite(args) {
+                eprintln!("ERROR: {}", err);
+                exit(1);
+            }
+        }
+        CmdUtilsArgs::DumpColors(args) => {
+            if let Err(err) = cmd_dump_colors(args) {
+                eprintln!("ERROR: {}", err);
+                exit(1);
+            }
         }
     }
 }
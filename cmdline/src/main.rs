@@ -24,7 +24,6 @@ use std::cmp::max;
 use crate::cmd_utils::{process_cmdutils, CmdUtilsArgs};
 use colors::bundles::multifile_building::ColorBundleMultifileBuilding;
 use colors::colors_manager::ColorsManager;
-use hashes::MinimizerHashFunctionFactory;
 use parallel_processor::enable_counters_logging;
 use parallel_processor::memory_data_size::MemoryDataSize;
 use rayon::ThreadPoolBuilder;
@@ -73,11 +72,9 @@ arg_enum! {
 use ::utils::{compute_best_m, DEBUG_LEVEL};
 use colors::bundles::graph_querying::ColorBundleGraphQuerying;
 use colors::non_colored::NonColoredManager;
-use colors::storage::deserializer::ColorsDeserializer;
-use colors::DefaultColorsSerializer;
+use colors::storage::indexed_reader::ColorsIndexedReader;
 use config::{ColorIndexType, FLUSH_QUEUE_FACTOR, KEEP_FILES, PREFER_MEMORY};
-use hashes::cn_nthash::CanonicalNtHashIteratorFactory;
-use hashes::fw_nthash::ForwardNtHashIteratorFactory;
+use io::errors::GGCATError;
 use parallel_processor::memory_fs::MemoryFs;
 use parallel_processor::phase_times_monitor::PHASES_TIMES_MONITOR;
 use static_dispatch::StaticDispatch;
@@ -87,8 +84,11 @@ use structopt::clap::arg_enum;
 #[derive(StructOpt, Debug)]
 enum CliArgs {
     Build(AssemblerArgs),
+    Merge(MergeArgs),
     Matches(MatchesArgs),
     Query(QueryArgs),
+    Index(IndexArgs),
+    Serve(ServeArgs),
     Utils(CmdUtilsArgs),
 }
 
@@ -159,23 +159,65 @@ struct AssemblerArgs {
     #[structopt(short = "l", long = "input-lists")]
     pub input_lists: Vec<PathBuf>,
 
+    /// The lists of input files with their colors, with a <color_name><TAB><file_path> line for each file.
+    /// The files with the same color name share the same color (implies colors)
+    #[structopt(long = "colored-input-lists")]
+    pub colored_input_lists: Vec<PathBuf>,
+
     /// Enable colors
     #[structopt(short, long)]
     pub colors: bool,
 
+    /// Add the input files as new colors to an existing colored graph, keeping the indices of its colors (implies colors, the multiplicity filters apply only to the new inputs)
+    #[structopt(long)]
+    pub update: Option<PathBuf>,
+
     /// Minimum multiplicity required to keep a kmer
     #[structopt(short = "s", long = "min-multiplicity", default_value = "2")]
     pub min_multiplicity: usize,
 
-    // /// Minimum correctness probability for each kmer (using fastq quality checks)
-    // #[structopt(short = "q", long = "quality-threshold")]
-    // pub quality_threshold: Option<f64>,
+    /// Maximum multiplicity allowed to keep a kmer, used to discard highly repetitive kmers
+    #[structopt(long = "max-multiplicity")]
+    pub max_multiplicity: Option<usize>,
+
+    /// Minimum correctness probability for each kmer (using fastq quality checks)
+    #[structopt(short = "q", long = "quality-threshold")]
+    pub quality_threshold: Option<f64>,
+
+    /// Minimum Phred quality of the bases, reads are split at the bases with a lower quality
+    #[structopt(long = "min-base-quality")]
+    pub min_base_quality: Option<u8>,
+
     #[structopt(short = "n", long, default_value = "0")]
     pub number: usize,
 
     #[structopt(short = "o", long = "output-file", default_value = "output.fasta.lz4")]
     pub output_file: PathBuf,
 
+    /// Write the graph in GFA 1.0 format, including the links between unitigs
+    #[structopt(long)]
+    pub gfa: bool,
+
+    /// Join the unitigs in simplitigs, storing each kmer once in fewer and longer sequences
+    #[structopt(long, conflicts_with = "gfa")]
+    pub simplitigs: bool,
+
+    /// Annotate the unitigs with BCALM2-style links (L:+:id:-) and k-mer abundance (KC:i:, km:f:), not supported with colors
+    #[structopt(long = "bcalm-headers")]
+    pub bcalm_headers: bool,
+
+    /// Write the total (KC:i:), mean (km:f:) and median (md:f:) k-mer abundance of each unitig, not supported with colors
+    #[structopt(long = "abundance-stats")]
+    pub abundance_stats: bool,
+
+    /// Remove the dead-end unitigs (tips) with less than this number of kmers, not supported with colors
+    #[structopt(long = "clip-tips")]
+    pub clip_tips: Option<usize>,
+
+    /// Remove the branches of simple bubbles with a mean k-mer abundance lower than this fraction of the highest one, not supported with colors
+    #[structopt(long = "pop-bubbles")]
+    pub pop_bubbles: Option<f64>,
+
     #[structopt(long, default_value = "MinimizerBucketing")]
     pub step: AssemblerStartingStep,
 
@@ -186,14 +228,60 @@ struct AssemblerArgs {
     pub common_args: CommonArgs,
 }
 
+#[derive(StructOpt, Debug)]
+struct MergeArgs {
+    /// The colored graphs to merge, each one with its colors file (in the same folder)
+    pub input_graphs: Vec<PathBuf>,
+
+    /// The lists of colored graphs to merge
+    #[structopt(short = "l", long = "input-lists")]
+    pub input_lists: Vec<PathBuf>,
+
+    /// Merge the colors with the same name in different graphs in a single color
+    #[structopt(long = "merge-same-names")]
+    pub merge_same_names: bool,
+
+    #[structopt(short = "o", long = "output-file", default_value = "output.fasta.lz4")]
+    pub output_file: PathBuf,
+
+    #[structopt(flatten)]
+    pub common_args: CommonArgs,
+}
+
+#[derive(StructOpt, Debug)]
+struct MatchesFilterArgs {
+    /// Minimum fraction of the query kmers that a color must match to be reported
+    #[structopt(long = "min-match-fraction")]
+    pub min_match_fraction: Option<f64>,
+
+    /// Report only the colors with the highest match fractions for each query
+    #[structopt(long = "top-n")]
+    pub top_n: Option<usize>,
+}
+
+impl MatchesFilterArgs {
+    fn to_filter(&self) -> querier::QueryMatchesFilter {
+        querier::QueryMatchesFilter {
+            min_match_fraction: self.min_match_fraction,
+            top_n: self.top_n,
+        }
+    }
+}
+
 #[derive(StructOpt, Debug)]
 struct QueryArgs {
-    /// The input graph
+    /// The input graph, or an index directory built with the index command
     pub input_graph: PathBuf,
 
     /// The input query as a .fasta file
+    /// (read more than once, so it cannot be the standard input or a named pipe)
     pub input_query: PathBuf,
 
+    /// Other graphs (or index directories) to query with the same queries, can be repeated.
+    /// The results of each graph are written to a file tagged with the graph name
+    #[structopt(short = "g", long = "graph")]
+    pub additional_graphs: Vec<PathBuf>,
+
     /// Enable colors
     #[structopt(short, long)]
     pub colors: bool,
@@ -204,6 +292,60 @@ struct QueryArgs {
     #[structopt(short = "x", long, default_value = "MinimizerBucketing")]
     pub step: QuerierStartingStep,
 
+    /// Report the queries and the colors by their numeric index instead of their name
+    #[structopt(long = "numeric-ids")]
+    pub numeric_ids: bool,
+
+    #[structopt(flatten)]
+    pub matches_filter: MatchesFilterArgs,
+
+    /// Also write, for each query, the intervals of its k-mer positions found in the graph
+    /// with their colors, to the <output-file-prefix>.positions.jsonl file
+    #[structopt(long = "positional-hits")]
+    pub positional_hits: bool,
+
+    /// Also write, for each query, the unitigs of the graph sharing at least this number
+    /// of k-mers with it, to the <output-file-prefix>.unitigs.fasta file
+    #[structopt(long = "matching-unitigs")]
+    pub matching_unitigs: Option<u64>,
+
+    #[structopt(flatten)]
+    pub common_args: CommonArgs,
+}
+
+#[derive(StructOpt, Debug)]
+struct IndexArgs {
+    /// The input graph, with its colors file (in the same folder) when colors are enabled
+    pub input_graph: PathBuf,
+
+    /// Enable colors
+    #[structopt(short, long)]
+    pub colors: bool,
+
+    /// The directory where the index is saved
+    #[structopt(short = "o", long = "output-dir", default_value = "index")]
+    pub output_dir: PathBuf,
+
+    #[structopt(flatten)]
+    pub common_args: CommonArgs,
+}
+
+#[derive(StructOpt, Debug)]
+struct ServeArgs {
+    /// The input colored graph, or an index directory built with the index command
+    pub input_graph: PathBuf,
+
+    /// Listen on a unix socket instead of reading the queries from the standard input
+    #[structopt(long)]
+    pub socket: Option<PathBuf>,
+
+    /// Report the queries and the colors by their numeric index instead of their name
+    #[structopt(long = "numeric-ids")]
+    pub numeric_ids: bool,
+
+    #[structopt(flatten)]
+    pub matches_filter: MatchesFilterArgs,
+
     #[structopt(flatten)]
     pub common_args: CommonArgs,
 }
@@ -250,7 +392,8 @@ fn initialize(args: &CommonArgs, out_file: &PathBuf) {
         32768,
     );
 
-    println!(
+    // Printed to stderr, as the standard output is used to answer the queries by the server
+    eprintln!(
         "Using m: {} with k: {}",
         args.mlen.unwrap_or(compute_best_m(args.klen)),
         args.klen
@@ -260,77 +403,20 @@ fn initialize(args: &CommonArgs, out_file: &PathBuf) {
     // debug_print_allocations("/tmp/allocations", Duration::from_secs(5));
 }
 
-fn get_hash_static_id(hash_type: HashType, k: usize, forward_only: bool) -> StaticDispatch<()> {
-    let hash_type = match hash_type {
-        HashType::Auto => {
-            if k <= 64 {
-                HashType::SeqHash
-            } else {
-                HashType::RabinKarp128
-            }
-        }
-        x => x,
-    };
-
-    use hashes::*;
-
+fn convert_hash_type(hash_type: HashType) -> ggcat_api::HashType {
     match hash_type {
-        HashType::SeqHash => {
-            if k <= 8 {
-                if forward_only {
-                    fw_seqhash::u16::ForwardSeqHashFactory::STATIC_DISPATCH_ID
-                } else {
-                    cn_seqhash::u16::CanonicalSeqHashFactory::STATIC_DISPATCH_ID
-                }
-            } else if k <= 16 {
-                if forward_only {
-                    fw_seqhash::u32::ForwardSeqHashFactory::STATIC_DISPATCH_ID
-                } else {
-                    cn_seqhash::u32::CanonicalSeqHashFactory::STATIC_DISPATCH_ID
-                }
-            } else if k <= 32 {
-                if forward_only {
-                    fw_seqhash::u64::ForwardSeqHashFactory::STATIC_DISPATCH_ID
-                } else {
-                    cn_seqhash::u64::CanonicalSeqHashFactory::STATIC_DISPATCH_ID
-                }
-            } else if k <= 64 {
-                if forward_only {
-                    fw_seqhash::u128::ForwardSeqHashFactory::STATIC_DISPATCH_ID
-                } else {
-                    cn_seqhash::u128::CanonicalSeqHashFactory::STATIC_DISPATCH_ID
-                }
-            } else {
-                panic!("Cannot use sequence hash for k > 64!");
-            }
-        }
-        HashType::RabinKarp32 => {
-            if forward_only {
-                fw_rkhash::u32::ForwardRabinKarpHashFactory::STATIC_DISPATCH_ID
-            } else {
-                cn_rkhash::u32::CanonicalRabinKarpHashFactory::STATIC_DISPATCH_ID
-            }
-        }
-        HashType::RabinKarp64 => {
-            if forward_only {
-                fw_rkhash::u64::ForwardRabinKarpHashFactory::STATIC_DISPATCH_ID
-            } else {
-                cn_rkhash::u64::CanonicalRabinKarpHashFactory::STATIC_DISPATCH_ID
-            }
-        }
-        HashType::RabinKarp128 => {
-            if forward_only {
-                fw_rkhash::u128::ForwardRabinKarpHashFactory::STATIC_DISPATCH_ID
-            } else {
-                cn_rkhash::u128::CanonicalRabinKarpHashFactory::STATIC_DISPATCH_ID
-            }
-        }
-        HashType::Auto => {
-            unreachable!()
-        }
+        HashType::Auto => ggcat_api::HashType::Auto,
+        HashType::SeqHash => ggcat_api::HashType::SeqHash,
+        HashType::RabinKarp32 => ggcat_api::HashType::RabinKarp32,
+        HashType::RabinKarp64 => ggcat_api::HashType::RabinKarp64,
+        HashType::RabinKarp128 => ggcat_api::HashType::RabinKarp128,
     }
 }
 
+fn get_hash_static_id(hash_type: HashType, k: usize, forward_only: bool) -> StaticDispatch<()> {
+    ggcat_api::get_hash_static_id(convert_hash_type(hash_type), k, forward_only)
+}
+
 fn convert_assembler_step(step: AssemblerStartingStep) -> assembler::AssemblerStartingStep {
     match step {
         AssemblerStartingStep::MinimizerBucketing => {
@@ -344,26 +430,77 @@ fn convert_assembler_step(step: AssemblerStartingStep) -> assembler::AssemblerSt
     }
 }
 
+/// Reads the lists of input files with their colors, with a <color_name><TAB><file_path> line for each file
+fn read_colored_input_lists(
+    lists: &[PathBuf],
+) -> Result<Vec<(PathBuf, Option<String>)>, GGCATError> {
+    let mut inputs = Vec::new();
+
+    for list in lists {
+        let list_file = File::open(list).map_err(|error| GGCATError::FileOpen {
+            file: list.clone(),
+            error,
+        })?;
+
+        for (line_index, line) in BufReader::new(list_file).lines().enumerate() {
+            let line = line?;
+            // The lists written on Windows end with CRLF
+            let line = line.strip_suffix('\r').unwrap_or(&line);
+            if line.is_empty() {
+                continue;
+            }
+
+            let invalid_line = |reason: &str| GGCATError::InvalidInput {
+                file: list.clone(),
+                line: Some(line_index as u64 + 1),
+                reason: reason.to_string(),
+            };
+
+            match line.split_once('\t') {
+                Some(("", _)) => return Err(invalid_line("empty color name")),
+                Some((color_name, file)) => {
+                    inputs.push((PathBuf::from(file), Some(color_name.to_string())))
+                }
+                None => {
+                    return Err(invalid_line(
+                        "missing color name, expected <color_name><TAB><file_path>",
+                    ))
+                }
+            }
+        }
+    }
+
+    Ok(inputs)
+}
+
 fn run_assembler_from_args(
     generics: (StaticDispatch<()>, StaticDispatch<()>, StaticDispatch<()>),
     args: AssemblerArgs,
 ) {
-    let mut inputs = args.input.clone();
+    let mut inputs: Vec<_> = args.input.iter().map(|f| (f.clone(), None)).collect();
 
     for list in args.input_lists {
         for input in BufReader::new(File::open(list).unwrap()).lines() {
             if let Ok(input) = input {
-                inputs.push(PathBuf::from(input));
+                inputs.push((PathBuf::from(input), None));
             }
         }
     }
 
-    if inputs.is_empty() {
-        println!("ERROR: No input files specified!");
+    match read_colored_input_lists(&args.colored_input_lists) {
+        Ok(colored_inputs) => inputs.extend(colored_inputs),
+        Err(err) => {
+            eprintln!("ERROR: {}", err);
+            exit(1);
+        }
+    }
+
+    if inputs.is_empty() && args.update.is_none() {
+        eprintln!("ERROR: No input files specified!");
         exit(1);
     }
 
-    assembler::dynamic_dispatch::run_assembler(
+    if let Err(err) = assembler::dynamic_dispatch::run_assembler(
         generics,
         args.common_args.klen,
         args.common_args
@@ -372,14 +509,109 @@ fn run_assembler_from_args(
         convert_assembler_step(args.step),
         convert_assembler_step(args.last_step),
         inputs,
+        args.update.into_iter().collect(),
+        false,
         args.output_file,
         args.common_args.temp_dir,
         args.common_args.threads_count,
         args.min_multiplicity,
+        args.max_multiplicity,
+        assembler::QualityFilter {
+            min_base_quality: args.min_base_quality,
+            min_kmer_probability: args.quality_threshold,
+        },
         args.common_args.buckets_count_log,
         Some(args.number),
         args.common_args.only_bstats,
-    );
+        if args.gfa {
+            assembler::OutputFileFormat::Gfa
+        } else if args.simplitigs {
+            assembler::OutputFileFormat::Simplitigs
+        } else {
+            assembler::OutputFileFormat::Fasta
+        },
+        args.bcalm_headers,
+        args.abundance_stats,
+        assembler::GraphCleaning {
+            tips_max_kmers: args.clip_tips,
+            bubbles_coverage_ratio: args.pop_bubbles,
+        },
+    ) {
+        eprintln!("ERROR: {}", err);
+        exit(1);
+    }
+}
+
+fn read_graphs_lists(lists: &[PathBuf]) -> Result<Vec<PathBuf>, GGCATError> {
+    let mut graphs = Vec::new();
+
+    for list in lists {
+        let list_file = File::open(list).map_err(|error| GGCATError::FileOpen {
+            file: list.clone(),
+            error,
+        })?;
+
+        for line in BufReader::new(list_file).lines() {
+            let line = line?;
+            if !line.is_empty() {
+                graphs.push(PathBuf::from(line));
+            }
+        }
+    }
+
+    Ok(graphs)
+}
+
+fn run_merge_from_args(generics: (StaticDispatch<()>, StaticDispatch<()>), args: MergeArgs) {
+    let mut graphs = args.input_graphs.clone();
+
+    match read_graphs_lists(&args.input_lists) {
+        Ok(listed_graphs) => graphs.extend(listed_graphs),
+        Err(err) => {
+            eprintln!("ERROR: {}", err);
+            exit(1);
+        }
+    }
+
+    if graphs.is_empty() {
+        eprintln!("ERROR: No input graphs specified!");
+        exit(1);
+    }
+
+    // The colors of each graph are kept, following the ones of the previous graphs
+    if let Err(err) = assembler::dynamic_dispatch::run_assembler(
+        (
+            generics.0,
+            generics.1,
+            ColorBundleMultifileBuilding::STATIC_DISPATCH_ID,
+        ),
+        args.common_args.klen,
+        args.common_args
+            .mlen
+            .unwrap_or(compute_best_m(args.common_args.klen)),
+        assembler::AssemblerStartingStep::MinimizerBucketing,
+        assembler::AssemblerStartingStep::BuildUnitigs,
+        Vec::new(),
+        graphs,
+        args.merge_same_names,
+        args.output_file,
+        args.common_args.temp_dir,
+        args.common_args.threads_count,
+        // The kmers of the graphs are always kept, there are no other inputs to filter
+        1,
+        None,
+        assembler::QualityFilter::default(),
+        args.common_args.buckets_count_log,
+        Some(0),
+        args.common_args.only_bstats,
+        assembler::OutputFileFormat::Fasta,
+        false,
+        false,
+        assembler::GraphCleaning::default(),
+    ) {
+        eprintln!("ERROR: {}", err);
+        exit(1);
+    }
 }
 
 fn convert_querier_step(step: QuerierStartingStep) -> querier::QuerierStartingStep {
@@ -395,20 +627,67 @@ fn run_querier_from_args(
     generics: (StaticDispatch<()>, StaticDispatch<()>, StaticDispatch<()>),
     args: QueryArgs,
 ) {
-    querier::dynamic_dispatch::run_query(
+    if let Err(err) = querier::dynamic_dispatch::run_query(
         generics,
         args.common_args.klen,
         args.common_args
             .mlen
             .unwrap_or(compute_best_m(args.common_args.klen)),
         convert_querier_step(args.step),
-        args.input_graph,
+        std::iter::once(args.input_graph)
+            .chain(args.additional_graphs)
+            .collect(),
         args.input_query,
         args.output_file_prefix,
         args.common_args.temp_dir,
         args.common_args.buckets_count_log,
         args.common_args.threads_count,
-    );
+        args.numeric_ids,
+        args.matches_filter.to_filter(),
+        args.positional_hits,
+        args.matching_unitigs,
+    ) {
+        eprintln!("ERROR: {}", err);
+        exit(1);
+    }
+}
+
+fn run_index_from_args(
+    generics: (StaticDispatch<()>, StaticDispatch<()>, StaticDispatch<()>),
+    args: IndexArgs,
+) {
+    if let Err(err) = querier::index::dynamic_dispatch::run_index(
+        generics,
+        args.common_args.klen,
+        args.common_args
+            .mlen
+            .unwrap_or(compute_best_m(args.common_args.klen)),
+        args.input_graph,
+        args.output_dir,
+        args.common_args.temp_dir,
+        args.common_args.buckets_count_log,
+        args.common_args.threads_count,
+    ) {
+        eprintln!("ERROR: {}", err);
+        exit(1);
+    }
+}
+
+fn run_query_server_from_args(generics: (StaticDispatch<()>, StaticDispatch<()>), args: ServeArgs) {
+    if let Err(err) = querier::server::dynamic_dispatch::run_query_server(
+        generics,
+        args.common_args.klen,
+        args.common_args
+            .mlen
+            .unwrap_or(compute_best_m(args.common_args.klen)),
+        args.input_graph,
+        args.socket,
+        args.numeric_ids,
+        args.matches_filter.to_filter(),
+    ) {
+        eprintln!("ERROR: {}", err);
+        exit(1);
+    }
 }
 
 instrumenter::global_setup_instrumenter!();
@@ -439,7 +718,7 @@ fn main() {
             let _ = writeln!(err_lock, "Panic payload: {:?}", s);
         }
 
-        println!("Backtrace: {:?}", Backtrace::new());
+        let _ = writeln!(err_lock, "Backtrace: {:?}", Backtrace::new());
 
         exit(1);
     }));
@@ -453,11 +732,8 @@ fn main() {
 
             initialize(&args.common_args, &args.output_file);
 
-            let bucketing_hash = if args.common_args.forward_only {
-                <ForwardNtHashIteratorFactory as MinimizerHashFunctionFactory>::STATIC_DISPATCH_ID
-            } else {
-                <CanonicalNtHashIteratorFactory as MinimizerHashFunctionFactory>::STATIC_DISPATCH_ID
-            };
+            let bucketing_hash =
+                ggcat_api::get_bucketing_hash_static_id(args.common_args.forward_only);
 
             run_assembler_from_args(
                 (
@@ -467,7 +743,8 @@ fn main() {
                         args.common_args.klen,
                         args.common_args.forward_only,
                     ),
-                    if args.colors {
+                    if args.colors || args.update.is_some() || !args.colored_input_lists.is_empty()
+                    {
                         ColorBundleMultifileBuilding::STATIC_DISPATCH_ID
                     } else {
                         NonColoredManager::STATIC_DISPATCH_ID
@@ -476,28 +753,60 @@ fn main() {
                 args,
             )
         }
+        CliArgs::Merge(args) => {
+            initialize(&args.common_args, &args.output_file);
+
+            run_merge_from_args(
+                (
+                    ggcat_api::get_bucketing_hash_static_id(args.common_args.forward_only),
+                    get_hash_static_id(
+                        args.common_args.hash_type,
+                        args.common_args.klen,
+                        args.common_args.forward_only,
+                    ),
+                ),
+                args,
+            )
+        }
         CliArgs::Matches(args) => {
             let colors_file = args.input_file.with_extension("colors.dat");
-            let mut colors_deserializer =
-                ColorsDeserializer::<DefaultColorsSerializer>::new(colors_file);
-
-            let mut colors = Vec::new();
+            let mut colors_reader = match ColorsIndexedReader::new(colors_file) {
+                Ok(reader) => reader,
+                Err(err) => {
+                    eprintln!("ERROR: {}", err);
+                    exit(1);
+                }
+            };
 
-            colors_deserializer.get_color_mappings(args.match_color, &mut colors);
+            let colors = match colors_reader.get(args.match_color) {
+                Ok(Some(colors)) => colors.to_vec(),
+                Err(err) => {
+                    eprintln!("ERROR: {}", err);
+                    exit(1);
+                }
+                Ok(None) => {
+                    eprintln!(
+                        "ERROR: color subset {} not found, the colormap contains {} subsets",
+                        args.match_color,
+                        colors_reader.subsets_count()
+                    );
+                    exit(1);
+                }
+            };
 
             for color in colors {
-                println!("MATCHES: {}", color);
+                match colors_reader.color_name(color) {
+                    Some(name) => println!("MATCHES: {} {}", color, name),
+                    None => println!("MATCHES: {}", color),
+                }
             }
             return; // Skip final memory deallocation
         }
         CliArgs::Query(args) => {
             initialize(&args.common_args, &args.output_file_prefix);
 
-            let bucketing_hash = if args.common_args.forward_only {
-                <ForwardNtHashIteratorFactory as MinimizerHashFunctionFactory>::STATIC_DISPATCH_ID
-            } else {
-                <CanonicalNtHashIteratorFactory as MinimizerHashFunctionFactory>::STATIC_DISPATCH_ID
-            };
+            let bucketing_hash =
+                ggcat_api::get_bucketing_hash_static_id(args.common_args.forward_only);
 
             run_querier_from_args(
                 (
@@ -516,6 +825,47 @@ fn main() {
                 args,
             )
         }
+        CliArgs::Index(args) => {
+            initialize(&args.common_args, &args.output_dir);
+
+            let bucketing_hash =
+                ggcat_api::get_bucketing_hash_static_id(args.common_args.forward_only);
+
+            run_index_from_args(
+                (
+                    bucketing_hash,
+                    get_hash_static_id(
+                        args.common_args.hash_type,
+                        args.common_args.klen,
+                        args.common_args.forward_only,
+                    ),
+                    if args.colors {
+                        ColorBundleGraphQuerying::STATIC_DISPATCH_ID
+                    } else {
+                        NonColoredManager::STATIC_DISPATCH_ID
+                    },
+                ),
+                args,
+            )
+        }
+        CliArgs::Serve(args) => {
+            initialize(&args.common_args, &args.common_args.temp_dir.join("serve"));
+
+            let bucketing_hash =
+                ggcat_api::get_bucketing_hash_static_id(args.common_args.forward_only);
+
+            run_query_server_from_args(
+                (
+                    bucketing_hash,
+                    get_hash_static_id(
+                        args.common_args.hash_type,
+                        args.common_args.klen,
+                        args.common_args.forward_only,
+                    ),
+                ),
+                args,
+            )
+        }
         CliArgs::Utils(args) => {
             process_cmdutils(args);
         }
@@ -523,3 +873,62 @@ fn main() {
 
     MemoryFs::terminate();
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{read_colored_input_lists, read_graphs_lists};
+    use io::errors::GGCATError;
+    use io::test_utils::TestTempDir;
+    use std::path::PathBuf;
+
+    #[test]
+    fn colored_input_lists() {
+        let temp_dir = TestTempDir::new("colored-input-lists");
+        let list = temp_dir.file("colored-list.tsv");
+
+        std::fs::write(&list, "A\ta1.fa\n\nB\tdir/b.fa\r\nA\ta2.fa\n").unwrap();
+        let inputs = read_colored_input_lists(&[list.clone()]).unwrap();
+        assert_eq!(
+            inputs,
+            vec![
+                (PathBuf::from("a1.fa"), Some("A".to_string())),
+                (PathBuf::from("dir/b.fa"), Some("B".to_string())),
+                (PathBuf::from("a2.fa"), Some("A".to_string())),
+            ]
+        );
+
+        // The malformed lines are reported with their line number
+        std::fs::write(&list, "A\ta1.fa\nb.fa\n").unwrap();
+        match read_colored_input_lists(&[list.clone()]) {
+            Err(GGCATError::InvalidInput { file, line, .. }) => {
+                assert_eq!(file, list);
+                assert_eq!(line, Some(2));
+            }
+            _ => panic!("expected an invalid input error"),
+        }
+
+        std::fs::write(&list, "A\ta1.fa\n\tb.fa\n").unwrap();
+        match read_colored_input_lists(&[list.clone()]) {
+            Err(GGCATError::InvalidInput { line, .. }) => assert_eq!(line, Some(2)),
+            _ => panic!("expected an invalid input error"),
+        }
+    }
+
+    #[test]
+    fn graphs_lists() {
+        let temp_dir = TestTempDir::new("graphs-lists");
+        let list = temp_dir.file("graphs.txt");
+
+        std::fs::write(&list, "graph1.fa\n\ndir/graph2.fa\n").unwrap();
+        assert_eq!(
+            read_graphs_lists(&[list]).unwrap(),
+            vec![PathBuf::from("graph1.fa"), PathBuf::from("dir/graph2.fa")]
+        );
+
+        let missing_list = temp_dir.file("missing.txt");
+        match read_graphs_lists(&[missing_list.clone()]) {
+            Err(GGCATError::FileOpen { file, .. }) => assert_eq!(file, missing_list),
+            _ => panic!("expected a file open error"),
+        }
+    }
+}
@@ -75,6 +75,9 @@ impl SwapPriority {
     pub const QueryCounters: usize = 2;
     pub const ReorganizeReads: usize = 3;
     pub const LinksBuckets: usize = 3;
+    pub const UnitigEndpoints: usize = 3;
+    pub const MaximalUnitigLinks: usize = 3;
+    pub const JoinedUnitigs: usize = 3;
     pub const LinkPairs: usize = 4;
     pub const KmersMergeTempColors: usize = 4;
     pub const KmersMergeBuckets: usize = 6;
@@ -21,7 +21,7 @@ use crate::deflate_constants::{DEFLATE_MAX_NUM_SYMS, DEFLATE_NUM_PRECODE_SYMS};
 use crate::streams::deflate_chunked_buffer_input::DeflateChunkedBufferInput;
 use crate::streams::deflate_chunked_buffer_output::DeflateChunkedBufferOutput;
 use std::fs::File;
-use std::io::Read;
+use std::io::{ErrorKind, Read};
 use std::mem::{size_of, MaybeUninit};
 use std::path::Path;
 
@@ -51,15 +51,18 @@ pub struct LibdeflateDecompressor {
 pub enum LibdeflateError {
     /* Decompressed failed because the compressed data was invalid, corrupt,
      * or otherwise unsupported.  */
-    BadData = 1,
+    BadData,
 
     /* A NULL 'actual_out_nbytes_ret' was provided, but the data would have
      * decompressed to fewer than 'out_nbytes_avail' bytes.  */
-    ShortOutput = 2,
+    ShortOutput,
 
     /* The data would have decompressed to more than 'out_nbytes_avail'
      * bytes.  */
-    InsufficientSpace = 3,
+    InsufficientSpace,
+
+    /* The compressed data could not be read from the input stream.  */
+    Io(std::io::Error),
 }
 
 pub trait DeflateInput {
@@ -128,19 +131,52 @@ pub fn decompress_file_buffered(
     func: impl FnMut(&[u8]) -> Result<(), ()>,
     buf_size: usize,
 ) -> Result<(), LibdeflateError> {
-    let mut read_file = File::open(file).unwrap();
+    let read_file = File::open(file).map_err(LibdeflateError::Io)?;
+    decompress_stream_buffered(read_file, func, buf_size)
+}
 
-    let mut input_stream =
-        DeflateChunkedBufferInput::new(|buf| read_file.read(buf).unwrap_or(0), buf_size);
+pub fn decompress_stream_buffered(
+    mut stream: impl Read,
+    func: impl FnMut(&[u8]) -> Result<(), ()>,
+    buf_size: usize,
+) -> Result<(), LibdeflateError> {
+    let mut read_error = None;
+
+    let result = {
+        // A read error ends the input, the error is reported after the decompression stops
+        let mut input_stream = DeflateChunkedBufferInput::new(
+            |buf| loop {
+                match stream.read(buf) {
+                    Ok(count) => return count,
+                    Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                    Err(err) => {
+                        read_error = Some(err);
+                        return 0;
+                    }
+                }
+            },
+            buf_size,
+        );
 
-    let mut output_stream = DeflateChunkedBufferOutput::new(func, buf_size);
+        let mut output_stream = DeflateChunkedBufferOutput::new(func, buf_size);
 
-    let mut decompressor = libdeflate_alloc_decompressor();
+        let mut decompressor = libdeflate_alloc_decompressor();
+
+        let mut result = Ok(());
+        while result.is_ok() && input_stream.ensure_length(1) {
+            result = libdeflate_gzip_decompress(
+                &mut decompressor,
+                &mut input_stream,
+                &mut output_stream,
+            );
+        }
+        result
+    };
 
-    while input_stream.ensure_length(1) {
-        libdeflate_gzip_decompress(&mut decompressor, &mut input_stream, &mut output_stream)?
+    match read_error {
+        Some(err) => Err(LibdeflateError::Io(err)),
+        None => result,
     }
-    Ok(())
 }
 
 #[cfg(test)]
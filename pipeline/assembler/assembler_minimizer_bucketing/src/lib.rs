@@ -2,7 +2,7 @@ use ::static_dispatch::static_dispatch;
 use colors::colors_manager::color_types::MinimizerBucketingSeqColorDataType;
 use colors::colors_manager::{ColorsManager, MinimizerBucketingSeqColorData};
 use colors::parsers::SingleSequenceInfo;
-use config::BucketIndexType;
+use config::{BucketIndexType, ColorIndexType};
 use config::{READ_FLAG_INCL_BEGIN, READ_FLAG_INCL_END};
 use hashes::rolling::minqueue::RollingMinQueue;
 use hashes::ExtendableHashTraitType;
@@ -11,11 +11,14 @@ use hashes::MinimizerHashFunctionFactory;
 use io::concurrent::temp_reads::extra_data::{
     SequenceExtraData, SequenceExtraDataTempBufferManagement,
 };
+use io::errors::GGCATError;
 use io::sequences_reader::FastaSequence;
 use minimizer_bucketing::{
     GenericMinimizerBucketing, MinimizerBucketingCommonData, MinimizerBucketingExecutor,
     MinimizerBucketingExecutorFactory, MinimizerInputSequence,
 };
+
+pub use minimizer_bucketing::QualityFilter;
 use parallel_processor::phase_times_monitor::PHASES_TIMES_MONITOR;
 use std::cmp::max;
 use std::marker::PhantomData;
@@ -208,23 +211,31 @@ impl<H: MinimizerHashFunctionFactory, CX: ColorsManager>
     colors::non_colored::NonColoredManager,
 ])]
 pub fn minimizer_bucketing<H: MinimizerHashFunctionFactory, CX: ColorsManager>(
-    input_files: Vec<PathBuf>,
+    input_files: Vec<(PathBuf, ColorIndexType)>,
     output_path: &Path,
     buckets_count: usize,
     threads_count: usize,
     k: usize,
     m: usize,
-) -> (Vec<PathBuf>, PathBuf) {
+    quality_filter: QualityFilter,
+) -> Result<(Vec<PathBuf>, PathBuf), GGCATError> {
     H::initialize(k);
 
     PHASES_TIMES_MONITOR
         .write()
         .start_phase("phase: reads bucketing".to_string());
 
+    // Files sharing the same color are given the same index
     let input_files: Vec<_> = input_files
         .into_iter()
-        .enumerate()
-        .map(|(i, f)| (f, InputFileInfo { file_index: i }))
+        .map(|(f, color)| {
+            (
+                f,
+                InputFileInfo {
+                    file_index: color as usize,
+                },
+            )
+        })
         .collect();
 
     GenericMinimizerBucketing::do_bucketing::<AssemblerMinimizerBucketingExecutorFactory<H, CX>>(
@@ -237,5 +248,6 @@ pub fn minimizer_bucketing<H: MinimizerHashFunctionFactory, CX: ColorsManager>(
         (),
         Some(k - 1),
         false,
+        quality_filter,
     )
 }
@@ -1,4 +1,5 @@
 use crate::map_processor::ParallelKmersMergeMapPacket;
+use crate::structs::{PartialUnitigExtraBuffer, PartialUnitigExtraData, TempUnitigAbundance};
 use crate::{GlobalMergeData, ParallelKmersMergeFactory, ResultsBucket};
 use colors::colors_manager::ColorsMergeManager;
 use colors::colors_manager::{color_types, ColorsManager};
@@ -9,7 +10,6 @@ use hashes::HashFunction;
 use hashes::{ExtendableHashTraitType, HashFunctionFactory, MinimizerHashFunctionFactory};
 use instrumenter::local_setup_instrumenter;
 use io::compressed_read::CompressedRead;
-use io::concurrent::temp_reads::extra_data::SequenceExtraData;
 use io::concurrent::temp_reads::extra_data::SequenceExtraDataTempBufferManagement;
 use io::structs::hash_entry::Direction;
 use io::varint::decode_varint;
@@ -34,9 +34,12 @@ pub struct ParallelKmersMergeFinalExecutor<
     forward_seq: Vec<u8>,
     backward_seq: Vec<u8>,
     unitigs_temp_colors: color_types::TempUnitigColorStructure<H, MH, CX>,
-    current_bucket: Option<ResultsBucket<color_types::PartialUnitigsColorStructure<H, MH, CX>>>,
-    temp_color_buffer:
-        <color_types::PartialUnitigsColorStructure<H, MH, CX> as SequenceExtraData>::TempBuffer,
+    current_bucket: Option<
+        ResultsBucket<PartialUnitigExtraData<color_types::PartialUnitigsColorStructure<H, MH, CX>>>,
+    >,
+    unitigs_temp_abundance: TempUnitigAbundance,
+    temp_extra_buffer:
+        PartialUnitigExtraBuffer<color_types::PartialUnitigsColorStructure<H, MH, CX>>,
     bucket_counter: usize,
     bucket_change_threshold: usize,
     _phantom: PhantomData<H>,
@@ -56,14 +59,28 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
             unitigs_temp_colors: CX::ColorsMergeManagerType::<H, MH>::alloc_unitig_color_structure(
             ),
             current_bucket: None,
-            temp_color_buffer:
-                color_types::PartialUnitigsColorStructure::<H, MH, CX>::new_temp_buffer(),
+            unitigs_temp_abundance: TempUnitigAbundance::new(global_data.track_abundances),
+            temp_extra_buffer: PartialUnitigExtraData::<
+                color_types::PartialUnitigsColorStructure<H, MH, CX>,
+            >::new_temp_buffer(),
             bucket_counter: 0,
             bucket_change_threshold: 16, // TODO: Parametrize
             _phantom: PhantomData,
         }
     }
 
+    /// Checks if the multiplicity of a kmer is inside the allowed range.
+    /// With colors the counters of the kept kmers are replaced by their color index, and the ones
+    /// above the maximum multiplicity are already reset to zero by process_colors
+    #[inline(always)]
+    fn is_multiplicity_valid(
+        global_data: &GlobalMergeData<H, MH, CX>,
+        multiplicity: usize,
+    ) -> bool {
+        multiplicity >= global_data.min_multiplicity
+            && (CX::COLORS_ENABLED || multiplicity <= global_data.max_multiplicity)
+    }
+
     fn get_kmers(
         global_data: &<ParallelKmersMergeFactory<H, MH, CX> as KmersTransformExecutorFactory>::GlobalExtraData,
         map_struct: &ParallelKmersMergeMapPacket<H, MH, CX>,
@@ -78,7 +95,7 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
         if MH::INVERTIBLE {
             for (hash, rhentry) in map_struct.rhash_map.iter() {
                 let count = rhentry.get_kmer_multiplicity();
-                if count < global_data.min_multiplicity {
+                if !Self::is_multiplicity_valid(global_data, count) {
                     continue;
                 }
 
@@ -135,7 +152,7 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
                     };
 
                     let count = rhentry.get_kmer_multiplicity();
-                    if count < global_data.min_multiplicity {
+                    if !Self::is_multiplicity_valid(global_data, count) {
                         continue;
                     }
 
@@ -196,6 +213,7 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
                 &mut map_struct.rhash_map,
                 global_data.k,
                 global_data.min_multiplicity,
+                global_data.max_multiplicity,
             );
         }
 
@@ -236,6 +254,10 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
                 &mut self.unitigs_temp_colors,
                 rhentry,
             );
+
+            // The multiplicity must be read before marking the entry as used
+            self.unitigs_temp_abundance
+                .reset(rhentry.get_kmer_multiplicity() as u64);
             rhentry.set_used();
 
             let mut try_extend_function = |output: &mut Vec<u8>,
@@ -256,6 +278,10 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
                                            colors_function: fn(
                 ts: &mut color_types::TempUnitigColorStructure<H, MH, CX>,
                 entry: &MapEntry<color_types::HashMapTempColorIndex<H, MH, CX>>,
+            ),
+                                           abundance_function: fn(
+                temp_abundance: &mut TempUnitigAbundance,
+                count: u64,
             )| {
                 let mut temp_data = (hash, 0);
                 let mut current_hash;
@@ -271,7 +297,10 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
                             idx,
                         );
                         if let Some(hash) = map_struct.rhash_map.get(&new_hash.to_unextendable()) {
-                            if hash.get_kmer_multiplicity() >= global_data.min_multiplicity {
+                            if Self::is_multiplicity_valid(
+                                global_data,
+                                hash.get_kmer_multiplicity(),
+                            ) {
                                 // println!("Forward match extend read {:x?}!", new_hash);
                                 count += 1;
                                 temp_data = (new_hash, idx);
@@ -289,8 +318,10 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
                                 if let Some(hash) =
                                     map_struct.rhash_map.get(&bw_hash.to_unextendable())
                                 {
-                                    if hash.get_kmer_multiplicity() >= global_data.min_multiplicity
-                                    {
+                                    if Self::is_multiplicity_valid(
+                                        global_data,
+                                        hash.get_kmer_multiplicity(),
+                                    ) {
                                         if ocount > 0 {
                                             break 'ext_loop (current_hash, false);
                                         }
@@ -317,6 +348,11 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
                             colors_function(&mut self.unitigs_temp_colors, entryref);
                         }
 
+                        abundance_function(
+                            &mut self.unitigs_temp_abundance,
+                            entryref.get_kmer_multiplicity() as u64,
+                        );
+
                         // Flag the entry as already used
                         entryref.set_used();
 
@@ -343,6 +379,7 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
                         MH::manual_roll_forward,
                         MH::manual_roll_reverse,
                         CX::ColorsMergeManagerType::<H, MH>::extend_forward,
+                        TempUnitigAbundance::push_back,
                     );
                     (fw_hash, end_ignored)
                 }
@@ -357,6 +394,7 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
                         MH::manual_roll_reverse,
                         MH::manual_roll_forward,
                         CX::ColorsMergeManagerType::<H, MH>::extend_backward,
+                        TempUnitigAbundance::push_front,
                     );
                     (bw_hash, begin_ignored)
                 }
@@ -371,15 +409,27 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
             let colors =
                 color_types::ColorsMergeManagerType::<H, MH, CX>::encode_part_unitigs_colors(
                     &mut self.unitigs_temp_colors,
-                    &mut self.temp_color_buffer,
+                    &mut self.temp_extra_buffer.colors,
                 );
 
-            let read_index = current_bucket.add_read(colors, out_seq, &self.temp_color_buffer);
-
-            color_types::PartialUnitigsColorStructure::<H, MH, CX>::clear_temp_buffer(
-                &mut self.temp_color_buffer,
+            let abundances = self
+                .temp_extra_buffer
+                .add_abundances(self.unitigs_temp_abundance.abundances.iter().copied());
+
+            let read_index = current_bucket.add_read(
+                PartialUnitigExtraData {
+                    colors,
+                    counters: self.unitigs_temp_abundance.counters,
+                    abundances,
+                },
+                out_seq,
+                &self.temp_extra_buffer,
             );
 
+            PartialUnitigExtraData::<
+                color_types::PartialUnitigsColorStructure<H, MH, CX>,
+            >::clear_temp_buffer(&mut self.temp_extra_buffer);
+
             Self::write_hashes(
                 &mut self.hashes_tmp,
                 fw_hash.to_unextendable(),
@@ -3,14 +3,14 @@
 use crate::final_executor::ParallelKmersMergeFinalExecutor;
 use crate::map_processor::{ParallelKmersMergeMapProcessor, KMERGE_TEMP_DIR};
 use crate::preprocessor::ParallelKmersMergePreprocessor;
-use crate::structs::{ResultsBucket, RetType};
+use crate::structs::{PartialUnitigExtraData, ResultsBucket, RetType};
 use assembler_minimizer_bucketing::AssemblerMinimizerBucketingExecutorFactory;
 use colors::colors_manager::color_types::{
     GlobalColorsTableWriter, MinimizerBucketingSeqColorDataType,
 };
 use colors::colors_manager::{color_types, ColorsManager};
 use config::{
-    get_memory_mode, BucketIndexType, SwapPriority, DEFAULT_LZ4_COMPRESSION_LEVEL,
+    get_memory_mode, BucketIndexType, ColorIndexType, SwapPriority, DEFAULT_LZ4_COMPRESSION_LEVEL,
     MINIMUM_SUBBUCKET_KMERS_COUNT, RESPLITTING_MAX_K_M_DIFFERENCE,
 };
 use crossbeam::queue::*;
@@ -50,9 +50,13 @@ pub struct GlobalMergeData<
     m: usize,
     buckets_count: usize,
     min_multiplicity: usize,
+    max_multiplicity: usize,
+    kept_colors_count: ColorIndexType,
+    track_abundances: bool,
     colors_global_table: Arc<GlobalColorsTableWriter<H, MH, CX>>,
-    output_results_buckets:
-        ArrayQueue<ResultsBucket<color_types::PartialUnitigsColorStructure<H, MH, CX>>>,
+    output_results_buckets: ArrayQueue<
+        ResultsBucket<PartialUnitigExtraData<color_types::PartialUnitigsColorStructure<H, MH, CX>>>,
+    >,
     hashes_buckets: Arc<MultiThreadBuckets<LockFreeBinaryWriter>>,
     global_resplit_data: Arc<MinimizerBucketingCommonData<()>>,
     sequences_size_total: AtomicU64,
@@ -136,6 +140,9 @@ pub fn kmers_merge<
     colors_global_table: Arc<GlobalColorsTableWriter<H, MH, CX>>,
     buckets_count: usize,
     min_multiplicity: usize,
+    max_multiplicity: usize,
+    kept_colors_count: ColorIndexType,
+    track_abundances: bool,
     out_directory: P,
     k: usize,
     m: usize,
@@ -172,7 +179,9 @@ pub fn kmers_merge<
 
     let output_results_buckets = ArrayQueue::new(reads_buckets.count());
     for (index, bucket) in reads_buckets.into_buckets().enumerate() {
-        let bucket_read = ResultsBucket::<color_types::PartialUnitigsColorStructure<H, MH, CX>> {
+        let bucket_read = ResultsBucket::<
+            PartialUnitigExtraData<color_types::PartialUnitigsColorStructure<H, MH, CX>>,
+        > {
             read_index: 0,
             reads_writer: OwnedDrop::new(bucket),
             temp_buffer: Vec::with_capacity(256),
@@ -189,6 +198,9 @@ pub fn kmers_merge<
         m,
         buckets_count,
         min_multiplicity,
+        max_multiplicity,
+        kept_colors_count,
+        track_abundances,
         colors_global_table,
         output_results_buckets,
         hashes_buckets: hashes_buckets.clone(),
@@ -322,6 +334,9 @@ mod tests {
             global_colors_table.clone(),
             buckets_count,
             min_multiplicity,
+            usize::MAX,
+            0,
+            false,
             Path::new(TEMP_DIR),
             k,
             m,
@@ -219,7 +219,18 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
                     entry,
                 );
 
-                if entry.get_counter() == global_data.min_multiplicity {
+                // The kmers of the updated graphs are saved as soon as they are found, as their
+                // counter may never reach the minimum multiplicity
+                let newly_kept = !entry.is_always_kept()
+                    && CX::is_kept_color(&kmer_color, global_data.kept_colors_count);
+                if newly_kept {
+                    entry.set_always_kept();
+                }
+
+                if (newly_kept && entry.get_counter() <= global_data.min_multiplicity)
+                    || (!entry.is_always_kept()
+                        && entry.get_counter() == global_data.min_multiplicity)
+                {
                     min_idx = min(min_idx, idx / 4);
                     max_idx = max(max_idx, idx);
                 }
@@ -1,13 +1,227 @@
+use byteorder::ReadBytesExt;
 use config::BucketIndexType;
 use io::concurrent::temp_reads::creads_utils::CompressedReadsBucketHelper;
-use io::concurrent::temp_reads::extra_data::SequenceExtraData;
+use io::concurrent::temp_reads::extra_data::{
+    SequenceExtraData, SequenceExtraDataTempBufferManagement,
+};
+use io::varint::{decode_varint, encode_varint, VARINT_MAX_SIZE};
 use parallel_processor::buckets::bucket_writer::BucketItem;
 use parallel_processor::buckets::writers::compressed_binary_writer::CompressedBinaryWriter;
 use parallel_processor::buckets::LockFreeBucket;
+use std::collections::VecDeque;
+use std::io::{Read, Write};
 use std::marker::PhantomData;
+use std::ops::Range;
 use std::path::PathBuf;
 use utils::owned_drop::OwnedDrop;
 
+/// Abundance of the kmers of a (partial) unitig. The abundances of the first and the last kmers
+/// are kept to avoid counting twice the kmer shared by two joined partial unitigs
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SequenceAbundance {
+    pub first: u64,
+    pub sum: u64,
+    pub last: u64,
+}
+
+impl SequenceAbundance {
+    pub fn new(count: u64) -> Self {
+        Self {
+            first: count,
+            sum: count,
+            last: count,
+        }
+    }
+
+    #[inline(always)]
+    pub fn push_front(&mut self, count: u64) {
+        self.first = count;
+        self.sum += count;
+    }
+
+    #[inline(always)]
+    pub fn push_back(&mut self, count: u64) {
+        self.last = count;
+        self.sum += count;
+    }
+
+    /// The abundance of the reverse complemented sequence
+    pub fn reverse(&self) -> Self {
+        Self {
+            first: self.last,
+            sum: self.sum,
+            last: self.first,
+        }
+    }
+
+    /// Appends a sequence whose first kmer is the same as the last kmer of this one
+    pub fn join(&mut self, other: &Self) {
+        self.sum += other.sum - other.first;
+        self.last = other.last;
+    }
+
+    /// Removes the last kmer, when it is a duplicate of the first one in a circular unitig
+    pub fn pop_back(&mut self) {
+        self.sum -= self.last;
+    }
+}
+
+/// Abundance of the kmers of a unitig while it is being extended in both directions
+pub struct TempUnitigAbundance {
+    pub counters: SequenceAbundance,
+    /// The abundance of each kmer, tracked only if needed as it is expensive to store
+    pub abundances: VecDeque<u64>,
+    pub track_abundances: bool,
+}
+
+impl TempUnitigAbundance {
+    pub fn new(track_abundances: bool) -> Self {
+        Self {
+            counters: SequenceAbundance::default(),
+            abundances: VecDeque::new(),
+            track_abundances,
+        }
+    }
+
+    pub fn reset(&mut self, count: u64) {
+        self.counters = SequenceAbundance::new(count);
+        self.abundances.clear();
+        if self.track_abundances {
+            self.abundances.push_back(count);
+        }
+    }
+
+    #[inline(always)]
+    pub fn push_front(&mut self, count: u64) {
+        self.counters.push_front(count);
+        if self.track_abundances {
+            self.abundances.push_front(count);
+        }
+    }
+
+    #[inline(always)]
+    pub fn push_back(&mut self, count: u64) {
+        self.counters.push_back(count);
+        if self.track_abundances {
+            self.abundances.push_back(count);
+        }
+    }
+}
+
+/// Extra data of the partial unitigs, with their colors and their kmers abundance
+#[derive(Clone, Debug)]
+pub struct PartialUnitigExtraData<CX: SequenceExtraData> {
+    pub colors: CX,
+    pub counters: SequenceAbundance,
+    /// Range of the kmers abundances in the temp buffer, empty if they are not tracked
+    pub abundances: Range<usize>,
+}
+
+impl<CX: SequenceExtraData> PartialUnitigExtraData<CX> {
+    pub fn get_abundances<'a>(&self, buffer: &'a PartialUnitigExtraBuffer<CX>) -> &'a [u64] {
+        &buffer.abundances[self.abundances.clone()]
+    }
+}
+
+pub struct PartialUnitigExtraBuffer<CX: SequenceExtraData> {
+    pub colors: CX::TempBuffer,
+    pub abundances: Vec<u64>,
+}
+
+impl<CX: SequenceExtraData> PartialUnitigExtraBuffer<CX> {
+    /// Adds the abundances to the buffer, returning their range
+    pub fn add_abundances(&mut self, abundances: impl Iterator<Item = u64>) -> Range<usize> {
+        let start = self.abundances.len();
+        self.abundances.extend(abundances);
+        start..self.abundances.len()
+    }
+}
+
+impl<CX: SequenceExtraData> SequenceExtraDataTempBufferManagement<PartialUnitigExtraBuffer<CX>>
+    for PartialUnitigExtraData<CX>
+{
+    #[inline(always)]
+    fn new_temp_buffer() -> PartialUnitigExtraBuffer<CX> {
+        PartialUnitigExtraBuffer {
+            colors: CX::new_temp_buffer(),
+            abundances: Vec::new(),
+        }
+    }
+
+    #[inline(always)]
+    fn clear_temp_buffer(buffer: &mut PartialUnitigExtraBuffer<CX>) {
+        CX::clear_temp_buffer(&mut buffer.colors);
+        buffer.abundances.clear();
+    }
+
+    fn copy_temp_buffer(
+        dest: &mut PartialUnitigExtraBuffer<CX>,
+        src: &PartialUnitigExtraBuffer<CX>,
+    ) {
+        CX::copy_temp_buffer(&mut dest.colors, &src.colors);
+        dest.abundances.clear();
+        dest.abundances.extend_from_slice(&src.abundances);
+    }
+
+    #[inline(always)]
+    fn copy_extra_from(
+        extra: Self,
+        src: &PartialUnitigExtraBuffer<CX>,
+        dst: &mut PartialUnitigExtraBuffer<CX>,
+    ) -> Self {
+        let abundances = dst.add_abundances(extra.get_abundances(src).iter().copied());
+        Self {
+            colors: CX::copy_extra_from(extra.colors, &src.colors, &mut dst.colors),
+            counters: extra.counters,
+            abundances,
+        }
+    }
+}
+
+impl<CX: SequenceExtraData> SequenceExtraData for PartialUnitigExtraData<CX> {
+    type TempBuffer = PartialUnitigExtraBuffer<CX>;
+
+    #[inline(always)]
+    fn decode_extended(buffer: &mut Self::TempBuffer, reader: &mut impl Read) -> Option<Self> {
+        let colors = CX::decode_extended(&mut buffer.colors, reader)?;
+        let first = decode_varint(|| reader.read_u8().ok())?;
+        let sum = decode_varint(|| reader.read_u8().ok())?;
+        let last = decode_varint(|| reader.read_u8().ok())?;
+
+        let abundances_count = decode_varint(|| reader.read_u8().ok())? as usize;
+        let abundances_start = buffer.abundances.len();
+        for _ in 0..abundances_count {
+            buffer
+                .abundances
+                .push(decode_varint(|| reader.read_u8().ok())?);
+        }
+
+        Some(Self {
+            colors,
+            counters: SequenceAbundance { first, sum, last },
+            abundances: abundances_start..buffer.abundances.len(),
+        })
+    }
+
+    #[inline(always)]
+    fn encode_extended(&self, buffer: &Self::TempBuffer, writer: &mut impl Write) {
+        self.colors.encode_extended(&buffer.colors, writer);
+        encode_varint(|b| writer.write_all(b), self.counters.first).unwrap();
+        encode_varint(|b| writer.write_all(b), self.counters.sum).unwrap();
+        encode_varint(|b| writer.write_all(b), self.counters.last).unwrap();
+
+        encode_varint(|b| writer.write_all(b), self.abundances.len() as u64).unwrap();
+        for abundance in self.get_abundances(buffer) {
+            encode_varint(|b| writer.write_all(b), *abundance).unwrap();
+        }
+    }
+
+    #[inline(always)]
+    fn max_size(&self) -> usize {
+        self.colors.max_size() + VARINT_MAX_SIZE * (4 + self.abundances.len())
+    }
+}
+
 pub struct ResultsBucket<X: SequenceExtraData> {
     pub read_index: u64,
     pub reads_writer: OwnedDrop<CompressedBinaryWriter>,
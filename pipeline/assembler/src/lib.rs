@@ -1,18 +1,24 @@
 #![feature(slice_group_by)]
 
-use crate::pipeline::build_unitigs::build_unitigs;
+use crate::pipeline::build_unitigs::{build_unitigs, AbundanceOutput};
+use crate::pipeline::graph_cleaning::clean_graph;
+use crate::pipeline::graph_colors_split::{check_colored_graphs, split_graph_colors, GraphsColors};
 use crate::pipeline::hashes_sorting::hashes_sorting;
 use crate::pipeline::links_compaction::links_compaction;
+use crate::pipeline::maximal_unitig_links::maximal_unitig_links;
 use crate::pipeline::reorganize_reads::reorganize_reads;
+use crate::pipeline::simplitigs::build_simplitigs;
 use crate::pipeline::unitig_links_manager::UnitigLinksManager;
 use ::static_dispatch::static_dispatch;
 use colors::colors_manager::ColorsManager;
 use colors::colors_manager::ColorsMergeManager;
 use config::{
-    get_memory_mode, SwapPriority, DEFAULT_PER_CPU_BUFFER_SIZE, KEEP_FILES,
+    get_memory_mode, ColorIndexType, SwapPriority, DEFAULT_PER_CPU_BUFFER_SIZE, KEEP_FILES,
     MAXIMUM_SECOND_BUCKETS_LOG, MINIMUM_LOG_DELTA_TIME,
 };
+use hashbrown::HashMap;
 use hashes::{HashFunctionFactory, MinimizerHashFunctionFactory};
+use io::errors::GGCATError;
 use io::reads_writer::ReadsWriter;
 use io::{compute_buckets_log_from_input_files, generate_bucket_names};
 use kmers_merge::structs::RetType;
@@ -25,11 +31,14 @@ use parallel_processor::phase_times_monitor::PHASES_TIMES_MONITOR;
 use parallel_processor::utils::scoped_thread_local::ScopedThreadLocal;
 use parking_lot::Mutex;
 use std::fs::remove_file;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::time::Instant;
 
+pub use crate::pipeline::graph_cleaning::GraphCleaning;
+pub use assembler_minimizer_bucketing::QualityFilter;
+
 mod pipeline;
 mod structs;
 
@@ -45,6 +54,70 @@ pub enum AssemblerStartingStep {
     BuildUnitigs = 5,
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum OutputFileFormat {
+    /// Unitigs as FASTA records
+    Fasta,
+    /// GFA 1.0 graph, with a segment for each unitig and its links
+    Gfa,
+    /// Simplitigs as FASTA records, joining the unitigs so that each kmer is written only once
+    Simplitigs,
+}
+
+/// Assigns a color to each input file, the files with the same color name share the same color,
+/// while each file without a name has its own color, named after the file.
+/// Returns the files with their colors indices and the colors names
+fn assign_input_colors(
+    input: Vec<(PathBuf, Option<String>)>,
+    first_color: ColorIndexType,
+) -> (Vec<(PathBuf, ColorIndexType)>, Vec<String>) {
+    let mut color_names = Vec::new();
+    let mut named_colors = HashMap::new();
+
+    let input = input
+        .into_iter()
+        .map(|(file, color_name)| {
+            let mut new_color = |name: String| {
+                color_names.push(name);
+                first_color + color_names.len() as ColorIndexType - 1
+            };
+
+            let color = match color_name {
+                Some(color_name) => match named_colors.get(&color_name) {
+                    Some(color) => *color,
+                    None => {
+                        let color = new_color(color_name.clone());
+                        named_colors.insert(color_name, color);
+                        color
+                    }
+                },
+                // Unnamed files are never merged, even with the same file name. The paths
+                // without a file name (as '..') are used as they are
+                None => new_color(
+                    file.file_name()
+                        .unwrap_or(file.as_os_str())
+                        .to_string_lossy()
+                        .to_string(),
+                ),
+            };
+            (file, color)
+        })
+        .collect();
+
+    (input, color_names)
+}
+
+fn create_output_writer(path: &Path) -> ReadsWriter {
+    match path.extension() {
+        Some(ext) => match ext.to_string_lossy().to_string().as_str() {
+            "lz4" => ReadsWriter::new_compressed_lz4(path, 2),
+            "gz" => ReadsWriter::new_compressed_gzip(path, 2),
+            _ => ReadsWriter::new_plain(path),
+        },
+        None => ReadsWriter::new_plain(path),
+    }
+}
+
 #[static_dispatch(BucketingHash = [
     hashes::cn_nthash::CanonicalNtHashIteratorFactory,
     #[cfg(not(feature = "devel-build"))] hashes::fw_nthash::ForwardNtHashIteratorFactory
@@ -76,26 +149,141 @@ pub fn run_assembler<
     m: usize,
     step: AssemblerStartingStep,
     last_step: AssemblerStartingStep,
-    input: Vec<PathBuf>,
+    input: Vec<(PathBuf, Option<String>)>,
+    colored_graphs: Vec<PathBuf>,
+    merge_same_name_colors: bool,
     output_file: PathBuf,
     temp_dir: PathBuf,
     threads_count: usize,
     min_multiplicity: usize,
+    max_multiplicity: Option<usize>,
+    quality_filter: QualityFilter,
     buckets_count_log: Option<usize>,
     loopit_number: Option<usize>,
     only_bstats: bool,
-) {
-    PHASES_TIMES_MONITOR.write().init();
+    output_format: OutputFileFormat,
+    bcalm_headers: bool,
+    abundance_stats: bool,
+    graph_cleaning: GraphCleaning,
+) -> Result<(), GGCATError> {
+    if input.is_empty() && colored_graphs.is_empty() {
+        return Err(GGCATError::NoInputFiles);
+    }
 
-    let buckets_count_log =
-        buckets_count_log.unwrap_or_else(|| compute_buckets_log_from_input_files(&input));
-    let buckets_count = 1 << buckets_count_log;
+    if !colored_graphs.is_empty() && !AssemblerColorsManager::COLORS_ENABLED {
+        return Err(GGCATError::InvalidConfig(
+            "colors must be enabled to add samples to a colored graph".to_string(),
+        ));
+    }
+    check_colored_graphs(&colored_graphs, &output_file)?;
+
+    let max_multiplicity = max_multiplicity.unwrap_or(usize::MAX);
+    if max_multiplicity < min_multiplicity {
+        return Err(GGCATError::InvalidConfig(format!(
+            "max multiplicity ({}) must not be lower than min multiplicity ({})",
+            max_multiplicity, min_multiplicity
+        )));
+    }
+    if let Some(min_kmer_probability) = quality_filter.min_kmer_probability {
+        if !(0.0..=1.0).contains(&min_kmer_probability) {
+            return Err(GGCATError::InvalidConfig(format!(
+                "kmer correctness probability threshold must be between 0 and 1, found {}",
+                min_kmer_probability
+            )));
+        }
+    }
 
-    let color_names: Vec<_> = input
+    if output_format == OutputFileFormat::Simplitigs && (bcalm_headers || abundance_stats) {
+        return Err(GGCATError::InvalidConfig(
+            "links and abundance headers are not supported for simplitigs".to_string(),
+        ));
+    }
+
+    // With colors the kmers counters are replaced by their color index before building the
+    // unitigs, so their abundance is not available anymore
+    if AssemblerColorsManager::COLORS_ENABLED && bcalm_headers {
+        return Err(GGCATError::InvalidConfig(
+            "links and abundance headers are not supported with colors".to_string(),
+        ));
+    }
+    if AssemblerColorsManager::COLORS_ENABLED && abundance_stats {
+        return Err(GGCATError::InvalidConfig(
+            "abundance statistics are not supported with colors".to_string(),
+        ));
+    }
+
+    if let Some(coverage_ratio) = graph_cleaning.bubbles_coverage_ratio {
+        if !(0.0..=1.0).contains(&coverage_ratio) {
+            return Err(GGCATError::InvalidConfig(format!(
+                "bubbles coverage ratio must be between 0 and 1, found {}",
+                coverage_ratio
+            )));
+        }
+    }
+
+    // Bubbles popping relies on the kmers abundance, that is lost with colors
+    if AssemblerColorsManager::COLORS_ENABLED && graph_cleaning.is_enabled() {
+        return Err(GGCATError::InvalidConfig(
+            "graph cleaning is not supported with colors".to_string(),
+        ));
+    }
+
+    if graph_cleaning.is_enabled() && abundance_stats {
+        return Err(GGCATError::InvalidConfig(
+            "abundance statistics are not supported with graph cleaning".to_string(),
+        ));
+    }
+
+    // Graph cleaning needs the abundance of the unitigs to pop the bubbles
+    let abundance_output = if abundance_stats {
+        AbundanceOutput::Full
+    } else if bcalm_headers || graph_cleaning.is_enabled() {
+        AbundanceOutput::TotalAndMean
+    } else {
+        AbundanceOutput::None
+    };
+
+    PHASES_TIMES_MONITOR.write().init();
+
+    // The kmers of the existing colored graphs are split by color, so that their colors are
+    // the first ones of the new graph, keeping the same indices
+    let mut graphs_colors = GraphsColors::new(merge_same_name_colors);
+    for (graph_index, graph) in colored_graphs.iter().enumerate() {
+        let (colors_files, names) = split_graph_colors(graph, temp_dir.as_path(), graph_index, k)?;
+        let merged_count = graphs_colors.add_graph(colors_files, names);
+        if merged_count > 0 {
+            println!(
+                "Merged {} colors of graph '{}' with the same name ones of the previous graphs",
+                merged_count,
+                graph.display()
+            );
+        }
+    }
+    let graphs_colors_files: Vec<_> = graphs_colors
+        .files
         .iter()
-        .map(|f| f.file_name().unwrap().to_string_lossy().to_string())
+        .map(|(file, _)| file.clone())
         .collect();
 
+    // The kmers of the graphs colors are kept regardless of their multiplicity
+    let graphs_colors_count = graphs_colors.names.len() as ColorIndexType;
+
+    let mut color_names = graphs_colors.names;
+    let (input, input_color_names) =
+        assign_input_colors(input, color_names.len() as ColorIndexType);
+    color_names.extend(input_color_names);
+
+    let input: Vec<_> = graphs_colors.files.into_iter().chain(input).collect();
+
+    let buckets_count_log = match buckets_count_log {
+        Some(buckets_count_log) => buckets_count_log,
+        None => {
+            let input_files: Vec<_> = input.iter().map(|(file, _)| file.clone()).collect();
+            compute_buckets_log_from_input_files(&input_files)?
+        }
+    };
+    let buckets_count = 1 << buckets_count_log;
+
     let global_colors_table = Arc::new(
         AssemblerColorsManager::ColorsMergeManagerType::create_colors_table(
             output_file.with_extension("colors.dat"),
@@ -114,7 +302,8 @@ pub fn run_assembler<
             threads_count,
             k,
             m,
-        )
+            quality_filter,
+        )?
     } else {
         (
             generate_bucket_names(temp_dir.join("bucket"), buckets_count, None),
@@ -122,6 +311,12 @@ pub fn run_assembler<
         )
     };
 
+    if !KEEP_FILES.load(Ordering::Relaxed) {
+        for file in graphs_colors_files {
+            let _ = remove_file(file);
+        }
+    }
+
     println!(
         "Temp buckets files size: {:.2}",
         MemoryDataSize::from_bytes(fs_extra::dir::get_size(&temp_dir).unwrap_or(0) as usize)
@@ -131,7 +326,7 @@ pub fn run_assembler<
         PHASES_TIMES_MONITOR
             .write()
             .print_stats("Completed minimizer bucketing.".to_string());
-        return;
+        return Ok(());
     } else {
         MemoryFs::flush_all_to_disk();
         MemoryFs::free_memory();
@@ -152,7 +347,7 @@ pub fn run_assembler<
                 m,
             );
         });
-        return;
+        return Ok(());
     }
 
     let RetType { sequences, hashes } = if step <= AssemblerStartingStep::KmersMerge {
@@ -162,6 +357,9 @@ pub fn run_assembler<
             global_colors_table.clone(),
             buckets_count,
             min_multiplicity,
+            max_multiplicity,
+            graphs_colors_count,
+            abundance_output.needs_kmers_abundances(),
             temp_dir.as_path(),
             k,
             m,
@@ -177,7 +375,7 @@ pub fn run_assembler<
         PHASES_TIMES_MONITOR
             .write()
             .print_stats("Completed kmers merge.".to_string());
-        return;
+        return Ok(());
     } else {
         MemoryFs::flush_all_to_disk();
         MemoryFs::free_memory();
@@ -196,7 +394,7 @@ pub fn run_assembler<
         PHASES_TIMES_MONITOR
             .write()
             .print_stats("Hashes sorting.".to_string());
-        return;
+        return Ok(());
     } else {
         MemoryFs::flush_all_to_disk();
         MemoryFs::free_memory();
@@ -317,20 +515,23 @@ pub fn run_assembler<
         PHASES_TIMES_MONITOR
             .write()
             .print_stats("Links Compaction.".to_string());
-        return;
+        return Ok(());
     } else {
         MemoryFs::flush_all_to_disk();
         MemoryFs::free_memory();
     }
 
-    let final_unitigs_file = Mutex::new(match output_file.extension() {
-        Some(ext) => match ext.to_string_lossy().to_string().as_str() {
-            "lz4" => ReadsWriter::new_compressed_lz4(&output_file, 2),
-            "gz" => ReadsWriter::new_compressed_gzip(&output_file, 2),
-            _ => ReadsWriter::new_plain(&output_file),
-        },
-        None => ReadsWriter::new_plain(&output_file),
-    });
+    let needs_links = output_format != OutputFileFormat::Fasta || bcalm_headers;
+
+    // When links are needed or the graph is cleaned the unitigs are written to a temporary file,
+    // that is then rewritten to the final output together with the links (or joined in simplitigs)
+    let unitigs_file = if needs_links || graph_cleaning.is_enabled() {
+        temp_dir.join("maximal_unitigs.tmp.fa.lz4")
+    } else {
+        output_file.clone()
+    };
+
+    let final_unitigs_file = Mutex::new(create_output_writer(&unitigs_file));
 
     let (reorganized_reads, _final_unitigs_bucket) =
         if step <= AssemblerStartingStep::ReorganizeReads {
@@ -340,6 +541,8 @@ pub fn run_assembler<
                 temp_dir.as_path(),
                 &final_unitigs_file,
                 buckets_count,
+                k,
+                abundance_output,
             )
         } else {
             (
@@ -355,7 +558,7 @@ pub fn run_assembler<
         PHASES_TIMES_MONITOR
             .write()
             .print_stats("Reorganize reads.".to_string());
-        return;
+        return Ok(());
     } else {
         MemoryFs::flush_all_to_disk();
         MemoryFs::free_memory();
@@ -371,16 +574,89 @@ pub fn run_assembler<
             &final_unitigs_file,
             k,
             &links_manager,
+            abundance_output,
         );
     }
 
-    let _ = std::fs::remove_dir(temp_dir.as_path());
-
     final_unitigs_file.into_inner().finalize();
 
+    let unitigs_file = if graph_cleaning.is_enabled() {
+        let cleaned_file = if needs_links {
+            temp_dir.join("cleaned_unitigs.tmp.fa.lz4")
+        } else {
+            output_file.clone()
+        };
+
+        let mut cleaned_writer = create_output_writer(&cleaned_file);
+        clean_graph::<MergingHash>(
+            unitigs_file,
+            &mut cleaned_writer,
+            temp_dir.as_path(),
+            buckets_count,
+            k,
+            graph_cleaning,
+            bcalm_headers,
+        )?;
+        cleaned_writer.finalize();
+        cleaned_file
+    } else {
+        unitigs_file
+    };
+
+    if needs_links {
+        let mut output_writer = create_output_writer(&output_file);
+        if output_format == OutputFileFormat::Simplitigs {
+            build_simplitigs::<MergingHash>(
+                unitigs_file,
+                &mut output_writer,
+                temp_dir.as_path(),
+                buckets_count,
+                k,
+            )?;
+        } else {
+            maximal_unitig_links::<MergingHash>(
+                unitigs_file,
+                &mut output_writer,
+                output_format,
+                temp_dir.as_path(),
+                buckets_count,
+                k,
+            )?;
+        }
+        output_writer.finalize();
+    }
+
+    let _ = std::fs::remove_dir(temp_dir.as_path());
+
     PHASES_TIMES_MONITOR
         .write()
         .print_stats("Compacted De Bruijn graph construction completed.".to_string());
 
     println!("Final output saved to: {}", output_file.display());
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assign_input_colors;
+    use std::path::PathBuf;
+
+    #[test]
+    fn input_colors_names() {
+        let (input, color_names) = assign_input_colors(
+            vec![
+                (PathBuf::from("dir/a.fa"), None),
+                (PathBuf::from("b.fa"), Some("sample".to_string())),
+                (PathBuf::from(".."), None),
+                (PathBuf::from("c.fa"), Some("sample".to_string())),
+            ],
+            2,
+        );
+        assert_eq!(
+            input.iter().map(|(_, color)| *color).collect::<Vec<_>>(),
+            vec![2, 3, 4, 3]
+        );
+        assert_eq!(color_names, vec!["a.fa", "sample", ".."]);
+    }
 }
@@ -15,6 +15,7 @@ use io::get_bucket_index;
 use io::reads_writer::ReadsWriter;
 use io::sequences_reader::FastaSequence;
 use io::structs::unitig_link::{UnitigFlags, UnitigIndex, UnitigLink};
+use kmers_merge::structs::{PartialUnitigExtraData, SequenceAbundance};
 use parallel_processor::buckets::bucket_writer::BucketItem;
 use parallel_processor::buckets::readers::compressed_binary_reader::CompressedBinaryReader;
 use parallel_processor::buckets::readers::lock_free_binary_reader::LockFreeBinaryReader;
@@ -61,6 +62,62 @@ impl FastaCompatibleRead for [u8] {
     }
 }
 
+/// Which kmers abundance statistics are written in the unitigs headers
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AbundanceOutput {
+    None,
+    /// Total (KC:i:) and mean (km:f:) abundance, as in BCALM2
+    TotalAndMean,
+    /// Total, mean and median (md:f:) abundance
+    Full,
+}
+
+impl AbundanceOutput {
+    /// True if the abundance of each kmer must be tracked to compute the statistics
+    pub fn needs_kmers_abundances(&self) -> bool {
+        *self == AbundanceOutput::Full
+    }
+}
+
+pub struct UnitigAbundanceStats {
+    pub total: u64,
+    pub median: Option<f64>,
+}
+
+impl UnitigAbundanceStats {
+    pub fn new(
+        output: AbundanceOutput,
+        counters: &SequenceAbundance,
+        abundances: &mut [u64],
+    ) -> Option<Self> {
+        match output {
+            AbundanceOutput::None => None,
+            AbundanceOutput::TotalAndMean => Some(Self {
+                total: counters.sum,
+                median: None,
+            }),
+            AbundanceOutput::Full => Some(Self {
+                total: counters.sum,
+                median: Some(compute_median(abundances)),
+            }),
+        }
+    }
+}
+
+fn compute_median(values: &mut [u64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+
+    let middle = values.len() / 2;
+    let (lower, upper, _) = values.select_nth_unstable(middle);
+    if values.len() % 2 == 1 {
+        *upper as f64
+    } else {
+        (*lower.iter().max().unwrap() + *upper) as f64 / 2.0
+    }
+}
+
 #[allow(unused_variables)]
 pub fn write_fasta_entry<
     H: MinimizerHashFunctionFactory,
@@ -73,14 +130,29 @@ pub fn write_fasta_entry<
     writer: &mut FastaWriterConcurrentBuffer,
     color: color_types::PartialUnitigsColorStructure<H, MH, CX>,
     color_buffer: &<color_types::PartialUnitigsColorStructure<H, MH, CX> as SequenceExtraData>::TempBuffer,
+    abundance: Option<UnitigAbundanceStats>,
     read: &R,
     index: usize,
+    k: usize,
     // links_iterator: I,
 ) {
-    // KC:i:{} km:f:
     temp_buffer.clear();
     write!(temp_buffer, ">{} LN:i:{}", index, read.get_length()).unwrap();
 
+    if let Some(abundance) = abundance {
+        let kmers_count = read.get_length() - k + 1;
+        write!(
+            temp_buffer,
+            " KC:i:{} km:f:{:.1}",
+            abundance.total,
+            abundance.total as f64 / kmers_count as f64
+        )
+        .unwrap();
+        if let Some(median) = abundance.median {
+            write!(temp_buffer, " md:f:{:.1}", median).unwrap();
+        }
+    }
+
     CX::ColorsMergeManagerType::<H, MH>::print_color_data(&color, color_buffer, temp_buffer);
 
     let ident_buffer_size = temp_buffer.len();
@@ -97,7 +169,9 @@ pub fn write_fasta_entry<
 
 type CompressedReadsHelperUnitigsBuilding<'a, H, MH, CX> = CompressedReadsBucketHelper<
     'a,
-    ReorganizedReadsExtraData<color_types::PartialUnitigsColorStructure<H, MH, CX>>,
+    ReorganizedReadsExtraData<
+        PartialUnitigExtraData<color_types::PartialUnitigsColorStructure<H, MH, CX>>,
+    >,
     typenum::U0,
     false,
 >;
@@ -113,6 +187,7 @@ pub fn build_unitigs<
     out_file: &Mutex<ReadsWriter>,
     k: usize,
     links_manager: &UnitigLinksManager,
+    abundance_output: AbundanceOutput,
 ) {
     PHASES_TIMES_MONITOR
         .write()
@@ -218,7 +293,7 @@ pub fn build_unitigs<
                 final_sequences.resize(counter, None);
 
                 let mut color_extra_buffer = ReorganizedReadsExtraData::<
-                    color_types::PartialUnitigsColorStructure<H, MH, CX>,
+                    PartialUnitigExtraData<color_types::PartialUnitigsColorStructure<H, MH, CX>>,
                 >::new_temp_buffer();
                 let mut final_color_extra_buffer =
                     color_types::PartialUnitigsColorStructure::<H, MH, CX>::new_temp_buffer();
@@ -249,6 +324,8 @@ pub fn build_unitigs<
 
                 let mut final_unitig_color =
                     CX::ColorsMergeManagerType::<H, MH>::alloc_unitig_color_structure();
+                let mut final_unitig_counters = SequenceAbundance::default();
+                let mut final_unitig_abundances = Vec::new();
 
                 'uloop: for sequence in
                     final_sequences.group_by(|_a, b| !b.as_ref().unwrap().1.is_start)
@@ -257,6 +334,7 @@ pub fn build_unitigs<
                     let is_circular = sequence[0].as_ref().unwrap().1.is_circular;
 
                     temp_sequence.clear();
+                    final_unitig_abundances.clear();
                     CX::ColorsMergeManagerType::<H, MH>::reset_unitig_color_structure(
                         &mut final_unitig_color,
                     );
@@ -274,26 +352,44 @@ pub fn build_unitigs<
                         if compr_read.bases_count() == 0 {
                             continue 'uloop;
                         }
+                        let counters = if flags.is_reverse_complemented() {
+                            color.counters.reverse()
+                        } else {
+                            color.counters
+                        };
+
+                        let abundances = color.get_abundances(&color_extra_buffer.0);
+                        // The first kmer is shared with the previous part of the unitig
+                        let skipped_kmers = if is_first { 0 } else { 1 };
+                        if flags.is_reverse_complemented() {
+                            final_unitig_abundances
+                                .extend(abundances.iter().rev().skip(skipped_kmers));
+                        } else {
+                            final_unitig_abundances.extend(abundances.iter().skip(skipped_kmers));
+                        }
+
                         if is_first {
+                            final_unitig_counters = counters;
                             if flags.is_reverse_complemented() {
                                 temp_sequence.extend(compr_read.as_reverse_complement_bases_iter());
                                 CX::ColorsMergeManagerType::<H, MH>::join_structures::<true>(
                                     &mut final_unitig_color,
-                                    color,
-                                    &color_extra_buffer.0,
+                                    &color.colors,
+                                    &color_extra_buffer.0.colors,
                                     0,
                                 );
                             } else {
                                 temp_sequence.extend(compr_read.as_bases_iter());
                                 CX::ColorsMergeManagerType::<H, MH>::join_structures::<false>(
                                     &mut final_unitig_color,
-                                    color,
-                                    &color_extra_buffer.0,
+                                    &color.colors,
+                                    &color_extra_buffer.0.colors,
                                     0,
                                 );
                             }
                             is_first = false;
                         } else {
+                            final_unitig_counters.join(&counters);
                             if flags.is_reverse_complemented() {
                                 temp_sequence.extend(
                                     compr_read
@@ -303,8 +399,8 @@ pub fn build_unitigs<
                                 );
                                 CX::ColorsMergeManagerType::<H, MH>::join_structures::<true>(
                                     &mut final_unitig_color,
-                                    color,
-                                    &color_extra_buffer.0,
+                                    &color.colors,
+                                    &color_extra_buffer.0.colors,
                                     1,
                                 );
                             } else {
@@ -316,8 +412,8 @@ pub fn build_unitigs<
                                 );
                                 CX::ColorsMergeManagerType::<H, MH>::join_structures::<false>(
                                     &mut final_unitig_color,
-                                    color,
-                                    &color_extra_buffer.0,
+                                    &color.colors,
+                                    &color_extra_buffer.0.colors,
                                     1,
                                 );
                             }
@@ -328,6 +424,8 @@ pub fn build_unitigs<
                     if is_circular {
                         temp_sequence.pop();
                         CX::ColorsMergeManagerType::<H, MH>::pop_base(&mut final_unitig_color);
+                        final_unitig_counters.pop_back();
+                        final_unitig_abundances.pop();
                     }
 
                     let writable_color =
@@ -341,8 +439,14 @@ pub fn build_unitigs<
                         &mut tmp_final_unitigs_buffer,
                         writable_color,
                         &final_color_extra_buffer,
+                        UnitigAbundanceStats::new(
+                            abundance_output,
+                            &final_unitig_counters,
+                            &mut final_unitig_abundances,
+                        ),
                         temp_sequence.as_slice(),
                         links_manager.get_unitig_index(bucket_index, unitig_index),
+                        k,
                     );
                     unitig_index += 1;
                 }
@@ -0,0 +1,405 @@
+use crate::pipeline::maximal_unitig_links::find_links;
+use crate::pipeline::unitigs_graph::{
+    join_unitigs, parse_total_abundance, UnitigPlacement, UnitigsGraph,
+};
+use crate::structs::maximal_unitig_link::MaximalUnitigLink;
+use hashes::HashFunctionFactory;
+use io::errors::GGCATError;
+use io::reads_writer::ReadsWriter;
+use io::sequences_reader::SequencesReader;
+use parallel_processor::phase_times_monitor::PHASES_TIMES_MONITOR;
+use std::path::{Path, PathBuf};
+
+/// Simplifications applied to the graph after its construction
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct GraphCleaning {
+    /// Dead-end unitigs with less kmers are removed
+    pub tips_max_kmers: Option<usize>,
+    /// Branches of a simple bubble with a mean kmers abundance lower than this fraction
+    /// of the highest one in the same bubble are removed
+    pub bubbles_coverage_ratio: Option<f64>,
+}
+
+impl GraphCleaning {
+    pub fn is_enabled(&self) -> bool {
+        self.tips_max_kmers.is_some() || self.bubbles_coverage_ratio.is_some()
+    }
+}
+
+/// An oriented unitig, with the unitig index and its orientation
+type OrientedUnitig = (u64, bool);
+
+struct CleaningGraph {
+    graph: UnitigsGraph,
+    kmers_counts: Vec<u64>,
+    abundances: Vec<u64>,
+    removed: Vec<bool>,
+}
+
+impl CleaningGraph {
+    fn live_links(&self, unitig: u64, rc: bool) -> impl Iterator<Item = &MaximalUnitigLink> {
+        self.graph
+            .links(unitig, rc)
+            .filter(|link| !self.removed[link.to as usize])
+    }
+
+    /// Returns the only live link leaving `unitig` in the given orientation, if it is unique
+    fn single_live_link(&self, unitig: u64, rc: bool) -> Option<&MaximalUnitigLink> {
+        let mut links = self.live_links(unitig, rc);
+        let link = links.next()?;
+        match links.next() {
+            Some(_) => None,
+            None => Some(link),
+        }
+    }
+
+    fn mean_abundance(&self, unitig: u64) -> f64 {
+        self.abundances[unitig as usize] as f64 / self.kmers_counts[unitig as usize] as f64
+    }
+
+    /// Iteratively removes the short dead-end unitigs attached to a branching node,
+    /// returning the number of removed unitigs
+    fn clip_tips(&mut self, max_kmers: usize) -> u64 {
+        let mut clipped_count = 0;
+
+        loop {
+            let mut changed = false;
+
+            for unitig in 0..self.graph.unitigs_count() {
+                if self.removed[unitig as usize]
+                    || self.kmers_counts[unitig as usize] >= max_kmers as u64
+                {
+                    continue;
+                }
+
+                let has_forward = self.live_links(unitig, false).next().is_some();
+                let has_backward = self.live_links(unitig, true).next().is_some();
+                if has_forward == has_backward {
+                    continue;
+                }
+
+                // Without another branch on the attached node, the unitig is the end of
+                // a linear path that should not be eroded
+                let is_tip = match self.single_live_link(unitig, has_backward) {
+                    Some(link) => {
+                        link.to != unitig
+                            && self.live_links(link.to, !link.to_rc()).nth(1).is_some()
+                    }
+                    None => false,
+                };
+
+                if is_tip {
+                    self.removed[unitig as usize] = true;
+                    clipped_count += 1;
+                    changed = true;
+                }
+            }
+
+            if !changed {
+                break clipped_count;
+            }
+        }
+    }
+
+    /// Removes the low coverage branches of the simple bubbles, made of unitigs with
+    /// a single link on each side joining the same two oriented unitigs.
+    /// Returns the number of removed unitigs
+    fn pop_bubbles(&mut self, coverage_ratio: f64) -> u64 {
+        let mut branches: Vec<((OrientedUnitig, OrientedUnitig), u64)> = Vec::new();
+
+        for unitig in 0..self.graph.unitigs_count() {
+            if self.removed[unitig as usize] {
+                continue;
+            }
+
+            let (forward, backward) = match (
+                self.single_live_link(unitig, false),
+                self.single_live_link(unitig, true),
+            ) {
+                (Some(forward), Some(backward)) => (forward, backward),
+                _ => continue,
+            };
+
+            if forward.to == unitig || backward.to == unitig {
+                continue;
+            }
+
+            // The branch is entered from the reversed backward neighbour
+            let key = (
+                (backward.to, !backward.to_rc()),
+                (forward.to, forward.to_rc()),
+            );
+            let reverse_key = (
+                (forward.to, !forward.to_rc()),
+                (backward.to, backward.to_rc()),
+            );
+            branches.push((std::cmp::min(key, reverse_key), unitig));
+        }
+
+        branches.sort_unstable();
+
+        let mut popped_count = 0;
+        for bubble in branches.group_by(|a, b| a.0 == b.0) {
+            if bubble.len() < 2 {
+                continue;
+            }
+
+            let max_abundance = bubble
+                .iter()
+                .map(|(_, unitig)| self.mean_abundance(*unitig))
+                .fold(0.0, f64::max);
+
+            for (_, unitig) in bubble {
+                if self.mean_abundance(*unitig) < max_abundance * coverage_ratio {
+                    self.removed[*unitig as usize] = true;
+                    popped_count += 1;
+                }
+            }
+        }
+
+        popped_count
+    }
+
+    /// Follows the unambiguous links from `unitig` (reverse complemented if `rc`),
+    /// where the next unitig has no other incoming link
+    fn extend(
+        &self,
+        mut unitig: u64,
+        mut rc: bool,
+        used: &mut [bool],
+        path: &mut Vec<OrientedUnitig>,
+    ) {
+        path.clear();
+        while let Some(link) = self.single_live_link(unitig, rc) {
+            if used[link.to as usize] || self.single_live_link(link.to, !link.to_rc()).is_none() {
+                break;
+            }
+            used[link.to as usize] = true;
+            unitig = link.to;
+            rc = link.to_rc();
+            path.push((unitig, rc));
+        }
+    }
+
+    /// Compacts again the unitigs left unbranched by the removals, returning the placement
+    /// of each remaining unitig and the new unitigs count
+    fn compact(&self) -> (Vec<Option<UnitigPlacement>>, u64) {
+        let unitigs_count = self.graph.unitigs_count();
+        let mut used = self.removed.clone();
+        let mut placements = vec![None; unitigs_count as usize];
+        let mut forward_path = Vec::new();
+        let mut backward_path = Vec::new();
+        let mut compacted_count = 0;
+
+        for start in 0..unitigs_count {
+            if used[start as usize] {
+                continue;
+            }
+            used[start as usize] = true;
+
+            self.extend(start, false, &mut used, &mut forward_path);
+            self.extend(start, true, &mut used, &mut backward_path);
+
+            let compacted_unitigs = backward_path
+                .iter()
+                .rev()
+                .map(|(unitig, rc)| (*unitig, !*rc))
+                .chain(std::iter::once((start, false)))
+                .chain(forward_path.iter().copied());
+
+            for (position, (unitig, rc)) in compacted_unitigs.enumerate() {
+                placements[unitig as usize] = Some(UnitigPlacement {
+                    joined: compacted_count,
+                    position: position as u64,
+                    rc,
+                });
+            }
+            compacted_count += 1;
+        }
+
+        (placements, compacted_count)
+    }
+}
+
+/// Removes the tips and the low coverage bubble branches from the maximal unitigs in `unitigs_file`,
+/// then writes the remaining unitigs compacting again the ones that are no longer branching.
+/// The unitigs headers must contain the total kmers abundance (KC:i:) to pop the bubbles
+pub fn clean_graph<MH: HashFunctionFactory>(
+    unitigs_file: PathBuf,
+    out_file: &mut ReadsWriter,
+    temp_dir: &Path,
+    buckets_count: usize,
+    k: usize,
+    cleaning: GraphCleaning,
+    with_abundance: bool,
+) -> Result<(), GGCATError> {
+    PHASES_TIMES_MONITOR
+        .write()
+        .start_phase("phase: graph cleaning".to_string());
+
+    let (links_buckets, _, unitigs_count) =
+        find_links::<MH>(&unitigs_file, temp_dir, buckets_count, k)?;
+
+    let mut kmers_counts = Vec::with_capacity(unitigs_count as usize);
+    let mut abundances = Vec::with_capacity(unitigs_count as usize);
+
+    SequencesReader::process_file_extended(
+        &unitigs_file,
+        |seq| {
+            kmers_counts.push((seq.seq.len() - k + 1) as u64);
+            abundances.push(parse_total_abundance(seq.ident));
+        },
+        None,
+        true,
+        false,
+        false,
+    )?;
+
+    let mut graph = CleaningGraph {
+        graph: UnitigsGraph::load::<MH>(links_buckets, unitigs_count),
+        kmers_counts,
+        abundances,
+        removed: vec![false; unitigs_count as usize],
+    };
+
+    if let Some(max_kmers) = cleaning.tips_max_kmers {
+        println!("Clipped {} tips", graph.clip_tips(max_kmers));
+    }
+
+    if let Some(coverage_ratio) = cleaning.bubbles_coverage_ratio {
+        println!(
+            "Popped {} bubble branches",
+            graph.pop_bubbles(coverage_ratio)
+        );
+    }
+
+    let (placements, compacted_count) = graph.compact();
+    drop(graph);
+
+    PHASES_TIMES_MONITOR
+        .write()
+        .start_phase("phase: cleaned graph writing".to_string());
+
+    join_unitigs(
+        &unitigs_file,
+        &placements,
+        compacted_count,
+        out_file,
+        temp_dir,
+        buckets_count,
+        k,
+        with_abundance,
+    )?;
+
+    println!(
+        "Cleaned graph: {} unitigs compacted in {}",
+        unitigs_count, compacted_count
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pipeline::graph_cleaning::CleaningGraph;
+    use crate::pipeline::unitigs_graph::UnitigsGraph;
+    use crate::structs::maximal_unitig_link::MaximalUnitigLink;
+
+    /// Builds a graph from the (kmers, total abundance) of each unitig and its edges,
+    /// adding each edge in both directions as done for canonical kmers
+    fn build_graph(unitigs: &[(u64, u64)], edges: &[(u64, bool, u64, bool)]) -> CleaningGraph {
+        let links = edges
+            .iter()
+            .flat_map(|(from, from_rc, to, to_rc)| {
+                let link = MaximalUnitigLink::new(*from, *from_rc, *to, *to_rc);
+                [link.reversed(), link]
+            })
+            .collect();
+
+        CleaningGraph {
+            graph: UnitigsGraph::from_links(links, unitigs.len() as u64),
+            kmers_counts: unitigs.iter().map(|(kmers, _)| *kmers).collect(),
+            abundances: unitigs.iter().map(|(_, abundance)| *abundance).collect(),
+            removed: vec![false; unitigs.len()],
+        }
+    }
+
+    fn placements(graph: &CleaningGraph) -> Vec<Option<(u64, u64, bool)>> {
+        let (placements, _) = graph.compact();
+        placements
+            .iter()
+            .map(|p| p.map(|p| (p.joined, p.position, p.rc)))
+            .collect()
+    }
+
+    #[test]
+    fn tips_clipping() {
+        // 0 branches to 1 and to the short tip 2, 1 is joined reverse complemented
+        let mut graph = build_graph(
+            &[(100, 1000), (100, 1000), (5, 50)],
+            &[(0, false, 1, true), (0, false, 2, false)],
+        );
+
+        assert_eq!(graph.clip_tips(10), 1);
+        assert!(graph.removed[2]);
+
+        // The remaining unitigs are no longer branching and are compacted again
+        assert_eq!(graph.compact().1, 1);
+        assert_eq!(
+            placements(&graph),
+            vec![Some((0, 0, false)), Some((0, 1, true)), None]
+        );
+    }
+
+    #[test]
+    fn linear_path_ends_kept() {
+        let mut graph = build_graph(&[(100, 1000), (5, 50)], &[(0, false, 1, false)]);
+
+        assert_eq!(graph.clip_tips(10), 0);
+        assert_eq!(graph.compact().1, 1);
+    }
+
+    #[test]
+    fn bubbles_popping() {
+        // 0 -> 1 -> 3 and 0 -> 2 -> 3, with the low coverage branch 2 stored reverse complemented
+        let mut graph = build_graph(
+            &[(100, 1000), (10, 100), (10, 10), (100, 1000)],
+            &[
+                (0, false, 1, false),
+                (1, false, 3, false),
+                (0, false, 2, true),
+                (2, true, 3, false),
+            ],
+        );
+
+        assert_eq!(graph.pop_bubbles(0.5), 1);
+        assert!(!graph.removed[1]);
+        assert!(graph.removed[2]);
+
+        assert_eq!(
+            placements(&graph),
+            vec![
+                Some((0, 0, false)),
+                Some((0, 1, false)),
+                None,
+                Some((0, 2, false))
+            ]
+        );
+    }
+
+    #[test]
+    fn bubbles_branches_with_similar_coverage_kept() {
+        let mut graph = build_graph(
+            &[(100, 1000), (10, 100), (10, 80), (100, 1000)],
+            &[
+                (0, false, 1, false),
+                (1, false, 3, false),
+                (0, false, 2, false),
+                (2, false, 3, false),
+            ],
+        );
+
+        assert_eq!(graph.pop_bubbles(0.5), 0);
+        assert_eq!(graph.compact().1, 4);
+    }
+}
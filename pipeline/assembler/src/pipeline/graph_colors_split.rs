@@ -0,0 +1,364 @@
+use colors::colors_manager::{ColorsParser, MinimizerBucketingSeqColorData};
+use colors::parsers::graph::GraphColorsParser;
+use colors::parsers::SingleSequenceInfo;
+use colors::storage::indexed_reader::ColorsIndexedReader;
+use config::ColorIndexType;
+use hashbrown::HashMap;
+use io::concurrent::temp_reads::extra_data::SequenceExtraDataTempBufferManagement;
+use io::errors::GGCATError;
+use io::reads_writer::ReadsWriter;
+use io::sequences_reader::{FastaSequence, SequencesReader};
+use parallel_processor::phase_times_monitor::PHASES_TIMES_MONITOR;
+use std::cmp::min;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+type GraphColorsData = <GraphColorsParser as ColorsParser>::MinimizerBucketingSeqColorDataType;
+
+/// Maximum number of colors files written at the same time, the graph is read once for each batch
+const SPLIT_COLORS_BATCH_SIZE: usize = 256;
+
+/// Writes the kmers ranges of a unitig pending for each color of the batch
+struct ColorsBatchWriter {
+    batch: Range<usize>,
+    writers: Vec<ReadsWriter>,
+    pending: Vec<Option<Range<usize>>>,
+    touched: Vec<usize>,
+}
+
+impl ColorsBatchWriter {
+    fn add_range(&mut self, color: ColorIndexType, kmers: Range<usize>, seq: &[u8], k: usize) {
+        let color = color as usize;
+        if !self.batch.contains(&color) {
+            return;
+        }
+        let index = color - self.batch.start;
+
+        match &mut self.pending[index] {
+            Some(pending) if pending.end == kmers.start => pending.end = kmers.end,
+            pending => {
+                if let Some(last) = pending.replace(kmers) {
+                    Self::write_range(&mut self.writers[index], last, seq, k);
+                } else {
+                    self.touched.push(index);
+                }
+            }
+        }
+    }
+
+    fn flush(&mut self, seq: &[u8], k: usize) {
+        for index in self.touched.drain(..) {
+            let range = self.pending[index].take().unwrap();
+            Self::write_range(&mut self.writers[index], range, seq, k);
+        }
+    }
+
+    fn write_range(writer: &mut ReadsWriter, kmers: Range<usize>, seq: &[u8], k: usize) {
+        writer.add_read(FastaSequence {
+            ident: b">",
+            seq: &seq[kmers.start..kmers.end + k - 1],
+            qual: None,
+        });
+    }
+}
+
+/// Checks that the colored graphs can be added to a new graph written to `output_file`
+pub fn check_colored_graphs(
+    colored_graphs: &[PathBuf],
+    output_file: &Path,
+) -> Result<(), GGCATError> {
+    let same_file = |a: &Path, b: &Path| {
+        a == b || matches!((a.canonicalize(), b.canonicalize()), (Ok(a), Ok(b)) if a == b)
+    };
+
+    // The colormap of the output is written alongside it, so it must not replace the graph one
+    for graph in colored_graphs {
+        if same_file(graph, output_file)
+            || same_file(
+                &graph.with_extension("colors.dat"),
+                &output_file.with_extension("colors.dat"),
+            )
+        {
+            return Err(GGCATError::InvalidConfig(format!(
+                "the output file cannot overwrite the colored graph '{}' or its colormap",
+                graph.display()
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Splits the kmers of a colored graph in a fasta file for each of its colors, using the colors tags
+/// of the unitigs and the colormap in the same folder. Returns the colors files, in the colors order,
+/// with the colors names
+pub fn split_graph_colors(
+    graph_file: &Path,
+    temp_dir: &Path,
+    graph_index: usize,
+    k: usize,
+) -> Result<(Vec<PathBuf>, Vec<String>), GGCATError> {
+    PHASES_TIMES_MONITOR
+        .write()
+        .start_phase(format!("phase: graph {} colors splitting", graph_index));
+
+    let colormap_file = graph_file.with_extension("colors.dat");
+    let mut colormap = ColorsIndexedReader::new(&colormap_file)?;
+    let color_names = colormap.color_names().to_vec();
+
+    let colors_files: Vec<_> = (0..color_names.len())
+        .map(|color| temp_dir.join(format!("graph{}-color{}.fa.lz4", graph_index, color)))
+        .collect();
+
+    let mut colors_buffer = GraphColorsData::new_temp_buffer();
+    let mut subsets_runs = Vec::new();
+    let mut subset_error = None;
+
+    for batch_start in (0..color_names.len()).step_by(SPLIT_COLORS_BATCH_SIZE) {
+        let batch = batch_start..min(color_names.len(), batch_start + SPLIT_COLORS_BATCH_SIZE);
+
+        let mut batch_writer = ColorsBatchWriter {
+            writers: colors_files[batch.clone()]
+                .iter()
+                .map(|file| ReadsWriter::new_compressed_lz4(file, 2))
+                .collect(),
+            pending: vec![None; batch.len()],
+            touched: Vec::new(),
+            batch,
+        };
+
+        let mut unitig_index = 0;
+
+        SequencesReader::process_file_extended(
+            graph_file,
+            |seq| {
+                if subset_error.is_some() {
+                    return;
+                }
+
+                GraphColorsData::clear_temp_buffer(&mut colors_buffer);
+                let unitig_colors = GraphColorsData::create(
+                    SingleSequenceInfo {
+                        file_index: 0,
+                        sequence_ident: seq.ident,
+                    },
+                    &mut colors_buffer,
+                );
+
+                // Groups the consecutive kmers with the same colors subset
+                subsets_runs.clear();
+                let mut kmers_count = 0;
+                if !colors_buffer.is_empty() {
+                    let mut kmers_subsets = unitig_colors.get_iterator(&colors_buffer).peekable();
+                    while let Some(subset) = kmers_subsets.next() {
+                        let run_start = kmers_count;
+                        kmers_count += 1;
+                        while kmers_subsets.next_if_eq(&subset).is_some() {
+                            kmers_count += 1;
+                        }
+                        subsets_runs.push((subset, run_start..kmers_count));
+                    }
+                }
+
+                if seq.seq.len() < k || kmers_count != seq.seq.len() - k + 1 {
+                    subset_error = Some(GGCATError::InvalidInput {
+                        file: graph_file.to_path_buf(),
+                        line: None,
+                        reason: format!(
+                            "the colors of unitig {} do not match its kmers, is k = {} correct?",
+                            unitig_index, k
+                        ),
+                    });
+                    return;
+                }
+
+                for (subset, kmers) in subsets_runs.iter() {
+                    match colormap.get(*subset) {
+                        Ok(Some(colors)) => {
+                            for color in colors {
+                                batch_writer.add_range(*color, kmers.clone(), seq.seq, k);
+                            }
+                        }
+                        Err(error) => {
+                            subset_error = Some(error);
+                            return;
+                        }
+                        Ok(None) => {
+                            subset_error = Some(GGCATError::InvalidColormap {
+                                file: colormap_file.clone(),
+                                reason: format!(
+                                    "missing color subset {} of unitig {}",
+                                    subset, unitig_index
+                                ),
+                            });
+                            return;
+                        }
+                    }
+                }
+
+                batch_writer.flush(seq.seq, k);
+                unitig_index += 1;
+            },
+            None,
+            true,
+            false,
+            false,
+        )?;
+
+        for writer in batch_writer.writers {
+            writer.finalize();
+        }
+
+        if let Some(error) = subset_error.take() {
+            return Err(error);
+        }
+    }
+
+    Ok((colors_files, color_names))
+}
+
+/// The colors of the existing graphs in the new graph, the colors of each graph follow the ones
+/// of the previous graphs
+pub struct GraphsColors {
+    /// The colors files of the graphs, with the index of their color
+    pub files: Vec<(PathBuf, ColorIndexType)>,
+    pub names: Vec<String>,
+    /// The first color index of each name, if the colors with the same name are merged
+    named_colors: Option<HashMap<String, ColorIndexType>>,
+}
+
+impl GraphsColors {
+    pub fn new(merge_same_names: bool) -> Self {
+        Self {
+            files: Vec::new(),
+            names: Vec::new(),
+            named_colors: merge_same_names.then(HashMap::new),
+        }
+    }
+
+    /// Adds the colors files of a graph, returning the number of its colors merged with the same
+    /// name ones of the previous graphs. The colors of the same graph are never merged together,
+    /// so the colors of the first graph always keep their indices
+    pub fn add_graph(&mut self, colors_files: Vec<PathBuf>, names: Vec<String>) -> usize {
+        let mut merged_count = 0;
+        let mut graph_colors = Vec::new();
+
+        for (file, name) in colors_files.into_iter().zip(names) {
+            let merged_color = self
+                .named_colors
+                .as_ref()
+                .and_then(|named_colors| named_colors.get(&name).copied());
+
+            let color = match merged_color {
+                Some(color) => {
+                    merged_count += 1;
+                    color
+                }
+                None => {
+                    let color = self.names.len() as ColorIndexType;
+                    self.names.push(name.clone());
+                    graph_colors.push((name, color));
+                    color
+                }
+            };
+            self.files.push((file, color));
+        }
+
+        if let Some(named_colors) = &mut self.named_colors {
+            for (name, color) in graph_colors {
+                named_colors.entry(name).or_insert(color);
+            }
+        }
+
+        merged_count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pipeline::graph_colors_split::{check_colored_graphs, GraphsColors};
+    use config::ColorIndexType;
+    use io::errors::GGCATError;
+    use io::test_utils::TestTempDir;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn colored_graphs_not_overwritten() {
+        let temp_dir = TestTempDir::new("updated-graph");
+        let graph = temp_dir.file("graph.fa");
+        std::fs::write(&graph, ">0\nACGT\n").unwrap();
+
+        let graphs = vec![graph.clone()];
+        let same_graph = temp_dir.path().join(".").join("graph.fa");
+
+        assert!(matches!(
+            check_colored_graphs(&graphs, &graph),
+            Err(GGCATError::InvalidConfig(_))
+        ));
+        assert!(matches!(
+            check_colored_graphs(&graphs, &same_graph),
+            Err(GGCATError::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn colored_graphs_colormap_not_overwritten() {
+        let graphs = vec![PathBuf::from("graph.fasta")];
+
+        // Both files would have their colormap in graph.colors.dat
+        assert!(matches!(
+            check_colored_graphs(&graphs, Path::new("graph.fa")),
+            Err(GGCATError::InvalidConfig(_))
+        ));
+        assert!(check_colored_graphs(&graphs, Path::new("new-graph.fasta")).is_ok());
+    }
+
+    fn graph_colors(names: &[&str]) -> (Vec<PathBuf>, Vec<String>) {
+        (
+            names
+                .iter()
+                .map(|name| PathBuf::from(format!("{}.fa", name)))
+                .collect(),
+            names.iter().map(|name| name.to_string()).collect(),
+        )
+    }
+
+    fn add_graphs(merge_same_names: bool) -> (GraphsColors, Vec<usize>) {
+        let mut graphs_colors = GraphsColors::new(merge_same_names);
+        let merged_counts = [&["A", "B", "A"][..], &["C", "A"][..]]
+            .into_iter()
+            .map(|names| {
+                let (files, names) = graph_colors(names);
+                graphs_colors.add_graph(files, names)
+            })
+            .collect();
+        (graphs_colors, merged_counts)
+    }
+
+    fn colors(graphs_colors: &GraphsColors) -> Vec<ColorIndexType> {
+        graphs_colors
+            .files
+            .iter()
+            .map(|(_, color)| *color)
+            .collect()
+    }
+
+    #[test]
+    fn graphs_colors_kept_separate() {
+        let (graphs_colors, merged_counts) = add_graphs(false);
+
+        assert_eq!(merged_counts, vec![0, 0]);
+        assert_eq!(colors(&graphs_colors), vec![0, 1, 2, 3, 4]);
+        assert_eq!(graphs_colors.names, vec!["A", "B", "A", "C", "A"]);
+    }
+
+    #[test]
+    fn graphs_colors_merged_by_name() {
+        let (graphs_colors, merged_counts) = add_graphs(true);
+
+        // The colors of the first graph keep their indices, even with the same name
+        assert_eq!(merged_counts, vec![0, 1]);
+        assert_eq!(colors(&graphs_colors), vec![0, 1, 2, 3, 0]);
+        assert_eq!(graphs_colors.names, vec!["A", "B", "A", "C"]);
+    }
+}
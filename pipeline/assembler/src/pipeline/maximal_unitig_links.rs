@@ -0,0 +1,529 @@
+use crate::structs::maximal_unitig_link::{MaximalUnitigLink, UnitigEndpoint};
+use crate::OutputFileFormat;
+use config::{
+    get_memory_mode, BucketIndexType, SwapPriority, DEFAULT_PER_CPU_BUFFER_SIZE,
+    DEFAULT_PREFETCH_AMOUNT, KEEP_FILES,
+};
+use hashes::HashFunctionFactory;
+use io::errors::GGCATError;
+use io::reads_writer::ReadsWriter;
+use io::sequences_reader::{FastaSequence, SequencesReader};
+use parallel_processor::buckets::concurrent::{BucketsThreadBuffer, BucketsThreadDispatcher};
+use parallel_processor::buckets::readers::lock_free_binary_reader::LockFreeBinaryReader;
+use parallel_processor::buckets::readers::BucketReader;
+use parallel_processor::buckets::writers::lock_free_binary_writer::LockFreeBinaryWriter;
+use parallel_processor::buckets::MultiThreadBuckets;
+use parallel_processor::fast_smart_bucket_sort::{fast_smart_radix_sort, SortKey};
+use parallel_processor::memory_fs::{MemoryFs, RemoveFileMode};
+use parallel_processor::phase_times_monitor::PHASES_TIMES_MONITOR;
+use parallel_processor::utils::scoped_thread_local::ScopedThreadLocal;
+use rayon::iter::IntoParallelRefIterator;
+use rayon::iter::ParallelIterator;
+use siphasher::sip128::{Hasher128, SipHasher13};
+use std::cmp::Ordering as CmpOrdering;
+use std::hash::Hasher;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+pub(crate) fn reverse_complement(seq: &[u8], output: &mut Vec<u8>) {
+    output.clear();
+    output.extend(seq.iter().rev().map(|base| match base {
+        b'A' => b'T',
+        b'C' => b'G',
+        b'G' => b'C',
+        b'T' => b'A',
+        _ => b'N',
+    }));
+}
+
+fn hash_kmer(kmer: &[u8]) -> u128 {
+    let mut hasher = SipHasher13::new();
+    hasher.write(kmer);
+    hasher.finish128().as_u128()
+}
+
+fn compute_endpoint(
+    kmer: &[u8],
+    unitig: u64,
+    is_end: bool,
+    canonical: bool,
+    rc_buffer: &mut Vec<u8>,
+) -> UnitigEndpoint {
+    if !canonical {
+        return UnitigEndpoint::new(hash_kmer(kmer), unitig, is_end, false, false);
+    }
+
+    reverse_complement(kmer, rc_buffer);
+    match kmer.cmp(rc_buffer.as_slice()) {
+        CmpOrdering::Less => UnitigEndpoint::new(hash_kmer(kmer), unitig, is_end, false, false),
+        CmpOrdering::Equal => UnitigEndpoint::new(hash_kmer(kmer), unitig, is_end, false, true),
+        CmpOrdering::Greater => {
+            UnitigEndpoint::new(hash_kmer(rc_buffer), unitig, is_end, true, false)
+        }
+    }
+}
+
+/// Returns the link going out of `from` and entering `to`, if their shared (k-1)-mer matches on the same strand
+fn link_endpoints(
+    from: &UnitigEndpoint,
+    to: &UnitigEndpoint,
+    canonical: bool,
+) -> Option<MaximalUnitigLink> {
+    // Leaving from the beginning of a unitig requires walking it reverse complemented,
+    // entering from its end requires the same
+    let from_rc = !from.is_end();
+    let to_rc = to.is_end();
+
+    if !canonical {
+        return (!from_rc && !to_rc)
+            .then(|| MaximalUnitigLink::new(from.unitig, false, to.unitig, false));
+    }
+
+    let exit_strand = from.is_flipped() ^ from_rc;
+    let entry_strand = to.is_flipped() ^ to_rc;
+
+    (exit_strand == entry_strand || from.is_palindrome())
+        .then(|| MaximalUnitigLink::new(from.unitig, from_rc, to.unitig, to_rc))
+}
+
+fn orientation(rc: bool) -> char {
+    if rc {
+        '-'
+    } else {
+        '+'
+    }
+}
+
+/// Writes the unitig with its links as BCALM2 tags (L:+:id:-)
+fn write_fasta_entry(
+    line_buffer: &mut Vec<u8>,
+    out_file: &mut ReadsWriter,
+    index: u64,
+    ident: &[u8],
+    seq: &[u8],
+    links: &[MaximalUnitigLink],
+) {
+    line_buffer.clear();
+
+    write!(line_buffer, ">{}", index).unwrap();
+    if let Some(pos) = ident.iter().position(|c| *c == b' ') {
+        line_buffer.extend_from_slice(&ident[pos..]);
+    }
+
+    for link in links {
+        write!(
+            line_buffer,
+            " L:{}:{}:{}",
+            orientation(link.from_rc()),
+            link.to,
+            orientation(link.to_rc())
+        )
+        .unwrap();
+    }
+
+    out_file.add_read(FastaSequence {
+        ident: line_buffer,
+        seq,
+        qual: None,
+    });
+}
+
+fn write_gfa_entry(
+    line_buffer: &mut Vec<u8>,
+    colors_buffer: &mut Vec<u8>,
+    out_file: &mut ReadsWriter,
+    index: u64,
+    ident: &[u8],
+    seq: &[u8],
+    links: &[MaximalUnitigLink],
+    k: usize,
+) {
+    line_buffer.clear();
+    colors_buffer.clear();
+
+    write!(line_buffer, "S\t{}\t", index).unwrap();
+    line_buffer.extend_from_slice(seq);
+
+    // Skip the old unitig index, the remaining tags are kept, merging the colors in a single tag
+    let tags = match ident.iter().position(|c| *c == b' ') {
+        Some(pos) => &ident[pos + 1..],
+        None => &[],
+    };
+
+    for tag in tags.split(|c| *c == b' ').filter(|t| !t.is_empty()) {
+        if tag.starts_with(b"C:") {
+            colors_buffer.push(if colors_buffer.is_empty() { b':' } else { b',' });
+            colors_buffer.extend_from_slice(&tag[2..]);
+        } else {
+            line_buffer.push(b'\t');
+            line_buffer.extend_from_slice(tag);
+        }
+    }
+
+    if !colors_buffer.is_empty() {
+        line_buffer.extend_from_slice(b"\tCL:Z");
+        line_buffer.extend_from_slice(colors_buffer);
+    }
+
+    out_file.add_line(line_buffer);
+
+    for link in links {
+        // Every edge is found from both its endpoints, write it only once
+        if link.sort_key() > link.reversed().sort_key() {
+            continue;
+        }
+
+        line_buffer.clear();
+        write!(
+            line_buffer,
+            "L\t{}\t{}\t{}\t{}\t{}M",
+            link.from,
+            orientation(link.from_rc()),
+            link.to,
+            orientation(link.to_rc()),
+            k - 1
+        )
+        .unwrap();
+        out_file.add_line(line_buffer);
+    }
+}
+
+/// Finds the links between the unitigs, returning the links buckets (each one with the links
+/// going out of a contiguous range of `unitigs_per_bucket` unitigs) and the unitigs count.
+///
+/// The links known to the links compaction cannot be reused here: they only join the partial
+/// unitigs split at the buckets boundaries, and are consumed when building the maximal unitigs.
+/// The branching edges between maximal unitigs are never stored, as the kmers merging stops
+/// extending a unitig at a branch without recording the next kmers, and the final unitigs indices
+/// and orientations are known only after build_unitigs. So the edges are found again matching
+/// the (k-1)-mers at the ends of the written unitigs, using a 128 bit hash of their bases as
+/// the merging hash functions are defined only on k-mers
+pub(crate) fn find_links<MH: HashFunctionFactory>(
+    unitigs_file: &Path,
+    temp_dir: &Path,
+    buckets_count: usize,
+    k: usize,
+) -> Result<(Vec<PathBuf>, u64, u64), GGCATError> {
+    let endpoints_buckets = Arc::new(MultiThreadBuckets::<LockFreeBinaryWriter>::new(
+        buckets_count,
+        temp_dir.join("unitigs_endpoints"),
+        &(
+            get_memory_mode(SwapPriority::UnitigEndpoints),
+            LockFreeBinaryWriter::CHECKPOINT_SIZE_UNLIMITED,
+        ),
+    ));
+
+    let mut endpoints_tmp = BucketsThreadDispatcher::new(
+        &endpoints_buckets,
+        BucketsThreadBuffer::new(DEFAULT_PER_CPU_BUFFER_SIZE, buckets_count),
+    );
+
+    let mut unitigs_count: u64 = 0;
+    let mut rc_buffer = Vec::new();
+
+    SequencesReader::process_file_extended(
+        unitigs_file,
+        |seq| {
+            let endpoints = [
+                (false, &seq.seq[..k - 1]),
+                (true, &seq.seq[seq.seq.len() - (k - 1)..]),
+            ];
+
+            for (is_end, kmer) in endpoints {
+                let endpoint =
+                    compute_endpoint(kmer, unitigs_count, is_end, MH::CANONICAL, &mut rc_buffer);
+                endpoints_tmp.add_element(
+                    (endpoint.hash % (buckets_count as u128)) as BucketIndexType,
+                    &(),
+                    &endpoint,
+                );
+            }
+            unitigs_count += 1;
+        },
+        None,
+        false,
+        false,
+        false,
+    )?;
+
+    endpoints_tmp.finalize();
+    let endpoints = endpoints_buckets.finalize();
+
+    let links_buckets = Arc::new(MultiThreadBuckets::<LockFreeBinaryWriter>::new(
+        buckets_count,
+        temp_dir.join("maximal_links"),
+        &(
+            get_memory_mode(SwapPriority::MaximalUnitigLinks),
+            LockFreeBinaryWriter::CHECKPOINT_SIZE_UNLIMITED,
+        ),
+    ));
+
+    // Links are bucketed by their source unitig, to be merged sequentially with the unitigs
+    let unitigs_per_bucket = std::cmp::max(1, unitigs_count.div_ceil(buckets_count as u64));
+
+    let buckets_thread_buffers = ScopedThreadLocal::new(move || {
+        BucketsThreadBuffer::new(DEFAULT_PER_CPU_BUFFER_SIZE, buckets_count)
+    });
+
+    endpoints.par_iter().for_each(|input| {
+        let mut buffers = buckets_thread_buffers.get();
+        let mut links_tmp = BucketsThreadDispatcher::new(&links_buckets, buffers.take());
+
+        let mut endpoints_vec = Vec::new();
+
+        LockFreeBinaryReader::new(
+            input,
+            RemoveFileMode::Remove {
+                remove_fs: !KEEP_FILES.load(Ordering::Relaxed),
+            },
+            DEFAULT_PREFETCH_AMOUNT,
+        )
+        .decode_all_bucket_items::<UnitigEndpoint, _>((), &mut (), |endpoint, _| {
+            endpoints_vec.push(endpoint);
+        });
+
+        parallel_processor::make_comparer!(Compare, UnitigEndpoint, hash: u128);
+        fast_smart_radix_sort::<_, Compare, false>(&mut endpoints_vec[..]);
+
+        for group in endpoints_vec.group_by(|a, b| a.hash == b.hash) {
+            for from in group {
+                for to in group {
+                    if let Some(link) = link_endpoints(from, to, MH::CANONICAL) {
+                        links_tmp.add_element(
+                            (link.from / unitigs_per_bucket) as BucketIndexType,
+                            &(),
+                            &link,
+                        );
+                    }
+                }
+            }
+        }
+
+        buffers.put_back(links_tmp.finalize().0);
+    });
+
+    Ok((links_buckets.finalize(), unitigs_per_bucket, unitigs_count))
+}
+
+/// Computes the links between the maximal unitigs in `unitigs_file` and writes them
+/// to the output file with the unitigs, renumbering them sequentially
+pub fn maximal_unitig_links<MH: HashFunctionFactory>(
+    unitigs_file: PathBuf,
+    out_file: &mut ReadsWriter,
+    output_format: OutputFileFormat,
+    temp_dir: &Path,
+    buckets_count: usize,
+    k: usize,
+) -> Result<(), GGCATError> {
+    PHASES_TIMES_MONITOR
+        .write()
+        .start_phase("phase: maximal unitigs links building".to_string());
+
+    let (links_buckets, unitigs_per_bucket, _) =
+        find_links::<MH>(&unitigs_file, temp_dir, buckets_count, k)?;
+
+    PHASES_TIMES_MONITOR
+        .write()
+        .start_phase("phase: maximal unitigs links writing".to_string());
+
+    if output_format == OutputFileFormat::Gfa {
+        out_file.add_line(b"H\tVN:Z:1.0");
+    }
+
+    let mut links_buckets = links_buckets.into_iter();
+    let mut bucket_links = Vec::new();
+    let mut link_index = 0;
+    let mut unitig_index: u64 = 0;
+
+    let mut line_buffer = Vec::new();
+    let mut colors_buffer = Vec::new();
+
+    SequencesReader::process_file_extended(
+        &unitigs_file,
+        |seq| {
+            if unitig_index % unitigs_per_bucket == 0 {
+                bucket_links.clear();
+                link_index = 0;
+                if let Some(bucket) = links_buckets.next() {
+                    LockFreeBinaryReader::new(
+                        bucket,
+                        RemoveFileMode::Remove {
+                            remove_fs: !KEEP_FILES.load(Ordering::Relaxed),
+                        },
+                        DEFAULT_PREFETCH_AMOUNT,
+                    )
+                    .decode_all_bucket_items::<MaximalUnitigLink, _>(
+                        (),
+                        &mut (),
+                        |link, _| {
+                            bucket_links.push(link);
+                        },
+                    );
+                    bucket_links.sort_unstable_by_key(|link| link.sort_key());
+                }
+            }
+
+            let links_start = link_index;
+            while link_index < bucket_links.len() && bucket_links[link_index].from == unitig_index {
+                link_index += 1;
+            }
+
+            match output_format {
+                OutputFileFormat::Gfa => write_gfa_entry(
+                    &mut line_buffer,
+                    &mut colors_buffer,
+                    out_file,
+                    unitig_index,
+                    seq.ident,
+                    seq.seq,
+                    &bucket_links[links_start..link_index],
+                    k,
+                ),
+                OutputFileFormat::Fasta => write_fasta_entry(
+                    &mut line_buffer,
+                    out_file,
+                    unitig_index,
+                    seq.ident,
+                    seq.seq,
+                    &bucket_links[links_start..link_index],
+                ),
+                OutputFileFormat::Simplitigs => {
+                    unreachable!("simplitigs are written by build_simplitigs")
+                }
+            }
+
+            unitig_index += 1;
+        },
+        None,
+        true,
+        false,
+        !KEEP_FILES.load(Ordering::Relaxed),
+    )?;
+
+    // Buckets after the last unitig are empty
+    for bucket in links_buckets {
+        MemoryFs::remove_file(
+            &bucket,
+            RemoveFileMode::Remove {
+                remove_fs: !KEEP_FILES.load(Ordering::Relaxed),
+            },
+        )
+        .unwrap();
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pipeline::maximal_unitig_links::{
+        compute_endpoint, link_endpoints, write_gfa_entry,
+    };
+    use crate::structs::maximal_unitig_link::MaximalUnitigLink;
+    use io::reads_writer::ReadsWriter;
+    use io::test_utils::TestTempDir;
+
+    /// Finds the links between the unitigs as done by find_links, without buckets
+    fn find_links(unitigs: &[&[u8]], k: usize, canonical: bool) -> Vec<(u64, bool, u64, bool)> {
+        let mut rc_buffer = Vec::new();
+        let endpoints: Vec<_> = unitigs
+            .iter()
+            .enumerate()
+            .flat_map(|(unitig, seq)| {
+                [(false, &seq[..k - 1]), (true, &seq[seq.len() - (k - 1)..])].map(
+                    |(is_end, kmer)| {
+                        compute_endpoint(kmer, unitig as u64, is_end, canonical, &mut rc_buffer)
+                    },
+                )
+            })
+            .collect();
+
+        let mut links: Vec<_> = endpoints
+            .iter()
+            .flat_map(|from| endpoints.iter().map(move |to| (from, to)))
+            .filter(|(from, to)| from.hash == to.hash)
+            .filter_map(|(from, to)| link_endpoints(from, to, canonical))
+            .map(|link| link.sort_key())
+            .collect();
+        links.sort_unstable();
+        links
+    }
+
+    #[test]
+    fn links_orientation() {
+        // The end of the first unitig is the beginning of the second one
+        assert_eq!(
+            find_links(&[b"ACACG", b"ACGGA"], 4, true),
+            vec![(0, false, 1, false), (1, true, 0, true)]
+        );
+
+        // The second unitig is stored reverse complemented
+        assert_eq!(
+            find_links(&[b"ACACG", b"TCCGT"], 4, true),
+            vec![(0, false, 1, true), (1, false, 0, true)]
+        );
+
+        // Without canonical kmers the links are found only from their source
+        assert_eq!(
+            find_links(&[b"ACACG", b"ACGGA"], 4, false),
+            vec![(0, false, 1, false)]
+        );
+        assert!(find_links(&[b"ACACG", b"TCCGT"], 4, false).is_empty());
+    }
+
+    #[test]
+    fn palindromic_links() {
+        // ACGT is its own reverse complement, so it links each unitig to its reverse complement too
+        assert_eq!(
+            find_links(&[b"GGACGT", b"ACGTAA"], 5, true),
+            vec![
+                (0, false, 0, true),
+                (0, false, 1, false),
+                (1, true, 0, true),
+                (1, true, 1, false)
+            ]
+        );
+    }
+
+    #[test]
+    fn gfa_links_written_once() {
+        let temp_dir = TestTempDir::new("gfa-links");
+        let gfa_file = temp_dir.file("links.gfa");
+        let mut writer = ReadsWriter::new_plain(&gfa_file);
+        let mut line_buffer = Vec::new();
+        let mut colors_buffer = Vec::new();
+
+        let unitigs: [(&[u8], Vec<MaximalUnitigLink>); 2] = [
+            (
+                b"GGACGT",
+                vec![
+                    MaximalUnitigLink::new(0, false, 0, true),
+                    MaximalUnitigLink::new(0, false, 1, false),
+                ],
+            ),
+            (b"ACGTAA", vec![MaximalUnitigLink::new(1, true, 0, true)]),
+        ];
+
+        for (index, (seq, links)) in unitigs.iter().enumerate() {
+            write_gfa_entry(
+                &mut line_buffer,
+                &mut colors_buffer,
+                &mut writer,
+                index as u64,
+                format!(">{} C:0:2", index).as_bytes(),
+                seq,
+                links,
+                5,
+            );
+        }
+        writer.finalize();
+
+        let gfa = std::fs::read_to_string(&gfa_file).unwrap();
+
+        let links: Vec<_> = gfa.lines().filter(|line| line.starts_with('L')).collect();
+        assert_eq!(links, vec!["L\t0\t+\t0\t-\t4M", "L\t0\t+\t1\t+\t4M"]);
+        assert!(gfa.contains("S\t0\tGGACGT\tCL:Z:0:2"));
+    }
+}
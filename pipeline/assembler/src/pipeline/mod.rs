@@ -1,5 +1,10 @@
 pub mod build_unitigs;
+pub mod graph_cleaning;
+pub mod graph_colors_split;
 pub mod hashes_sorting;
 pub mod links_compaction;
+pub mod maximal_unitig_links;
 pub mod reorganize_reads;
+pub mod simplitigs;
 pub mod unitig_links_manager;
+pub mod unitigs_graph;
@@ -4,7 +4,7 @@ use config::{
 };
 use hashes::{HashFunctionFactory, HashableSequence, MinimizerHashFunctionFactory};
 
-use crate::pipeline::build_unitigs::write_fasta_entry;
+use crate::pipeline::build_unitigs::{write_fasta_entry, AbundanceOutput, UnitigAbundanceStats};
 use crate::structs::link_mapping::LinkMapping;
 use colors::colors_manager::{color_types, ColorsManager};
 use config::DEFAULT_OUTPUT_BUFFER_SIZE;
@@ -16,6 +16,7 @@ use io::concurrent::temp_reads::extra_data::{
 use io::get_bucket_index;
 use io::reads_writer::ReadsWriter;
 use io::structs::unitig_link::UnitigIndex;
+use kmers_merge::structs::PartialUnitigExtraData;
 use parallel_processor::buckets::concurrent::{BucketsThreadBuffer, BucketsThreadDispatcher};
 use parallel_processor::buckets::readers::compressed_binary_reader::CompressedBinaryReader;
 use parallel_processor::buckets::readers::lock_free_binary_reader::LockFreeBinaryReader;
@@ -117,6 +118,8 @@ pub fn reorganize_reads<
     temp_path: &Path,
     out_file: &Mutex<ReadsWriter>,
     buckets_count: usize,
+    k: usize,
+    abundance_output: AbundanceOutput,
 ) -> (Vec<PathBuf>, PathBuf) {
     PHASES_TIMES_MONITOR
         .write()
@@ -175,9 +178,11 @@ pub fn reorganize_reads<
         let mut decompress_buffer = Vec::new();
 
         let mut fasta_temp_buffer = Vec::new();
+        let mut abundances_buffer = Vec::new();
 
-        let mut colors_buffer =
-            color_types::PartialUnitigsColorStructure::<H, MH, CX>::new_temp_buffer();
+        let mut colors_buffer = PartialUnitigExtraData::<
+            color_types::PartialUnitigsColorStructure<H, MH, CX>,
+        >::new_temp_buffer();
 
         CompressedBinaryReader::new(
             read_file,
@@ -187,7 +192,7 @@ pub fn reorganize_reads<
             DEFAULT_PREFETCH_AMOUNT,
         )
         .decode_all_bucket_items::<CompressedReadsBucketHelper<
-            color_types::PartialUnitigsColorStructure<H, MH, CX>,
+            PartialUnitigExtraData<color_types::PartialUnitigsColorStructure<H, MH, CX>>,
             typenum::U0,
             false,
         >, _>(
@@ -212,7 +217,9 @@ pub fn reorganize_reads<
                         ReorganizedReadsBuffer::from_inner(color_buffer),
                         &CompressedReadsBucketHelper::<
                             ReorganizedReadsExtraData<
-                                color_types::PartialUnitigsColorStructure<H, MH, CX>,
+                                PartialUnitigExtraData<
+                                    color_types::PartialUnitigsColorStructure<H, MH, CX>,
+                                >,
                             >,
                             typenum::U0,
                             false,
@@ -221,19 +228,29 @@ pub fn reorganize_reads<
                     map_index += 1;
                 } else {
                     // No mapping, write unitig to file
+                    abundances_buffer.clear();
+                    abundances_buffer.extend_from_slice(color.get_abundances(color_buffer));
+                    let abundance = UnitigAbundanceStats::new(
+                        abundance_output,
+                        &color.counters,
+                        &mut abundances_buffer,
+                    );
+
                     write_fasta_entry::<H, MH, CX, _>(
                         &mut fasta_temp_buffer,
                         &mut tmp_lonely_unitigs_buffer,
-                        color,
-                        color_buffer,
+                        color.colors,
+                        &color_buffer.colors,
+                        abundance,
                         seq,
                         0,
+                        k,
                     );
                 }
 
-                color_types::PartialUnitigsColorStructure::<H, MH, CX>::clear_temp_buffer(
-                    color_buffer,
-                );
+                PartialUnitigExtraData::<
+                    color_types::PartialUnitigsColorStructure<H, MH, CX>,
+                >::clear_temp_buffer(color_buffer);
 
                 index += 1;
             },
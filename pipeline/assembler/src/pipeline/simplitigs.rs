@@ -0,0 +1,181 @@
+use crate::pipeline::maximal_unitig_links::find_links;
+use crate::pipeline::unitigs_graph::{join_unitigs, UnitigPlacement, UnitigsGraph};
+use hashes::HashFunctionFactory;
+use io::errors::GGCATError;
+use io::reads_writer::ReadsWriter;
+use parallel_processor::phase_times_monitor::PHASES_TIMES_MONITOR;
+use std::path::{Path, PathBuf};
+
+/// Greedily walks the graph from `unitig` (reverse complemented if `rc`) through the unused unitigs
+fn extend(
+    graph: &UnitigsGraph,
+    mut unitig: u64,
+    mut rc: bool,
+    used: &mut [bool],
+    path: &mut Vec<(u64, bool)>,
+) {
+    path.clear();
+    loop {
+        match graph.links(unitig, rc).find(|link| !used[link.to as usize]) {
+            Some(link) => {
+                used[link.to as usize] = true;
+                unitig = link.to;
+                rc = link.to_rc();
+                path.push((unitig, rc));
+            }
+            None => break,
+        }
+    }
+}
+
+/// Chains the unitigs in simplitigs, returning the placement of each unitig and the simplitigs count
+fn chain_unitigs(graph: &UnitigsGraph) -> (Vec<Option<UnitigPlacement>>, u64) {
+    let unitigs_count = graph.unitigs_count();
+    let mut used = vec![false; unitigs_count as usize];
+    let mut placements = vec![None; unitigs_count as usize];
+    let mut forward_path = Vec::new();
+    let mut backward_path = Vec::new();
+    let mut simplitigs_count = 0;
+
+    for start in 0..unitigs_count {
+        if used[start as usize] {
+            continue;
+        }
+        used[start as usize] = true;
+
+        extend(graph, start, false, &mut used, &mut forward_path);
+        extend(graph, start, true, &mut used, &mut backward_path);
+
+        // The backward path is walked in the opposite direction
+        let simplitig_unitigs = backward_path
+            .iter()
+            .rev()
+            .map(|(unitig, rc)| (*unitig, !*rc))
+            .chain(std::iter::once((start, false)))
+            .chain(forward_path.iter().copied());
+
+        for (position, (unitig, rc)) in simplitig_unitigs.enumerate() {
+            placements[unitig as usize] = Some(UnitigPlacement {
+                joined: simplitigs_count,
+                position: position as u64,
+                rc,
+            });
+        }
+        simplitigs_count += 1;
+    }
+
+    (placements, simplitigs_count)
+}
+
+/// Joins the maximal unitigs in `unitigs_file` in simplitigs, greedily following the links between them.
+/// Each kmer appears exactly once in the output, so the colors tags of the unitigs are kept, while the
+/// other tags are dropped
+pub fn build_simplitigs<MH: HashFunctionFactory>(
+    unitigs_file: PathBuf,
+    out_file: &mut ReadsWriter,
+    temp_dir: &Path,
+    buckets_count: usize,
+    k: usize,
+) -> Result<(), GGCATError> {
+    PHASES_TIMES_MONITOR
+        .write()
+        .start_phase("phase: simplitigs building".to_string());
+
+    let (links_buckets, _, unitigs_count) =
+        find_links::<MH>(&unitigs_file, temp_dir, buckets_count, k)?;
+
+    let (placements, simplitigs_count) =
+        chain_unitigs(&UnitigsGraph::load::<MH>(links_buckets, unitigs_count));
+
+    PHASES_TIMES_MONITOR
+        .write()
+        .start_phase("phase: simplitigs writing".to_string());
+
+    join_unitigs(
+        &unitigs_file,
+        &placements,
+        simplitigs_count,
+        out_file,
+        temp_dir,
+        buckets_count,
+        k,
+        false,
+    )?;
+
+    println!(
+        "Joined {} unitigs in {} simplitigs",
+        unitigs_count, simplitigs_count
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pipeline::simplitigs::chain_unitigs;
+    use crate::pipeline::unitigs_graph::UnitigsGraph;
+    use crate::structs::maximal_unitig_link::MaximalUnitigLink;
+
+    /// Chains the unitigs of a graph with the given edges, added in both directions,
+    /// returning the (simplitig, position, rc) placement of each unitig
+    fn chain(unitigs_count: u64, edges: &[(u64, bool, u64, bool)]) -> (Vec<(u64, u64, bool)>, u64) {
+        let links = edges
+            .iter()
+            .flat_map(|(from, from_rc, to, to_rc)| {
+                let link = MaximalUnitigLink::new(*from, *from_rc, *to, *to_rc);
+                [link.reversed(), link]
+            })
+            .collect();
+
+        let (placements, simplitigs_count) =
+            chain_unitigs(&UnitigsGraph::from_links(links, unitigs_count));
+        (
+            placements
+                .iter()
+                .map(|p| {
+                    // Each unitig must be written exactly once
+                    let p = p.unwrap();
+                    (p.joined, p.position, p.rc)
+                })
+                .collect(),
+            simplitigs_count,
+        )
+    }
+
+    #[test]
+    fn linear_path_chained() {
+        // 0 -> rc(1) -> 2
+        assert_eq!(
+            chain(3, &[(0, false, 1, true), (1, true, 2, false)]),
+            (vec![(0, 0, false), (0, 1, true), (0, 2, false)], 1)
+        );
+    }
+
+    #[test]
+    fn path_extended_backwards() {
+        // 2 -> 1 -> 0, starting from 0 the path is walked backwards
+        assert_eq!(
+            chain(3, &[(2, false, 1, false), (1, false, 0, false)]),
+            (vec![(0, 2, false), (0, 1, false), (0, 0, false)], 1)
+        );
+    }
+
+    #[test]
+    fn branches_split() {
+        // 0 -> 1 and 0 -> 2, the second branch is a different simplitig
+        assert_eq!(
+            chain(3, &[(0, false, 1, false), (0, false, 2, false)]),
+            (vec![(0, 0, false), (0, 1, false), (1, 0, false)], 2)
+        );
+    }
+
+    #[test]
+    fn cycles_chained_once() {
+        assert_eq!(
+            chain(2, &[(0, false, 1, false), (1, false, 0, false)]),
+            (vec![(0, 0, false), (0, 1, false)], 1)
+        );
+        // Unitigs without links are simplitigs by themselves
+        assert_eq!(chain(2, &[]), (vec![(0, 0, false), (1, 0, false)], 2));
+    }
+}
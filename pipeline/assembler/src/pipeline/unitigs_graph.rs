@@ -0,0 +1,277 @@
+use crate::pipeline::maximal_unitig_links::reverse_complement;
+use crate::structs::joined_unitig::JoinedUnitig;
+use crate::structs::maximal_unitig_link::MaximalUnitigLink;
+use config::{
+    get_memory_mode, BucketIndexType, SwapPriority, DEFAULT_PER_CPU_BUFFER_SIZE,
+    DEFAULT_PREFETCH_AMOUNT, KEEP_FILES,
+};
+use hashes::HashFunctionFactory;
+use io::errors::GGCATError;
+use io::reads_writer::ReadsWriter;
+use io::sequences_reader::{FastaSequence, SequencesReader};
+use parallel_processor::buckets::concurrent::{BucketsThreadBuffer, BucketsThreadDispatcher};
+use parallel_processor::buckets::readers::lock_free_binary_reader::LockFreeBinaryReader;
+use parallel_processor::buckets::readers::BucketReader;
+use parallel_processor::buckets::writers::lock_free_binary_writer::LockFreeBinaryWriter;
+use parallel_processor::buckets::MultiThreadBuckets;
+use parallel_processor::memory_fs::RemoveFileMode;
+use std::cmp::min;
+use std::io::Write;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+/// Links between the maximal unitigs, grouped by source unitig
+pub struct UnitigsGraph {
+    links: Vec<MaximalUnitigLink>,
+    first_link: Vec<usize>,
+}
+
+impl UnitigsGraph {
+    pub fn load<MH: HashFunctionFactory>(links_buckets: Vec<PathBuf>, unitigs_count: u64) -> Self {
+        let mut links = Vec::new();
+
+        for bucket in links_buckets {
+            LockFreeBinaryReader::new(
+                bucket,
+                RemoveFileMode::Remove {
+                    remove_fs: !KEEP_FILES.load(Ordering::Relaxed),
+                },
+                DEFAULT_PREFETCH_AMOUNT,
+            )
+            .decode_all_bucket_items::<MaximalUnitigLink, _>((), &mut (), |link, _| {
+                // Without canonical kmers the links are found only from their source,
+                // the reversed ones are needed to walk the graph backwards
+                if !MH::CANONICAL {
+                    links.push(link.reversed());
+                }
+                links.push(link);
+            });
+        }
+
+        Self::from_links(links, unitigs_count)
+    }
+
+    /// Builds the graph from all its links, each edge must be present in both directions
+    pub fn from_links(mut links: Vec<MaximalUnitigLink>, unitigs_count: u64) -> Self {
+        links.sort_unstable_by_key(|link| link.sort_key());
+
+        let mut first_link = Vec::with_capacity(unitigs_count as usize + 1);
+        let mut link_index = 0;
+        for unitig in 0..=unitigs_count {
+            while link_index < links.len() && links[link_index].from < unitig {
+                link_index += 1;
+            }
+            first_link.push(link_index);
+        }
+
+        Self { links, first_link }
+    }
+
+    pub fn unitigs_count(&self) -> u64 {
+        self.first_link.len() as u64 - 1
+    }
+
+    /// Links leaving `unitig` when it is walked reverse complemented (if `rc`) or forward
+    pub fn links(&self, unitig: u64, rc: bool) -> impl Iterator<Item = &MaximalUnitigLink> {
+        self.links[self.first_link[unitig as usize]..self.first_link[unitig as usize + 1]]
+            .iter()
+            .filter(move |link| link.from_rc() == rc)
+    }
+}
+
+/// Position of a maximal unitig in a joined sequence
+#[derive(Clone, Copy, Default)]
+pub struct UnitigPlacement {
+    pub joined: u64,
+    pub position: u64,
+    pub rc: bool,
+}
+
+/// Returns the total kmers abundance (KC:i:) of a unitig header, or 0 if missing
+pub fn parse_total_abundance(ident: &[u8]) -> u64 {
+    ident
+        .split(|c| *c == b' ')
+        .find_map(|tag| tag.strip_prefix(b"KC:i:"))
+        .and_then(|value| std::str::from_utf8(value).ok()?.parse().ok())
+        .unwrap_or(0)
+}
+
+/// Copies the colors tags of a unitig header, reversing their order if the unitig is reverse complemented
+fn extract_color_tags(ident: &[u8], rc: bool, output: &mut Vec<u8>) {
+    output.clear();
+
+    // Skip the unitig index
+    let tags = match ident.iter().position(|c| *c == b' ') {
+        Some(pos) => &ident[pos + 1..],
+        None => &[],
+    };
+    let tags = tags
+        .split(|c| *c == b' ')
+        .filter(|tag| tag.starts_with(b"C:"));
+
+    let mut append_tag = |tag: &[u8]| {
+        output.push(b' ');
+        output.extend_from_slice(tag);
+    };
+
+    if rc {
+        tags.rev().for_each(&mut append_tag);
+    } else {
+        tags.for_each(&mut append_tag);
+    }
+}
+
+/// Writes the maximal unitigs of `unitigs_file` joined in `joined_count` sequences as specified by
+/// `placements`, overlapping by k - 1 bases. The unitigs without a placement are discarded.
+/// The colors tags of the unitigs are kept and, if `with_abundance` is set, the total and mean
+/// kmers abundance of each joined sequence is computed from the unitigs ones
+pub fn join_unitigs(
+    unitigs_file: &Path,
+    placements: &[Option<UnitigPlacement>],
+    joined_count: u64,
+    out_file: &mut ReadsWriter,
+    temp_dir: &Path,
+    buckets_count: usize,
+    k: usize,
+    with_abundance: bool,
+) -> Result<(), GGCATError> {
+    let joined_buckets = Arc::new(MultiThreadBuckets::<LockFreeBinaryWriter>::new(
+        buckets_count,
+        temp_dir.join("joined_unitigs"),
+        &(
+            get_memory_mode(SwapPriority::JoinedUnitigs),
+            LockFreeBinaryWriter::CHECKPOINT_SIZE_UNLIMITED,
+        ),
+    ));
+
+    let mut joined_tmp = BucketsThreadDispatcher::new(
+        &joined_buckets,
+        BucketsThreadBuffer::new(DEFAULT_PER_CPU_BUFFER_SIZE, buckets_count),
+    );
+
+    // Each bucket holds a contiguous range of joined sequences, to write them in order
+    let get_bucket = |joined: u64| {
+        min(
+            buckets_count as u64 - 1,
+            joined * buckets_count as u64 / joined_count.max(1),
+        ) as BucketIndexType
+    };
+
+    let mut unitig_index = 0;
+    let mut rc_buffer = Vec::new();
+    let mut tags_buffer = Vec::new();
+
+    SequencesReader::process_file_extended(
+        unitigs_file,
+        |seq| {
+            if let Some(placement) = placements[unitig_index] {
+                let sequence = if placement.rc {
+                    reverse_complement(seq.seq, &mut rc_buffer);
+                    rc_buffer.as_slice()
+                } else {
+                    seq.seq
+                };
+                extract_color_tags(seq.ident, placement.rc, &mut tags_buffer);
+
+                joined_tmp.add_element(
+                    get_bucket(placement.joined),
+                    &(),
+                    &JoinedUnitig {
+                        joined: placement.joined,
+                        position: placement.position,
+                        abundance: parse_total_abundance(seq.ident),
+                        sequence,
+                        tags: &tags_buffer,
+                    },
+                );
+            }
+            unitig_index += 1;
+        },
+        None,
+        true,
+        false,
+        !KEEP_FILES.load(Ordering::Relaxed),
+    )?;
+
+    joined_tmp.finalize();
+
+    let mut data_buffer = Vec::new();
+    let mut bucket_unitigs: Vec<(u64, u64, u64, Range<usize>, Range<usize>)> = Vec::new();
+    let mut ident_buffer = Vec::new();
+    let mut sequence_buffer = Vec::new();
+    let mut joined_tags = Vec::new();
+
+    for bucket in joined_buckets.finalize() {
+        data_buffer.clear();
+        bucket_unitigs.clear();
+
+        LockFreeBinaryReader::new(
+            bucket,
+            RemoveFileMode::Remove {
+                remove_fs: !KEEP_FILES.load(Ordering::Relaxed),
+            },
+            DEFAULT_PREFETCH_AMOUNT,
+        )
+        .decode_all_bucket_items::<JoinedUnitig, _>(Vec::new(), &mut (), |unitig, _| {
+            let sequence_start = data_buffer.len();
+            data_buffer.extend_from_slice(unitig.sequence);
+            let tags_start = data_buffer.len();
+            data_buffer.extend_from_slice(unitig.tags);
+
+            bucket_unitigs.push((
+                unitig.joined,
+                unitig.position,
+                unitig.abundance,
+                sequence_start..tags_start,
+                tags_start..data_buffer.len(),
+            ));
+        });
+
+        bucket_unitigs.sort_unstable_by_key(|(joined, position, _, _, _)| (*joined, *position));
+
+        for joined in bucket_unitigs.group_by(|a, b| a.0 == b.0) {
+            sequence_buffer.clear();
+            joined_tags.clear();
+            let mut total_abundance = 0;
+
+            for (index, (_, _, abundance, sequence, tags)) in joined.iter().enumerate() {
+                // Consecutive unitigs overlap by k - 1 bases
+                let skip = if index == 0 { 0 } else { k - 1 };
+                sequence_buffer
+                    .extend_from_slice(&data_buffer[sequence.start + skip..sequence.end]);
+                joined_tags.extend_from_slice(&data_buffer[tags.clone()]);
+                total_abundance += abundance;
+            }
+
+            ident_buffer.clear();
+            write!(
+                ident_buffer,
+                ">{} LN:i:{}",
+                joined[0].0,
+                sequence_buffer.len()
+            )
+            .unwrap();
+            if with_abundance {
+                let kmers_count = sequence_buffer.len() - k + 1;
+                write!(
+                    ident_buffer,
+                    " KC:i:{} km:f:{:.1}",
+                    total_abundance,
+                    total_abundance as f64 / kmers_count as f64
+                )
+                .unwrap();
+            }
+            ident_buffer.extend_from_slice(&joined_tags);
+
+            out_file.add_read(FastaSequence {
+                ident: &ident_buffer,
+                seq: &sequence_buffer,
+                qual: None,
+            });
+        }
+    }
+
+    Ok(())
+}
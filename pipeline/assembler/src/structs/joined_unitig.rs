@@ -0,0 +1,69 @@
+use byteorder::ReadBytesExt;
+use io::varint::{decode_varint, encode_varint, VARINT_MAX_SIZE};
+use parallel_processor::buckets::bucket_writer::BucketItem;
+use std::io::{Read, Write};
+
+/// A maximal unitig placed in a longer joined sequence, with its sequence and colors tags
+/// already in the orientation of the joined sequence
+#[derive(Clone, Debug)]
+pub struct JoinedUnitig<'a> {
+    pub joined: u64,
+    pub position: u64,
+    /// Total abundance of the unitig kmers, if known
+    pub abundance: u64,
+    pub sequence: &'a [u8],
+    pub tags: &'a [u8],
+}
+
+impl<'a> BucketItem for JoinedUnitig<'a> {
+    type ExtraData = ();
+    type ReadBuffer = Vec<u8>;
+    type ExtraDataBuffer = ();
+    type ReadType<'b> = JoinedUnitig<'b>;
+
+    #[inline(always)]
+    fn write_to(
+        &self,
+        bucket: &mut Vec<u8>,
+        _extra_data: &Self::ExtraData,
+        _: &Self::ExtraDataBuffer,
+    ) {
+        encode_varint(|b| bucket.write_all(b), self.joined).unwrap();
+        encode_varint(|b| bucket.write_all(b), self.position).unwrap();
+        encode_varint(|b| bucket.write_all(b), self.abundance).unwrap();
+        encode_varint(|b| bucket.write_all(b), self.sequence.len() as u64).unwrap();
+        encode_varint(|b| bucket.write_all(b), self.tags.len() as u64).unwrap();
+        bucket.extend_from_slice(self.sequence);
+        bucket.extend_from_slice(self.tags);
+    }
+
+    fn read_from<'b, S: Read>(
+        mut stream: S,
+        read_buffer: &'b mut Self::ReadBuffer,
+        _: &mut Self::ExtraDataBuffer,
+    ) -> Option<Self::ReadType<'b>> {
+        let joined = decode_varint(|| stream.read_u8().ok())?;
+        let position = decode_varint(|| stream.read_u8().ok())?;
+        let abundance = decode_varint(|| stream.read_u8().ok())?;
+        let sequence_len = decode_varint(|| stream.read_u8().ok())? as usize;
+        let tags_len = decode_varint(|| stream.read_u8().ok())? as usize;
+
+        read_buffer.clear();
+        read_buffer.resize(sequence_len + tags_len, 0);
+        stream.read_exact(read_buffer).ok()?;
+
+        let (sequence, tags) = read_buffer.split_at(sequence_len);
+        Some(JoinedUnitig {
+            joined,
+            position,
+            abundance,
+            sequence,
+            tags,
+        })
+    }
+
+    #[inline(always)]
+    fn get_size(&self, _: &()) -> usize {
+        VARINT_MAX_SIZE * 5 + self.sequence.len() + self.tags.len()
+    }
+}
@@ -0,0 +1,158 @@
+use byteorder::ReadBytesExt;
+use io::varint::{decode_varint, encode_varint, VARINT_MAX_SIZE};
+use parallel_processor::buckets::bucket_writer::BucketItem;
+use std::io::{Read, Write};
+use std::mem::size_of;
+
+/// A (k-1)-mer at the beginning or at the end of a maximal unitig
+#[derive(Clone, Debug)]
+pub struct UnitigEndpoint {
+    pub hash: u128,
+    pub unitig: u64,
+    flags: u8,
+}
+
+impl UnitigEndpoint {
+    const END_FLAG: u8 = 1;
+    const FLIPPED_FLAG: u8 = 2;
+    const PALINDROME_FLAG: u8 = 4;
+
+    pub fn new(hash: u128, unitig: u64, is_end: bool, flipped: bool, palindrome: bool) -> Self {
+        Self {
+            hash,
+            unitig,
+            flags: (is_end as u8 * Self::END_FLAG)
+                | (flipped as u8 * Self::FLIPPED_FLAG)
+                | (palindrome as u8 * Self::PALINDROME_FLAG),
+        }
+    }
+
+    /// True if the (k-1)-mer is the suffix of the unitig, false if it is the prefix
+    pub fn is_end(&self) -> bool {
+        self.flags & Self::END_FLAG != 0
+    }
+
+    /// True if the hashed (k-1)-mer is the reverse complement of the one in the unitig
+    pub fn is_flipped(&self) -> bool {
+        self.flags & Self::FLIPPED_FLAG != 0
+    }
+
+    /// True if the (k-1)-mer is equal to its reverse complement
+    pub fn is_palindrome(&self) -> bool {
+        self.flags & Self::PALINDROME_FLAG != 0
+    }
+}
+
+impl BucketItem for UnitigEndpoint {
+    type ExtraData = ();
+    type ReadBuffer = ();
+    type ExtraDataBuffer = ();
+    type ReadType<'a> = Self;
+
+    #[inline(always)]
+    fn write_to(
+        &self,
+        bucket: &mut Vec<u8>,
+        _extra_data: &Self::ExtraData,
+        _: &Self::ExtraDataBuffer,
+    ) {
+        bucket.write_all(&self.hash.to_le_bytes()).unwrap();
+        encode_varint(|b| bucket.write_all(b), self.unitig).unwrap();
+        bucket.push(self.flags);
+    }
+
+    fn read_from<'a, S: Read>(
+        mut stream: S,
+        _read_buffer: &'a mut Self::ReadBuffer,
+        _: &mut Self::ExtraDataBuffer,
+    ) -> Option<Self::ReadType<'a>> {
+        let mut hash = [0; size_of::<u128>()];
+        stream.read_exact(&mut hash).ok()?;
+        let unitig = decode_varint(|| stream.read_u8().ok())?;
+        let flags = stream.read_u8().ok()?;
+        Some(Self {
+            hash: u128::from_le_bytes(hash),
+            unitig,
+            flags,
+        })
+    }
+
+    #[inline(always)]
+    fn get_size(&self, _: &()) -> usize {
+        size_of::<u128>() + VARINT_MAX_SIZE + 1
+    }
+}
+
+/// An edge of the graph between two maximal unitigs, with their orientations
+#[derive(Clone, Debug)]
+pub struct MaximalUnitigLink {
+    pub from: u64,
+    pub to: u64,
+    flags: u8,
+}
+
+impl MaximalUnitigLink {
+    const FROM_RC_FLAG: u8 = 1;
+    const TO_RC_FLAG: u8 = 2;
+
+    pub fn new(from: u64, from_rc: bool, to: u64, to_rc: bool) -> Self {
+        Self {
+            from,
+            to,
+            flags: (from_rc as u8 * Self::FROM_RC_FLAG) | (to_rc as u8 * Self::TO_RC_FLAG),
+        }
+    }
+
+    pub fn from_rc(&self) -> bool {
+        self.flags & Self::FROM_RC_FLAG != 0
+    }
+
+    pub fn to_rc(&self) -> bool {
+        self.flags & Self::TO_RC_FLAG != 0
+    }
+
+    /// The same edge, traversed in the opposite direction
+    pub fn reversed(&self) -> Self {
+        Self::new(self.to, !self.to_rc(), self.from, !self.from_rc())
+    }
+
+    /// Key used to get a deterministic ordering of the links
+    pub fn sort_key(&self) -> (u64, bool, u64, bool) {
+        (self.from, self.from_rc(), self.to, self.to_rc())
+    }
+}
+
+impl BucketItem for MaximalUnitigLink {
+    type ExtraData = ();
+    type ReadBuffer = ();
+    type ExtraDataBuffer = ();
+    type ReadType<'a> = Self;
+
+    #[inline(always)]
+    fn write_to(
+        &self,
+        bucket: &mut Vec<u8>,
+        _extra_data: &Self::ExtraData,
+        _: &Self::ExtraDataBuffer,
+    ) {
+        encode_varint(|b| bucket.write_all(b), self.from).unwrap();
+        encode_varint(|b| bucket.write_all(b), self.to).unwrap();
+        bucket.push(self.flags);
+    }
+
+    fn read_from<'a, S: Read>(
+        mut stream: S,
+        _read_buffer: &'a mut Self::ReadBuffer,
+        _: &mut Self::ExtraDataBuffer,
+    ) -> Option<Self::ReadType<'a>> {
+        let from = decode_varint(|| stream.read_u8().ok())?;
+        let to = decode_varint(|| stream.read_u8().ok())?;
+        let flags = stream.read_u8().ok()?;
+        Some(Self { from, to, flags })
+    }
+
+    #[inline(always)]
+    fn get_size(&self, _: &()) -> usize {
+        VARINT_MAX_SIZE * 2 + 1
+    }
+}
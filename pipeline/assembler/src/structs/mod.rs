@@ -1 +1,3 @@
+pub mod joined_unitig;
 pub mod link_mapping;
+pub mod maximal_unitig_link;
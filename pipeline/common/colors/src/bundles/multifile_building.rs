@@ -27,6 +27,14 @@ impl ColorsManager for ColorBundleMultifileBuilding {
         )
     }
 
+    #[inline(always)]
+    fn is_kept_color(
+        color: &Self::SingleKmerColorDataType,
+        kept_colors_count: ColorIndexType,
+    ) -> bool {
+        *color < kept_colors_count
+    }
+
     type ColorsParserType = SeparateColorsParser;
     type ColorsMergeManagerType<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory> =
         MultipleColorsManager<H, MH>;
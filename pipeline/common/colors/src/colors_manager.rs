@@ -1,9 +1,10 @@
 use crate::parsers::SingleSequenceInfo;
-use config::BucketIndexType;
+use config::{BucketIndexType, ColorIndexType};
 use hashbrown::HashMap;
 use hashes::{HashFunctionFactory, MinimizerHashFunctionFactory};
 use io::compressed_read::CompressedRead;
 use io::concurrent::temp_reads::extra_data::SequenceExtraData;
+use io::errors::GGCATError;
 use static_dispatch::static_dispatch;
 use std::cmp::min;
 use std::hash::Hash;
@@ -116,8 +117,10 @@ pub trait ColorsMergeManager<H: MinimizerHashFunctionFactory, MH: HashFunctionFa
         color_names: Vec<String>,
     ) -> Self::GlobalColorsTableWriter;
 
-    /// Creates a new colors table at the given path
-    fn open_colors_table(path: impl AsRef<Path>) -> Self::GlobalColorsTableReader;
+    /// Opens an existing colors table at the given path
+    fn open_colors_table(
+        path: impl AsRef<Path>,
+    ) -> Result<Self::GlobalColorsTableReader, GGCATError>;
 
     /// Prints to stdout the final stats for the colors table
     fn print_color_stats(global_colors_table: &Self::GlobalColorsTableWriter);
@@ -152,6 +155,7 @@ pub trait ColorsMergeManager<H: MinimizerHashFunctionFactory, MH: HashFunctionFa
         map: &mut HashMap<MH::HashTypeUnextendable, MapEntry<Self::HashMapTempColorIndex>>,
         k: usize,
         min_multiplicity: usize,
+        max_multiplicity: usize,
     );
 
     /// Struct used to hold color information about unitigs
@@ -233,6 +237,16 @@ pub trait ColorsManager: 'static + Sync + Send + Sized {
         buckets_count_log: u32,
     ) -> BucketIndexType;
 
+    /// True if the kmers with this color are kept regardless of their multiplicity,
+    /// as the first colors are the ones of the updated graphs
+    #[inline(always)]
+    fn is_kept_color(
+        _color: &Self::SingleKmerColorDataType,
+        _kept_colors_count: ColorIndexType,
+    ) -> bool {
+        false
+    }
+
     type ColorsParserType: ColorsParser<SingleKmerColorDataType = Self::SingleKmerColorDataType>;
     type ColorsMergeManagerType<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory>: ColorsMergeManager<
         H,
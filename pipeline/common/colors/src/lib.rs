@@ -6,8 +6,6 @@
 #![feature(let_chains)]
 #![feature(int_log)]
 
-use crate::storage::run_length::RunLengthColorsSerializer;
-
 pub mod bundles;
 pub mod colors_manager;
 pub mod colors_memmap_writer;
@@ -18,4 +16,8 @@ pub mod storage;
 
 pub(crate) mod async_slice_queue;
 
-pub type DefaultColorsSerializer = RunLengthColorsSerializer;
+/// Encoding of the written colormaps, the readers support all the encodings
+#[cfg(not(feature = "roaring-colors"))]
+pub type DefaultColorsSerializer = storage::run_length::RunLengthColorsSerializer;
+#[cfg(feature = "roaring-colors")]
+pub type DefaultColorsSerializer = storage::roaring::RoaringColorsSerializer;
@@ -13,6 +13,7 @@ use io::compressed_read::CompressedRead;
 use io::concurrent::temp_reads::extra_data::{
     SequenceExtraData, SequenceExtraDataTempBufferManagement,
 };
+use io::errors::GGCATError;
 use io::varint::{decode_varint, encode_varint, VARINT_MAX_SIZE};
 use parallel_processor::buckets::readers::compressed_binary_reader::CompressedBinaryReader;
 use parallel_processor::buckets::writers::compressed_binary_writer::CompressedBinaryWriter;
@@ -127,8 +128,10 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory> ColorsMergeManage
         ColorsMemMapWriter::new(path, color_names)
     }
 
-    fn open_colors_table(_path: impl AsRef<Path>) -> Self::GlobalColorsTableReader {
-        ()
+    fn open_colors_table(
+        _path: impl AsRef<Path>,
+    ) -> Result<Self::GlobalColorsTableReader, GGCATError> {
+        Ok(())
     }
 
     fn print_color_stats(global_colors_table: &Self::GlobalColorsTableWriter) {
@@ -213,6 +216,7 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory> ColorsMergeManage
         map: &mut HashMap<MH::HashTypeUnextendable, MapEntry<Self::HashMapTempColorIndex>>,
         k: usize,
         min_multiplicity: usize,
+        max_multiplicity: usize,
     ) {
         for buffer in data.sequences.iter_mut() {
             data.temp_colors_buffer.clear();
@@ -258,7 +262,21 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory> ColorsMergeManage
                         tmp
                     };
 
-                    if entry.get_kmer_multiplicity() < min_multiplicity {
+                    // The kmers of the updated graphs are kept regardless of their multiplicity
+                    let always_kept = entry.is_always_kept();
+
+                    let multiplicity = entry.get_kmer_multiplicity();
+                    if !always_kept && multiplicity < min_multiplicity {
+                        continue;
+                    }
+
+                    // Already visited kmers have their counter replaced by the color index,
+                    // the ones above the maximum multiplicity are discarded by resetting it
+                    if !always_kept
+                        && entry.get_counter() & VISITED_BIT == 0
+                        && multiplicity > max_multiplicity
+                    {
+                        entry.set_counter_after_check(0);
                         continue;
                     }
 
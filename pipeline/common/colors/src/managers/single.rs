@@ -1,6 +1,5 @@
 use crate::colors_manager::ColorsMergeManager;
 use crate::storage::deserializer::ColorsDeserializer;
-use crate::DefaultColorsSerializer;
 use byteorder::ReadBytesExt;
 use config::ColorIndexType;
 use hashbrown::HashMap;
@@ -9,6 +8,7 @@ use io::compressed_read::CompressedRead;
 use io::concurrent::temp_reads::extra_data::{
     SequenceExtraData, SequenceExtraDataTempBufferManagement,
 };
+use io::errors::GGCATError;
 use io::varint::{decode_varint, encode_varint, VARINT_MAX_SIZE};
 use std::collections::VecDeque;
 use std::io::{Read, Write};
@@ -26,7 +26,7 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory> ColorsMergeManage
 {
     type SingleKmerColorDataType = ColorIndexType;
     type GlobalColorsTableWriter = ();
-    type GlobalColorsTableReader = ColorsDeserializer<DefaultColorsSerializer>;
+    type GlobalColorsTableReader = ColorsDeserializer;
 
     fn create_colors_table(
         _path: impl AsRef<Path>,
@@ -35,7 +35,9 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory> ColorsMergeManage
         ()
     }
 
-    fn open_colors_table(path: impl AsRef<Path>) -> Self::GlobalColorsTableReader {
+    fn open_colors_table(
+        path: impl AsRef<Path>,
+    ) -> Result<Self::GlobalColorsTableReader, GGCATError> {
         ColorsDeserializer::new(path)
     }
 
@@ -86,6 +88,7 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory> ColorsMergeManage
         _map: &mut HashMap<MH::HashTypeUnextendable, MapEntry<Self::HashMapTempColorIndex>>,
         _k: usize,
         _min_multiplicity: usize,
+        _max_multiplicity: usize,
     ) {
     }
 
@@ -7,6 +7,7 @@ use hashbrown::HashMap;
 use hashes::{HashFunctionFactory, MinimizerHashFunctionFactory};
 use io::compressed_read::CompressedRead;
 use io::concurrent::temp_reads::extra_data::SequenceExtraData;
+use io::errors::GGCATError;
 use static_dispatch::static_dispatch;
 use std::io::{Read, Write};
 use std::ops::Range;
@@ -99,8 +100,10 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory> ColorsMergeManage
         ()
     }
 
-    fn open_colors_table(_path: impl AsRef<Path>) -> Self::GlobalColorsTableReader {
-        ()
+    fn open_colors_table(
+        _path: impl AsRef<Path>,
+    ) -> Result<Self::GlobalColorsTableReader, GGCATError> {
+        Ok(())
     }
 
     fn print_color_stats(_global_colors_table: &Self::GlobalColorsTableWriter) {}
@@ -151,6 +154,7 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory> ColorsMergeManage
         >,
         _k: usize,
         _min_multiplicity: usize,
+        _max_multiplicity: usize,
     ) {
         unreachable!()
     }
@@ -208,15 +208,27 @@ impl SequenceExtraData for MinBkMultipleColors {
     }
 }
 
-fn parse_colors(ident: &[u8], colors_buffer: &mut Vec<(usize, ColorIndexType)>) -> Range<usize> {
-    let mut colors_count = 0;
-    for col_pos in ident.find_iter(b"C:") {
+/// Calls `callback` with the colors subset and the kmers count of each `C:{subset}:{count}`
+/// tag of a unitig header
+pub fn for_each_color_tag(ident: &[u8], mut callback: impl FnMut(ColorIndexType, usize)) {
+    // Only match the color tags, skipping other tags ending with 'C' as KC:i:
+    for col_pos in ident
+        .find_iter(b"C:")
+        .filter(|pos| *pos == 0 || ident[*pos - 1] == b' ')
+    {
         let (color_index, next_pos) = ColorIndexType::from_radix_16(&ident[(col_pos + 2)..]);
 
         let kmers_count = usize::from_radix_10(&ident[(col_pos + next_pos + 3)..]).0;
+        callback(color_index, kmers_count);
+    }
+}
+
+fn parse_colors(ident: &[u8], colors_buffer: &mut Vec<(usize, ColorIndexType)>) -> Range<usize> {
+    let mut colors_count = 0;
+    for_each_color_tag(ident, |color_index, kmers_count| {
         colors_buffer.push((kmers_count, color_index));
         colors_count += kmers_count
-    }
+    });
     if colors_count == 0 {
         println!("Warn: 0 colors for {:?}", std::str::from_utf8(ident));
     }
@@ -338,4 +350,25 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn graph_colors_with_bcalm_tags() {
+        let input_ident = ">0 LN:i:34 KC:i:40 km:f:10.0 C:1:2 C:a:2 L:+:1:-";
+
+        let mut extra_buffer = Vec::new();
+
+        let colors = MinBkMultipleColors::create(
+            SingleSequenceInfo {
+                file_index: 0,
+                sequence_ident: input_ident.as_bytes(),
+            },
+            &mut extra_buffer,
+        );
+
+        assert_eq!(colors.debug_count(), 4);
+        assert_eq!(
+            colors.get_iterator(&extra_buffer).collect::<Vec<_>>(),
+            vec![1, 1, 0xa, 0xa]
+        );
+    }
 }
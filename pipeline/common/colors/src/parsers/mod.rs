@@ -2,6 +2,7 @@ pub mod graph;
 pub mod separate;
 
 pub struct SingleSequenceInfo<'a> {
+    /// Index of the input file, or of its color when multiple files share the same color
     pub file_index: usize,
     pub sequence_ident: &'a [u8],
 }
@@ -1,57 +1,93 @@
 use crate::colors_manager::ColorMapReader;
-use crate::storage::serializer::{ColorsFileHeader, ColorsIndexEntry, ColorsIndexMap};
-use crate::storage::ColorsSerializerTrait;
+use crate::storage::serializer::{
+    ColorsFileHeader, ColorsIndexEntry, ColorsIndexMap, STORAGE_VERSION,
+};
+use crate::storage::ColorsEncoding;
 use config::ColorIndexType;
 use desse::Desse;
 use desse::DesseSized;
+use io::errors::GGCATError;
 use replace_with::replace_with_or_abort;
 use std::fs::File;
 use std::io::{BufReader, Read, Seek, SeekFrom};
-use std::marker::PhantomData;
 use std::path::Path;
 
-pub struct ColorsDeserializer<DS: ColorsSerializerTrait> {
+pub struct ColorsDeserializer {
     colormap_file: lz4::Decoder<BufReader<File>>,
-    #[allow(dead_code)]
     color_names: Vec<String>,
     colors_index: ColorsIndexMap,
+    encoding: ColorsEncoding,
     current_chunk: ColorsIndexEntry,
     current_chunk_size: ColorIndexType,
     current_index: ColorIndexType,
-    _phantom: PhantomData<DS>,
 }
 
-unsafe impl<DS: ColorsSerializerTrait> Sync for ColorsDeserializer<DS> {}
-unsafe impl<DS: ColorsSerializerTrait> Send for ColorsDeserializer<DS> {}
+unsafe impl Sync for ColorsDeserializer {}
+unsafe impl Send for ColorsDeserializer {}
+
+/// Reads the header, the color names and the chunks index of a colormap file.
+/// The encoding of the color subsets is detected from the magic of the header
+pub(crate) fn read_colormap_metadata(
+    file: impl AsRef<Path>,
+) -> Result<(File, Vec<String>, ColorsIndexMap, ColorsEncoding), GGCATError> {
+    let file_path = file.as_ref().to_path_buf();
+    let invalid_colormap = |reason: String| GGCATError::InvalidColormap {
+        file: file_path.clone(),
+        reason,
+    };
+
+    let mut file = File::open(&file).map_err(|error| GGCATError::FileOpen {
+        file: file_path.clone(),
+        error,
+    })?;
+
+    let mut header_buffer = [0; ColorsFileHeader::SIZE];
+    file.read_exact(&mut header_buffer)
+        .map_err(|_| invalid_colormap("file too short for the header".to_string()))?;
+
+    let header: ColorsFileHeader = ColorsFileHeader::deserialize_from(&header_buffer);
+    let encoding = ColorsEncoding::from_magic(&header.magic).ok_or_else(|| {
+        invalid_colormap(
+            "wrong magic, the file is not a colormap or uses an unknown encoding".to_string(),
+        )
+    })?;
+    if header.version != STORAGE_VERSION {
+        return Err(invalid_colormap(format!(
+            "unsupported version {} (expected {})",
+            header.version, STORAGE_VERSION
+        )));
+    }
 
-impl<DS: ColorsSerializerTrait> ColorsDeserializer<DS> {
-    pub fn new(file: impl AsRef<Path>) -> Self {
-        let mut file = File::open(file).unwrap();
+    // println!("Colors header: {:#?}", &header);
 
-        let mut header_buffer = [0; ColorsFileHeader::SIZE];
-        file.read_exact(&mut header_buffer).unwrap();
+    let color_names = {
+        let mut compressed_stream = lz4::Decoder::new(BufReader::new(file))?;
 
-        let header: ColorsFileHeader = ColorsFileHeader::deserialize_from(&header_buffer);
-        assert_eq!(header.magic, DS::MAGIC);
+        let color_names: Vec<String> = bincode::deserialize_from(&mut compressed_stream)
+            .map_err(|err| invalid_colormap(format!("corrupted color names ({})", err)))?;
+        file = compressed_stream.finish().0.into_inner();
+        color_names
+    };
 
-        // println!("Colors header: {:#?}", &header);
+    let colors_index: ColorsIndexMap = {
+        file.seek(SeekFrom::Start(header.index_offset))?;
+        bincode::deserialize_from(&mut file)
+            .map_err(|err| invalid_colormap(format!("corrupted colors index ({})", err)))?
+    };
 
-        let color_names = {
-            let mut compressed_stream = lz4::Decoder::new(BufReader::new(file)).unwrap();
+    if colors_index.pairs.is_empty() {
+        return Err(invalid_colormap("empty colors index".to_string()));
+    }
 
-            let color_names: Vec<String> =
-                bincode::deserialize_from(&mut compressed_stream).unwrap();
-            file = compressed_stream.finish().0.into_inner();
-            color_names
-        };
+    Ok((file, color_names, colors_index, encoding))
+}
 
-        let colors_index: ColorsIndexMap = {
-            file.seek(SeekFrom::Start(header.index_offset)).unwrap();
-            bincode::deserialize_from(&mut file).unwrap()
-        };
+impl ColorsDeserializer {
+    pub fn new(file: impl AsRef<Path>) -> Result<Self, GGCATError> {
+        let (mut file, color_names, colors_index, encoding) = read_colormap_metadata(file)?;
 
         let first_chunk = colors_index.pairs[0];
-        file.seek(SeekFrom::Start(first_chunk.file_offset)).unwrap();
+        file.seek(SeekFrom::Start(first_chunk.file_offset))?;
 
         let current_chunk_size = colors_index
             .pairs
@@ -60,29 +96,26 @@ impl<DS: ColorsSerializerTrait> ColorsDeserializer<DS> {
             .unwrap_or(colors_index.subsets_count as ColorIndexType)
             - first_chunk.start_index;
 
-        Self {
-            colormap_file: lz4::Decoder::new(BufReader::new(file)).unwrap(),
+        Ok(Self {
+            colormap_file: lz4::Decoder::new(BufReader::new(file))?,
             color_names,
             colors_index,
+            encoding,
             current_chunk: first_chunk,
             current_chunk_size,
             current_index: first_chunk.start_index,
-            _phantom: Default::default(),
-        }
+        })
+    }
+
+    /// Names of the colors, in the same order of the color indices
+    pub fn color_names(&self) -> &[String] {
+        &self.color_names
     }
 
     fn maybe_change_block(&mut self, target_color: ColorIndexType) {
         if target_color < self.current_index
             || target_color >= (self.current_chunk.start_index + self.current_chunk_size)
         {
-            println!(
-                "Changing chunk {} < {} || {} >= {} + {}!",
-                target_color,
-                self.current_index,
-                target_color,
-                self.current_chunk.start_index,
-                self.current_chunk_size
-            );
             // Requested color is outside of chunk range, update the current chunk
             let new_chunk_index = self
                 .colors_index
@@ -116,18 +149,53 @@ impl<DS: ColorsSerializerTrait> ColorsDeserializer<DS> {
 
         while self.current_index < color {
             // Skip the colors
-            DS::decode_color(&mut self.colormap_file, None);
+            self.encoding.decode_color(&mut self.colormap_file, None);
             self.current_index += 1;
         }
 
         // Decode the requested color
-        DS::decode_color(&mut self.colormap_file, Some(out_vec));
+        self.encoding
+            .decode_color(&mut self.colormap_file, Some(out_vec));
         self.current_index += 1;
     }
 }
 
-impl<DS: ColorsSerializerTrait> ColorMapReader for ColorsDeserializer<DS> {
+impl ColorMapReader for ColorsDeserializer {
     fn colors_count(&self) -> u64 {
         self.colors_index.subsets_count as u64
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::storage::deserializer::ColorsDeserializer;
+    use crate::storage::serializer::ColorsFileHeader;
+    use desse::DesseSized;
+    use io::errors::GGCATError;
+    use io::test_utils::TestTempDir;
+
+    #[test]
+    fn invalid_colormaps_rejected() {
+        let temp_dir = TestTempDir::new("invalid-colormaps");
+
+        assert!(matches!(
+            ColorsDeserializer::new(temp_dir.file("missing.colors.dat")),
+            Err(GGCATError::FileOpen { .. })
+        ));
+
+        let short_file = temp_dir.file("short.colors.dat");
+        std::fs::write(&short_file, b"GGCAT").unwrap();
+        assert!(matches!(
+            ColorsDeserializer::new(&short_file),
+            Err(GGCATError::InvalidColormap { .. })
+        ));
+
+        // A file of the right size that is not a colormap
+        let wrong_magic_file = temp_dir.file("graph.fa");
+        std::fs::write(&wrong_magic_file, vec![b'A'; ColorsFileHeader::SIZE * 2]).unwrap();
+        match ColorsDeserializer::new(&wrong_magic_file) {
+            Err(GGCATError::InvalidColormap { file, .. }) => assert_eq!(file, wrong_magic_file),
+            _ => panic!("expected an invalid colormap error"),
+        }
+    }
+}
@@ -0,0 +1,267 @@
+use crate::colors_manager::ColorMapReader;
+use crate::storage::deserializer::read_colormap_metadata;
+use crate::storage::serializer::ColorsIndexMap;
+use crate::storage::ColorsEncoding;
+use config::ColorIndexType;
+use hashbrown::HashMap;
+use io::errors::GGCATError;
+use std::fs::File;
+use std::io::{BufReader, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+/// Number of decoded chunks kept in memory by default
+pub const DEFAULT_CHUNKS_CACHE_SIZE: usize = 16;
+
+/// All the color subsets of a chunk, stored contiguously
+struct DecodedChunk {
+    start_index: ColorIndexType,
+    /// Start of each subset in the colors vector, with an additional end offset
+    offsets: Vec<usize>,
+    colors: Vec<ColorIndexType>,
+    last_use: u64,
+}
+
+impl DecodedChunk {
+    fn get(&self, subset: ColorIndexType) -> &[ColorIndexType] {
+        let index = (subset - self.start_index) as usize;
+        &self.colors[self.offsets[index]..self.offsets[index + 1]]
+    }
+}
+
+/// Random access reader of the color subsets of a colormap.
+/// The chunks containing the requested subsets are decoded entirely,
+/// and the most recently used ones are kept in memory
+pub struct ColorsIndexedReader {
+    file_path: PathBuf,
+    colormap_file: BufReader<File>,
+    color_names: Vec<String>,
+    colors_index: ColorsIndexMap,
+    encoding: ColorsEncoding,
+    chunks_cache: HashMap<usize, DecodedChunk>,
+    cache_size: usize,
+    uses_count: u64,
+}
+
+impl ColorsIndexedReader {
+    pub fn new(file: impl AsRef<Path>) -> Result<Self, GGCATError> {
+        Self::with_cache_size(file, DEFAULT_CHUNKS_CACHE_SIZE)
+    }
+
+    pub fn with_cache_size(file: impl AsRef<Path>, cache_size: usize) -> Result<Self, GGCATError> {
+        let file_path = file.as_ref().to_path_buf();
+        let (file, color_names, colors_index, encoding) = read_colormap_metadata(file)?;
+
+        Ok(Self {
+            file_path,
+            colormap_file: BufReader::new(file),
+            color_names,
+            colors_index,
+            encoding,
+            chunks_cache: HashMap::with_capacity(cache_size),
+            cache_size: cache_size.max(1),
+            uses_count: 0,
+        })
+    }
+
+    /// Names of the colors, in the same order of the color indices
+    pub fn color_names(&self) -> &[String] {
+        &self.color_names
+    }
+
+    pub fn color_name(&self, color: ColorIndexType) -> Option<&str> {
+        self.color_names
+            .get(color as usize)
+            .map(|name| name.as_str())
+    }
+
+    pub fn subsets_count(&self) -> u64 {
+        self.colors_index.subsets_count
+    }
+
+    fn get_chunk_index(&self, subset: ColorIndexType) -> Option<usize> {
+        if subset as u64 >= self.colors_index.subsets_count {
+            return None;
+        }
+        Some(
+            self.colors_index
+                .pairs
+                .partition_point(|x| x.start_index <= subset)
+                - 1,
+        )
+    }
+
+    fn decode_chunk(&mut self, chunk_index: usize) -> Result<DecodedChunk, GGCATError> {
+        let chunk = self.colors_index.pairs[chunk_index];
+        let chunk_end = self
+            .colors_index
+            .pairs
+            .get(chunk_index + 1)
+            .map(|p| p.start_index)
+            .unwrap_or(self.colors_index.subsets_count as ColorIndexType);
+
+        let invalid_chunk = || GGCATError::InvalidColormap {
+            file: self.file_path.clone(),
+            reason: format!(
+                "corrupted chunk {} at offset {}",
+                chunk_index, chunk.file_offset
+            ),
+        };
+
+        self.colormap_file
+            .seek(SeekFrom::Start(chunk.file_offset))?;
+        let mut decoder =
+            lz4::Decoder::new(&mut self.colormap_file).map_err(|_| invalid_chunk())?;
+
+        let mut decoded = DecodedChunk {
+            start_index: chunk.start_index,
+            offsets: Vec::with_capacity((chunk_end - chunk.start_index) as usize + 1),
+            colors: Vec::new(),
+            last_use: 0,
+        };
+
+        let mut subset_colors = Vec::new();
+        decoded.offsets.push(0);
+        for _ in chunk.start_index..chunk_end {
+            self.encoding
+                .decode_color(&mut decoder, Some(&mut subset_colors))
+                .ok_or_else(invalid_chunk)?;
+            decoded.colors.extend_from_slice(&subset_colors);
+            decoded.offsets.push(decoded.colors.len());
+        }
+        Ok(decoded)
+    }
+
+    fn get_cached_chunk(&mut self, chunk_index: usize) -> Result<&DecodedChunk, GGCATError> {
+        if !self.chunks_cache.contains_key(&chunk_index) {
+            if self.chunks_cache.len() >= self.cache_size {
+                // Evict the least recently used chunk
+                let evicted = *self
+                    .chunks_cache
+                    .iter()
+                    .min_by_key(|(_, chunk)| chunk.last_use)
+                    .unwrap()
+                    .0;
+                self.chunks_cache.remove(&evicted);
+            }
+            let chunk = self.decode_chunk(chunk_index)?;
+            self.chunks_cache.insert(chunk_index, chunk);
+        }
+
+        self.uses_count += 1;
+        let chunk = self.chunks_cache.get_mut(&chunk_index).unwrap();
+        chunk.last_use = self.uses_count;
+        Ok(chunk)
+    }
+
+    /// Returns the colors of a subset, or None if the subset does not exist.
+    /// A chunk that cannot be decoded is reported as an error
+    pub fn get(&mut self, subset: ColorIndexType) -> Result<Option<&[ColorIndexType]>, GGCATError> {
+        match self.get_chunk_index(subset) {
+            Some(chunk_index) => Ok(Some(self.get_cached_chunk(chunk_index)?.get(subset))),
+            None => Ok(None),
+        }
+    }
+
+    /// Calls `callback` with the colors of each of the subsets, sorted by subset index
+    /// to decode each chunk only once. The subsets that do not exist are skipped
+    pub fn get_many(
+        &mut self,
+        subsets: &[ColorIndexType],
+        mut callback: impl FnMut(ColorIndexType, &[ColorIndexType]),
+    ) -> Result<(), GGCATError> {
+        let mut sorted_subsets = subsets.to_vec();
+        sorted_subsets.sort_unstable();
+        sorted_subsets.dedup();
+
+        for subset in sorted_subsets {
+            if let Some(colors) = self.get(subset)? {
+                callback(subset, colors);
+            }
+        }
+        Ok(())
+    }
+
+    /// Iterates over all the subsets in order, without using the chunks cache.
+    /// A chunk that cannot be decoded is reported once, and ends the iteration
+    pub fn iter(
+        &mut self,
+    ) -> impl Iterator<Item = Result<(ColorIndexType, Vec<ColorIndexType>), GGCATError>> + '_ {
+        let mut failed = false;
+        (0..self.colors_index.pairs.len())
+            .map_while(move |chunk_index| {
+                if failed {
+                    return None;
+                }
+                let chunk = self.decode_chunk(chunk_index);
+                failed = chunk.is_err();
+                Some(chunk)
+            })
+            .flat_map(|chunk| match chunk {
+                Ok(chunk) => {
+                    let end_index = chunk.start_index + (chunk.offsets.len() - 1) as ColorIndexType;
+                    (chunk.start_index..end_index)
+                        .map(|subset| Ok((subset, chunk.get(subset).to_vec())))
+                        .collect()
+                }
+                Err(error) => vec![Err(error)],
+            })
+    }
+}
+
+impl ColorMapReader for ColorsIndexedReader {
+    fn colors_count(&self) -> u64 {
+        self.colors_index.subsets_count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ColorsIndexedReader;
+    use crate::storage::run_length::RunLengthColorsSerializer;
+    use crate::storage::serializer::ColorsSerializer;
+    use io::errors::GGCATError;
+    use io::test_utils::TestTempDir;
+    use std::io::{Seek, SeekFrom, Write};
+
+    #[test]
+    fn corrupted_chunk_not_panicking() {
+        let temp_dir = TestTempDir::new("corrupted-colormap");
+        let colormap_file = temp_dir.file("graph.colors.dat");
+        let subsets = [vec![0, 1], vec![1], vec![0, 1, 2]];
+        {
+            let serializer = ColorsSerializer::<RunLengthColorsSerializer>::new(
+                &colormap_file,
+                vec!["a".to_string(), "b".to_string(), "c".to_string()],
+            );
+            for subset in &subsets {
+                serializer.serialize_colors(subset);
+            }
+        }
+
+        let mut reader = ColorsIndexedReader::new(&colormap_file).unwrap();
+        assert_eq!(reader.get(2).unwrap(), Some(&subsets[2][..]));
+        assert_eq!(reader.get(3).unwrap(), None);
+        let chunk_offset = reader.colors_index.pairs[0].file_offset;
+        drop(reader);
+
+        let mut file = std::fs::OpenOptions::new()
+            .write(true)
+            .open(&colormap_file)
+            .unwrap();
+        file.seek(SeekFrom::Start(chunk_offset)).unwrap();
+        file.write_all(&[0xFF; 8]).unwrap();
+        drop(file);
+
+        let mut reader = ColorsIndexedReader::new(&colormap_file).unwrap();
+        assert!(matches!(
+            reader.get(0),
+            Err(GGCATError::InvalidColormap { .. })
+        ));
+        let decoded: Vec<_> = reader.iter().collect();
+        assert_eq!(decoded.len(), 1);
+        assert!(matches!(
+            decoded[0],
+            Err(GGCATError::InvalidColormap { .. })
+        ));
+    }
+}
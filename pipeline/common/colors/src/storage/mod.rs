@@ -1,8 +1,11 @@
+use crate::storage::roaring::RoaringColorsSerializer;
+use crate::storage::run_length::RunLengthColorsSerializer;
 use crate::storage::serializer::ColorsFlushProcessing;
 use config::ColorIndexType;
 use std::io::Read;
 
 pub mod deserializer;
+pub mod indexed_reader;
 pub mod roaring;
 pub mod run_length;
 pub mod serializer;
@@ -10,7 +13,8 @@ pub mod serializer;
 pub trait ColorsSerializerTrait {
     const MAGIC: [u8; 16];
 
-    fn decode_color(reader: impl Read, out_vec: Option<&mut Vec<ColorIndexType>>);
+    /// Decodes the next subset, returns None if the data is truncated or corrupted
+    fn decode_color(reader: impl Read, out_vec: Option<&mut Vec<ColorIndexType>>) -> Option<()>;
     // fn decode_colors(reader: impl Read) -> ;
 
     fn new(writer: ColorsFlushProcessing, checkpoint_distance: usize, colors_count: u64) -> Self;
@@ -19,3 +23,34 @@ pub trait ColorsSerializerTrait {
     fn print_stats(&self);
     fn finalize(self) -> ColorsFlushProcessing;
 }
+
+/// Encoding of the color subsets of a colormap, identified by the magic of its header
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorsEncoding {
+    RunLength,
+    Roaring,
+}
+
+impl ColorsEncoding {
+    pub fn from_magic(magic: &[u8; 16]) -> Option<Self> {
+        if *magic == RunLengthColorsSerializer::MAGIC {
+            Some(Self::RunLength)
+        } else if *magic == RoaringColorsSerializer::MAGIC {
+            Some(Self::Roaring)
+        } else {
+            None
+        }
+    }
+
+    #[inline(always)]
+    pub fn decode_color(
+        self,
+        reader: impl Read,
+        out_vec: Option<&mut Vec<ColorIndexType>>,
+    ) -> Option<()> {
+        match self {
+            Self::RunLength => RunLengthColorsSerializer::decode_color(reader, out_vec),
+            Self::Roaring => RoaringColorsSerializer::decode_color(reader, out_vec),
+        }
+    }
+}
@@ -1,155 +1,146 @@
-#![allow(warnings)]
+use crate::async_slice_queue::AsyncSliceQueue;
 use crate::storage::serializer::ColorsFlushProcessing;
 use crate::storage::ColorsSerializerTrait;
 use config::ColorIndexType;
-use io::chunks_writer::ChunksWriter;
-use parking_lot::Mutex;
+use config::DEFAULT_OUTPUT_BUFFER_SIZE;
 use roaring::RoaringBitmap;
 use std::io::Read;
-use std::sync::atomic::{AtomicU32, Ordering};
-
-struct RoaringBitmapInstance {
-    bitmap: RoaringBitmap,
-    offset: ColorIndexType,
-    colors_count: u64,
-    checkpoint_distance: u64,
-    stride: ColorIndexType,
-    last_color: ColorIndexType,
-}
-
-impl RoaringBitmapInstance {
-    fn new(
-        colors_count: u64,
-        checkpoint_distance: u64,
-        offset: ColorIndexType,
-        stride: ColorIndexType,
-    ) -> Self {
-        todo!("Fix meaning of 'stride'!");
-        Self {
-            bitmap: RoaringBitmap::new(),
-            offset,
-            colors_count,
-            checkpoint_distance,
-            stride,
-            last_color: 0,
-        }
-    }
-
-    fn try_append(
-        &mut self,
-        color_index: ColorIndexType,
-        colors: impl Iterator<Item = ColorIndexType>,
-        writer: &ColorsFlushProcessing,
-    ) -> bool {
-        let base_color = color_index - self.offset;
-
-        // Another append is in queue and the current is not the first one
-        if base_color > self.last_color + self.stride {
-            return false;
-        }
-
-        self.last_color = base_color;
-
-        assert_eq!(base_color % self.stride, 0);
-        let strided_color = base_color / self.stride;
-
-        let local_position = strided_color * (self.colors_count as u32);
-
-        self.bitmap
-            .append(colors.map(|c| local_position + c))
-            .unwrap();
-
-        // Flush the partial bitmap
-        if strided_color >= self.checkpoint_distance as u32 {
-            println!("Flushing with offset: {}", self.offset);
-            self.flush(writer);
-        }
-
-        true
-    }
-
-    fn flush(&mut self, writer: &ColorsFlushProcessing) {
-        let mut pdata = writer.start_processing();
-        self.bitmap
-            .serialize_into(writer.get_stream(&mut pdata))
-            .unwrap();
-        writer.end_processing(pdata, self.offset);
-        self.offset += self.last_color;
-        self.last_color = 0;
-        self.bitmap.clear();
-    }
-}
 
+/// Stores each color subset as a serialized roaring bitmap, that is more compact than
+/// the run length encoding for the subsets with many colors
 pub struct RoaringColorsSerializer {
-    colors_count: u64,
-    roaring_bitmaps: Vec<Mutex<RoaringBitmapInstance>>,
-    writer: ColorsFlushProcessing,
-    colors_index: AtomicU32,
+    async_buffer: AsyncSliceQueue<u8, ColorsFlushProcessing>,
 }
 
+#[thread_local]
+static mut TEMP_BITMAP_BUFFER: Vec<u8> = Vec::new();
+
 impl ColorsSerializerTrait for RoaringColorsSerializer {
     const MAGIC: [u8; 16] = *b"GGCAT_CMAP_ROARG";
 
-    // FIXME: Implement!
-    fn decode_color(_reader: impl Read, _out_vec: Option<&mut Vec<u32>>) {
-        todo!()
+    fn decode_color(reader: impl Read, out_vec: Option<&mut Vec<ColorIndexType>>) -> Option<()> {
+        // The bitmap must be read anyway to skip it
+        let bitmap = RoaringBitmap::deserialize_from(reader).ok()?;
+        if let Some(out_vec) = out_vec {
+            out_vec.clear();
+            out_vec.extend(bitmap.iter());
+        }
+        Some(())
     }
 
-    fn new(writer: ColorsFlushProcessing, checkpoint_distance: usize, colors_count: u64) -> Self {
-        todo!("Fix meaning of 'stride'!");
-        let stride = rayon::current_num_threads() as ColorIndexType;
-
+    fn new(writer: ColorsFlushProcessing, checkpoint_distance: usize, _colors_count: u64) -> Self {
         Self {
-            roaring_bitmaps: (0..stride)
-                .map(|off| {
-                    Mutex::new(RoaringBitmapInstance::new(
-                        colors_count,
-                        checkpoint_distance as u64,
-                        off,
-                        stride,
-                    ))
-                })
-                .collect(),
-            writer,
-            colors_index: AtomicU32::new(0),
-            colors_count,
+            async_buffer: AsyncSliceQueue::new(
+                DEFAULT_OUTPUT_BUFFER_SIZE,
+                rayon::current_num_threads(),
+                checkpoint_distance,
+                writer,
+            ),
         }
     }
 
     fn serialize_colors(&self, colors: &[ColorIndexType]) -> ColorIndexType {
-        let color_index = self.colors_index.fetch_add(1, Ordering::Relaxed);
-
-        let target_bitmap = color_index % self.roaring_bitmaps.len() as ColorIndexType;
-
-        loop {
-            let mut bitmap_lock = self.roaring_bitmaps[target_bitmap as usize].lock();
-            if bitmap_lock.try_append(color_index, colors.iter().copied(), &self.writer) {
-                break;
-            }
-            drop(bitmap_lock);
-            std::thread::yield_now();
+        let bitmap = RoaringBitmap::from_sorted_iter(colors.iter().copied()).unwrap();
+        unsafe {
+            TEMP_BITMAP_BUFFER.clear();
+            bitmap.serialize_into(&mut TEMP_BITMAP_BUFFER).unwrap();
+            self.async_buffer.add_data(TEMP_BITMAP_BUFFER.as_slice()) as ColorIndexType
         }
-
-        color_index
     }
 
     fn get_subsets_count(&self) -> u64 {
-        self.colors_index.load(Ordering::Relaxed) as u64
+        self.async_buffer.get_counter()
     }
 
     fn print_stats(&self) {
-        println!(
-            "Subsets count: {} witn {} colors",
-            self.get_subsets_count(),
-            self.colors_count
-        );
+        println!("Total color subsets: {}", self.async_buffer.get_counter())
+    }
+
+    fn finalize(self) -> ColorsFlushProcessing {
+        self.async_buffer.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RoaringColorsSerializer;
+    use crate::storage::deserializer::ColorsDeserializer;
+    use crate::storage::indexed_reader::ColorsIndexedReader;
+    use crate::storage::serializer::ColorsSerializer;
+    use crate::storage::ColorsSerializerTrait;
+    use config::ColorIndexType;
+    use io::test_utils::TestTempDir;
+    use roaring::RoaringBitmap;
+    use std::io::Cursor;
+
+    #[test]
+    fn colors_subset_roaring_encoding_test() {
+        let subsets: Vec<Vec<ColorIndexType>> = vec![
+            vec![0],
+            vec![1, 2, 5, 10, 15, 30, 45],
+            (0..70000).step_by(3).collect(),
+            vec![
+                3, 6, 9, 12, 62, 63, 64, 70, 71, 88, 95, 100, 105, 110, 198, 384,
+            ],
+        ];
+
+        let mut buffer = Vec::new();
+        for subset in &subsets {
+            RoaringBitmap::from_sorted_iter(subset.iter().copied())
+                .unwrap()
+                .serialize_into(&mut buffer)
+                .unwrap();
+        }
+
+        let mut cursor = Cursor::new(buffer);
+        let mut des_colors = Vec::new();
+
+        // Skipping a subset must consume all its data
+        RoaringColorsSerializer::decode_color(&mut cursor, None).unwrap();
+        for subset in &subsets[1..] {
+            RoaringColorsSerializer::decode_color(&mut cursor, Some(&mut des_colors)).unwrap();
+            assert_eq!(subset, &des_colors);
+        }
     }
 
-    fn finalize(mut self) -> ColorsFlushProcessing {
-        for bitmap in self.roaring_bitmaps {
-            bitmap.lock().flush(&mut self.writer);
+    #[test]
+    fn roaring_colormap_round_trip() {
+        let temp_dir = TestTempDir::new("roaring-colormap");
+        let colormap_file = temp_dir.file("graph.colors.dat");
+        let color_names: Vec<_> = (0..70000).map(|i| format!("color{}", i)).collect();
+        let subsets: Vec<Vec<ColorIndexType>> = vec![
+            vec![0],
+            vec![1, 2, 5, 10, 15, 30, 45],
+            (0..70000).step_by(3).collect(),
+            vec![69999],
+        ];
+        {
+            let serializer = ColorsSerializer::<RoaringColorsSerializer>::new(
+                &colormap_file,
+                color_names.clone(),
+            );
+            for (index, subset) in subsets.iter().enumerate() {
+                assert_eq!(serializer.serialize_colors(subset), index as ColorIndexType);
+            }
         }
 
-        self.writer
+        // The encoding is detected from the magic in the file header
+        let mut reader = ColorsIndexedReader::new(&colormap_file).unwrap();
+        assert_eq!(reader.color_names(), color_names.as_slice());
+        for (index, subset) in subsets.iter().enumerate() {
+            assert_eq!(
+                reader.get(index as ColorIndexType).unwrap(),
+                Some(subset.as_slice())
+            );
+        }
+        assert_eq!(reader.get(subsets.len() as ColorIndexType).unwrap(), None);
+
+        let mut deserializer = ColorsDeserializer::new(&colormap_file).unwrap();
+        assert_eq!(deserializer.color_names(), color_names.as_slice());
+        let mut des_colors = Vec::new();
+        for (index, subset) in subsets.iter().enumerate() {
+            deserializer.get_color_mappings(index as ColorIndexType, &mut des_colors);
+            assert_eq!(subset, &des_colors);
+        }
     }
 }
@@ -63,7 +63,7 @@ impl ColorIndexSerializer {
         mut reader: impl Read,
         mut add_color: impl FnMut(ColorIndexType),
     ) -> Option<()> {
-        add_color((decode_varint(|| reader.read_u8().ok())? - 2) as ColorIndexType);
+        add_color(decode_varint(|| reader.read_u8().ok())?.checked_sub(2)? as ColorIndexType);
         loop {
             let result = decode_varint(|| reader.read_u8().ok())? as ColorIndexType;
             if result == 0 {
@@ -107,14 +107,10 @@ static mut TEMP_COLOR_BUFFER: Vec<u8> = Vec::new();
 impl ColorsSerializerTrait for RunLengthColorsSerializer {
     const MAGIC: [u8; 16] = *b"GGCAT_CMAP_RNLEN";
 
-    fn decode_color(mut reader: impl Read, out_vec: Option<&mut Vec<u32>>) {
+    fn decode_color(mut reader: impl Read, out_vec: Option<&mut Vec<u32>>) -> Option<()> {
         match out_vec {
-            None => {
-                ColorIndexSerializer::deserialize_colors_diffs(&mut reader, |_| {});
-            }
-            Some(out_vec) => {
-                ColorIndexSerializer::deserialize_colors(&mut reader, out_vec);
-            }
+            None => ColorIndexSerializer::deserialize_colors_diffs(&mut reader, |_| {}),
+            Some(out_vec) => ColorIndexSerializer::deserialize_colors(&mut reader, out_vec),
         }
     }
 
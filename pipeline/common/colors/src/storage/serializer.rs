@@ -12,7 +12,7 @@ use std::ops::{Deref, DerefMut};
 use std::path::Path;
 use std::sync::atomic::{AtomicU64, Ordering};
 
-const STORAGE_VERSION: u64 = 1;
+pub(crate) const STORAGE_VERSION: u64 = 1;
 
 #[derive(Debug, Desse, DesseSized, Default)]
 pub(crate) struct ColorsFileHeader {
@@ -280,7 +280,9 @@ impl HashFunctionFactory for CanonicalRabinKarpHashFactory {
         )
     }
 
+    const CANONICAL: bool = true;
     const INVERTIBLE: bool = false;
+    const NAME: &'static str = "canonical_rabin_karp";
     type SeqType = [u8; 0];
     fn invert(_hash: Self::HashTypeUnextendable) -> Self::SeqType {
         unimplemented!()
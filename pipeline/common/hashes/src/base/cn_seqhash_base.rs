@@ -199,7 +199,9 @@ impl HashFunctionFactory for CanonicalSeqHashFactory {
         ExtCanonicalSeqHash(hash.0 & mask, hash.1 >> 2)
     }
 
+    const CANONICAL: bool = true;
     const INVERTIBLE: bool = true;
+    const NAME: &'static str = "canonical_seqhash";
     type SeqType = [u8; size_of::<Self::HashTypeUnextendable>()];
 
     fn invert(hash: Self::HashTypeUnextendable) -> Self::SeqType {
@@ -227,7 +227,9 @@ impl HashFunctionFactory for ForwardRabinKarpHashFactory {
         ExtForwardRabinKarpHash(hash.0.wrapping_sub(fwd_l(out_base)).wrapping_mul(MULT_INV))
     }
 
+    const CANONICAL: bool = false;
     const INVERTIBLE: bool = false;
+    const NAME: &'static str = "forward_rabin_karp";
     type SeqType = [u8; 0];
     fn invert(_hash: Self::HashTypeUnextendable) -> Self::SeqType {
         unimplemented!()
@@ -180,7 +180,9 @@ impl HashFunctionFactory for ForwardSeqHashFactory {
         ExtForwardSeqHash(hash.0 & mask)
     }
 
+    const CANONICAL: bool = false;
     const INVERTIBLE: bool = true;
+    const NAME: &'static str = "forward_seqhash";
     type SeqType = [u8; size_of::<Self::HashTypeUnextendable>()];
     fn invert(hash: Self::HashTypeUnextendable) -> Self::SeqType {
         hash.to_le_bytes()
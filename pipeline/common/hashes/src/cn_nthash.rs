@@ -184,7 +184,9 @@ impl HashFunctionFactory for CanonicalNtHashIteratorFactory {
         ExtCanonicalNtHash(fw, rc.rotate_right(1))
     }
 
+    const CANONICAL: bool = true;
     const INVERTIBLE: bool = false;
+    const NAME: &'static str = "canonical_nthash";
     type SeqType = [u8; 0];
     fn invert(_hash: Self::HashTypeUnextendable) -> Self::SeqType {
         unimplemented!()
@@ -173,7 +173,9 @@ impl HashFunctionFactory for ForwardNtHashIteratorFactory {
         fwd_nt_manual_roll_rev(hash, k, out_base, Self::NULL_BASE)
     }
 
+    const CANONICAL: bool = false;
     const INVERTIBLE: bool = false;
+    const NAME: &'static str = "forward_nthash";
 
     type SeqType = [u8; 0];
     fn invert(_hash: Self::HashTypeUnextendable) -> Self::SeqType {
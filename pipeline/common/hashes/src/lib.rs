@@ -103,7 +103,11 @@ pub trait HashFunctionFactory: Sized + Clone + Debug + Send + Sync + 'static {
         out_base: u8,
     ) -> Self::HashTypeExtendable;
 
+    /// True if a kmer and its reverse complement have the same hash
+    const CANONICAL: bool;
     const INVERTIBLE: bool;
+    /// Name of the hash function, stable across builds, the width of the hash is not included
+    const NAME: &'static str;
     type SeqType: AsRef<[u8]>;
     fn invert(hash: Self::HashTypeUnextendable) -> Self::SeqType;
 }
@@ -0,0 +1,118 @@
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+
+/// Errors that can be caused by invalid inputs or settings, returned by the pipelines
+#[derive(Debug)]
+pub enum GGCATError {
+    /// The configuration has some invalid parameters
+    InvalidConfig(String),
+    NoInputFiles,
+    /// An input file cannot be opened
+    FileOpen {
+        file: PathBuf,
+        error: std::io::Error,
+    },
+    /// The format of an input file cannot be recognized from its content
+    UnknownFileFormat {
+        file: PathBuf,
+    },
+    /// An input file is malformed, the line is reported when known
+    InvalidInput {
+        file: PathBuf,
+        line: Option<u64>,
+        reason: String,
+    },
+    /// The colormap file is corrupted or incompatible with this version
+    InvalidColormap {
+        file: PathBuf,
+        reason: String,
+    },
+    /// The query index directory is corrupted or incompatible with the query settings
+    InvalidIndex {
+        dir: PathBuf,
+        reason: String,
+    },
+    Io(std::io::Error),
+}
+
+impl Display for GGCATError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GGCATError::InvalidConfig(reason) => write!(f, "Invalid configuration: {}", reason),
+            GGCATError::NoInputFiles => write!(f, "No input files specified"),
+            GGCATError::FileOpen { file, error } => {
+                write!(f, "Cannot open file '{}': {}", file.display(), error)
+            }
+            GGCATError::UnknownFileFormat { file } => {
+                write!(f, "Cannot recognize file type of '{}'", file.display())
+            }
+            GGCATError::InvalidInput { file, line, reason } => match line {
+                Some(line) => write!(
+                    f,
+                    "Invalid input file '{}' at line {}: {}",
+                    file.display(),
+                    line,
+                    reason
+                ),
+                None => write!(f, "Invalid input file '{}': {}", file.display(), reason),
+            },
+            GGCATError::InvalidColormap { file, reason } => {
+                write!(f, "Invalid colormap file '{}': {}", file.display(), reason)
+            }
+            GGCATError::InvalidIndex { dir, reason } => {
+                write!(f, "Invalid query index '{}': {}", dir.display(), reason)
+            }
+            GGCATError::Io(err) => write!(f, "I/O error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for GGCATError {}
+
+impl From<std::io::Error> for GGCATError {
+    fn from(err: std::io::Error) -> Self {
+        GGCATError::Io(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::errors::GGCATError;
+    use std::path::PathBuf;
+
+    fn read_first_line(file: &str) -> Result<String, GGCATError> {
+        let content = std::fs::read_to_string(file)?;
+        Ok(content.lines().next().unwrap_or_default().to_string())
+    }
+
+    #[test]
+    fn io_errors_converted() {
+        assert!(matches!(
+            read_first_line("/nonexistent/ggcat-test-file"),
+            Err(GGCATError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn input_error_messages() {
+        let error = GGCATError::InvalidInput {
+            file: PathBuf::from("reads.fq"),
+            line: Some(7),
+            reason: "expected a '+' separator line".to_string(),
+        };
+        assert_eq!(
+            error.to_string(),
+            "Invalid input file 'reads.fq' at line 7: expected a '+' separator line"
+        );
+
+        let error = GGCATError::InvalidInput {
+            file: PathBuf::from("reads.fq.gz"),
+            line: None,
+            reason: "read failed".to_string(),
+        };
+        assert_eq!(
+            error.to_string(),
+            "Invalid input file 'reads.fq.gz': read failed"
+        );
+    }
+}
@@ -1,5 +1,7 @@
 #![feature(int_log, let_chains)]
 
+use crate::errors::GGCATError;
+use crate::lines_reader::LinesReader;
 use config::{BucketIndexType, MAX_BUCKETS_COUNT_LOG, MAX_BUCKET_SIZE, MIN_BUCKETS_COUNT_LOG};
 use std::cmp::{max, min};
 use std::path::{Path, PathBuf};
@@ -7,10 +9,12 @@ use std::path::{Path, PathBuf};
 pub mod chunks_writer;
 pub mod compressed_read;
 pub mod concurrent;
+pub mod errors;
 pub mod lines_reader;
 pub mod reads_writer;
 pub mod sequences_reader;
 pub mod structs;
+pub mod test_utils;
 pub mod varint;
 
 pub fn get_bucket_index(bucket_file: impl AsRef<Path>) -> BucketIndexType {
@@ -52,22 +56,27 @@ pub fn generate_bucket_names(
         .collect()
 }
 
-pub fn compute_buckets_log_from_input_files(files: &[PathBuf]) -> usize {
+pub fn compute_buckets_log_from_input_files(files: &[PathBuf]) -> Result<usize, GGCATError> {
     // TODO: Improve this ratio estimation
     const COMPRESSED_READS_RATIO: f64 = 0.5;
 
     let mut bases_count = 0;
 
     for file in files {
-        let length = std::fs::metadata(file)
-            .expect(&format!("Error while opening file {}", file.display()))
-            .len();
+        if LinesReader::is_stream(file) {
+            // The size of the streamed inputs is not known, use the maximum buckets count
+            // to avoid having too big buckets
+            return Ok(MAX_BUCKETS_COUNT_LOG);
+        }
+
+        let open_error = |error| GGCATError::FileOpen {
+            file: file.clone(),
+            error,
+        };
+
+        let length = std::fs::metadata(file).map_err(open_error)?.len();
 
-        let file_bases_count = if file
-            .extension()
-            .map(|x| x == "gz" || x == "lz4")
-            .unwrap_or(false)
-        {
+        let file_bases_count = if LinesReader::is_compressed(file).map_err(open_error)? {
             (length as f64 * COMPRESSED_READS_RATIO) as u64
         } else {
             length
@@ -79,8 +88,8 @@ pub fn compute_buckets_log_from_input_files(files: &[PathBuf]) -> usize {
 
     let buckets_log = (max(1, buckets_count) - 1).next_power_of_two().ilog2() as usize;
 
-    min(
+    Ok(min(
         MAX_BUCKETS_COUNT_LOG,
         max(MIN_BUCKETS_COUNT_LOG, buckets_log),
-    )
+    ))
 }
@@ -1,14 +1,46 @@
+use crate::errors::GGCATError;
 use bstr::ByteSlice;
 use config::DEFAULT_OUTPUT_BUFFER_SIZE;
-use libdeflate_rs::decompress_file_buffered;
+use libdeflate_rs::{decompress_stream_buffered, LibdeflateError};
 use parallel_processor::counter_stats::counter::{AtomicCounter, AvgMode, SumMode};
 use parallel_processor::counter_stats::{declare_avg_counter_i64, declare_counter_i64};
 use std::fs::File;
-use std::io::Read;
+use std::io::{Cursor, Read};
 use std::path::Path;
 
+/// Path used to read an input from the standard input
+pub const STDIN_PATH: &str = "-";
+
 pub struct LinesReader {}
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum CompressionFormat {
+    None,
+    /// Also used for BGZF files, as they are made of multiple gzip members
+    Gzip,
+    Lz4,
+    Zstd,
+}
+
+impl CompressionFormat {
+    const GZIP_MAGIC: &'static [u8] = &[0x1f, 0x8b];
+    const LZ4_MAGIC: &'static [u8] = &[0x04, 0x22, 0x4d, 0x18];
+    const ZSTD_MAGIC: &'static [u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+    const MAX_MAGIC_LENGTH: usize = 4;
+
+    fn from_magic(header: &[u8]) -> Self {
+        if header.starts_with(Self::GZIP_MAGIC) {
+            Self::Gzip
+        } else if header.starts_with(Self::LZ4_MAGIC) {
+            Self::Lz4
+        } else if header.starts_with(Self::ZSTD_MAGIC) {
+            Self::Zstd
+        } else {
+            Self::None
+        }
+    }
+}
+
 static COUNTER_THREADS_BUSY_READING: AtomicCounter<SumMode> =
     declare_counter_i64!("line_reading_threads", SumMode, false);
 
@@ -25,11 +57,18 @@ impl LinesReader {
     fn read_stream_buffered(
         mut stream: impl Read,
         mut callback: impl FnMut(&[u8]),
-    ) -> Result<(), ()> {
+    ) -> std::io::Result<()> {
         let mut buffer = vec![0; DEFAULT_OUTPUT_BUFFER_SIZE];
         COUNTER_THREADS_BUSY_READING.inc();
 
-        while let Ok(count) = stream.read(buffer.as_mut_slice()) {
+        loop {
+            let count = match stream.read(buffer.as_mut_slice()) {
+                Ok(count) => count,
+                Err(err) => {
+                    COUNTER_THREADS_BUSY_READING.sub(1);
+                    return Err(err);
+                }
+            };
             COUNTER_THREADS_READ_BYTES.inc_by(count as i64);
             COUNTER_THREADS_READ_BYTES_AVG.add_value(count as i64);
             COUNTER_THREADS_BUSY_READING.sub(1);
@@ -44,50 +83,111 @@ impl LinesReader {
             COUNTER_THREADS_PROCESSING_READS.sub(1);
             COUNTER_THREADS_BUSY_READING.inc();
         }
-        Err(())
     }
 
-    fn read_binary_file(path: impl AsRef<Path>, mut callback: impl FnMut(&[u8]), remove: bool) {
-        if path.as_ref().extension().filter(|x| *x == "gz").is_some() {
-            if let Err(_err) = decompress_file_buffered(
-                &path,
-                |data| {
-                    callback(data);
-                    Ok(())
-                },
-                DEFAULT_OUTPUT_BUFFER_SIZE,
-            ) {
-                println!(
-                    "WARNING: Error while reading file {}",
-                    path.as_ref().display()
-                );
-            }
-            callback(&[]);
-        } else if path.as_ref().extension().filter(|x| *x == "lz4").is_some() {
-            let file = lz4::Decoder::new(
-                File::open(&path).expect(&format!("Cannot open file {}", path.as_ref().display())),
-            )
-            .unwrap();
-            Self::read_stream_buffered(file, callback).unwrap_or_else(|_| {
-                println!(
-                    "WARNING: Error while reading file {}",
-                    path.as_ref().display()
-                );
-            });
+    /// True if the input is the standard input or a named pipe, that can be read only once
+    /// and whose size is not known in advance
+    pub fn is_stream(path: &Path) -> bool {
+        path.as_os_str() == STDIN_PATH
+            || std::fs::metadata(path)
+                .map(|metadata| !metadata.is_file())
+                .unwrap_or(false)
+    }
+
+    fn read_header(stream: &mut impl Read) -> std::io::Result<Vec<u8>> {
+        let mut header = Vec::with_capacity(CompressionFormat::MAX_MAGIC_LENGTH);
+        stream
+            .take(CompressionFormat::MAX_MAGIC_LENGTH as u64)
+            .read_to_end(&mut header)?;
+        Ok(header)
+    }
+
+    /// True if the file is compressed with one of the supported formats
+    pub fn is_compressed(path: &Path) -> std::io::Result<bool> {
+        let header = Self::read_header(&mut File::open(path)?)?;
+        Ok(CompressionFormat::from_magic(&header) != CompressionFormat::None)
+    }
+
+    fn read_binary_file(
+        path: impl AsRef<Path>,
+        callback: impl FnMut(&[u8]),
+        remove: bool,
+    ) -> Result<(), GGCATError> {
+        let read_error = |reason: String| GGCATError::InvalidInput {
+            file: path.as_ref().to_path_buf(),
+            line: None,
+            reason,
+        };
+
+        let open_error = |error| GGCATError::FileOpen {
+            file: path.as_ref().to_path_buf(),
+            error,
+        };
+
+        let mut stream: Box<dyn Read> = if path.as_ref().as_os_str() == STDIN_PATH {
+            Box::new(std::io::stdin())
         } else {
-            let file =
-                File::open(&path).expect(&format!("Cannot open file {}", path.as_ref().display()));
-            Self::read_stream_buffered(file, callback).unwrap_or_else(|_| {
-                println!(
-                    "WARNING: Error while reading file {}",
-                    path.as_ref().display()
-                );
-            });
-        }
+            Box::new(File::open(&path).map_err(open_error)?)
+        };
+
+        // The compression format is detected from the first bytes, that are then read again
+        let header = Self::read_header(&mut stream)
+            .map_err(|err| read_error(format!("read failed ({})", err)))?;
+
+        let format = CompressionFormat::from_magic(&header);
+        Self::decode_stream(
+            Cursor::new(header).chain(stream),
+            format,
+            callback,
+            read_error,
+        )?;
 
         if remove {
-            std::fs::remove_file(path).unwrap();
+            std::fs::remove_file(path)?;
         }
+        Ok(())
+    }
+
+    fn decode_stream(
+        stream: impl Read,
+        format: CompressionFormat,
+        mut callback: impl FnMut(&[u8]),
+        read_error: impl Fn(String) -> GGCATError,
+    ) -> Result<(), GGCATError> {
+        match format {
+            CompressionFormat::Gzip => {
+                decompress_stream_buffered(
+                    stream,
+                    |data| {
+                        callback(data);
+                        Ok(())
+                    },
+                    DEFAULT_OUTPUT_BUFFER_SIZE,
+                )
+                .map_err(|err| match err {
+                    LibdeflateError::Io(err) => read_error(format!("read failed ({})", err)),
+                    err => read_error(format!("gzip decompression failed ({:?})", err)),
+                })?;
+                callback(&[]);
+            }
+            CompressionFormat::Lz4 => {
+                let stream = lz4::Decoder::new(stream)
+                    .map_err(|err| read_error(format!("lz4 decompression failed ({})", err)))?;
+                Self::read_stream_buffered(stream, callback)
+                    .map_err(|err| read_error(format!("lz4 decompression failed ({})", err)))?;
+            }
+            CompressionFormat::Zstd => {
+                let stream = zstd::Decoder::new(stream)
+                    .map_err(|err| read_error(format!("zstd decompression failed ({})", err)))?;
+                Self::read_stream_buffered(stream, callback)
+                    .map_err(|err| read_error(format!("zstd decompression failed ({})", err)))?;
+            }
+            CompressionFormat::None => {
+                Self::read_stream_buffered(stream, callback)
+                    .map_err(|err| read_error(format!("read failed ({})", err)))?;
+            }
+        }
+        Ok(())
     }
 
     #[inline]
@@ -127,7 +227,7 @@ impl LinesReader {
             bool, /* finished (last line) */
         ),
         remove: bool,
-    ) {
+    ) -> Result<(), GGCATError> {
         let mut line_pending = false;
 
         Self::read_binary_file(
@@ -160,6 +260,145 @@ impl LinesReader {
                 }
             },
             remove,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::errors::GGCATError;
+    use crate::lines_reader::{CompressionFormat, LinesReader};
+    use crate::test_utils::TestTempDir;
+    use std::io::{ErrorKind, Read, Write};
+    use std::path::{Path, PathBuf};
+
+    const LINES: &[u8] = b">read1\nACGTACGT\n>read2\nTTGCA\n";
+
+    fn read_lines(file: &Path) -> Vec<Vec<u8>> {
+        let mut lines = Vec::new();
+        let mut current_line = Vec::new();
+        LinesReader::process_lines(
+            file,
+            |line, partial, finished| {
+                current_line.extend_from_slice(line);
+                if !partial && !finished {
+                    lines.push(std::mem::take(&mut current_line));
+                }
+            },
+            false,
+        )
+        .unwrap();
+        lines
+    }
+
+    #[test]
+    fn compression_magic() {
+        assert_eq!(
+            CompressionFormat::from_magic(&[0x1f, 0x8b, 0x08, 0x04]),
+            CompressionFormat::Gzip
+        );
+        assert_eq!(
+            CompressionFormat::from_magic(&[0x04, 0x22, 0x4d, 0x18]),
+            CompressionFormat::Lz4
+        );
+        assert_eq!(
+            CompressionFormat::from_magic(&[0x28, 0xb5, 0x2f, 0xfd]),
+            CompressionFormat::Zstd
         );
+        assert_eq!(
+            CompressionFormat::from_magic(b">seq"),
+            CompressionFormat::None
+        );
+        // Files shorter than the magic
+        assert_eq!(
+            CompressionFormat::from_magic(&[0x1f]),
+            CompressionFormat::None
+        );
+        assert_eq!(CompressionFormat::from_magic(&[]), CompressionFormat::None);
+    }
+
+    #[test]
+    fn compression_detected_from_content() {
+        let expected_lines = vec![
+            b">read1".to_vec(),
+            b"ACGTACGT".to_vec(),
+            b">read2".to_vec(),
+            b"TTGCA".to_vec(),
+        ];
+
+        let mut gzip_encoder =
+            flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gzip_encoder.write_all(LINES).unwrap();
+
+        let mut lz4_encoder = lz4::EncoderBuilder::new().build(Vec::new()).unwrap();
+        lz4_encoder.write_all(LINES).unwrap();
+
+        let files = [
+            ("plain", LINES.to_vec()),
+            ("gzip", gzip_encoder.finish().unwrap()),
+            ("lz4", lz4_encoder.finish().0),
+            ("zstd", zstd::encode_all(LINES, 0).unwrap()),
+        ];
+
+        let temp_dir = TestTempDir::new("lines-compression");
+        for (name, content) in files {
+            // The extension does not match the content
+            let file = temp_dir.file(format!("{}.txt", name));
+            std::fs::write(&file, content).unwrap();
+            assert_eq!(
+                LinesReader::is_compressed(&file).unwrap(),
+                name != "plain",
+                "{}",
+                name
+            );
+            assert_eq!(read_lines(&file), expected_lines, "{}", name);
+        }
+    }
+
+    #[test]
+    fn crlf_lines() {
+        let temp_dir = TestTempDir::new("lines-crlf");
+        let file = temp_dir.file("crlf.fa");
+        std::fs::write(&file, b">read1\r\nACGT\r\n").unwrap();
+        assert_eq!(
+            read_lines(&file),
+            vec![b">read1".to_vec(), b"ACGT".to_vec()]
+        );
+    }
+
+    struct FailingReader;
+
+    impl Read for FailingReader {
+        fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::new(ErrorKind::Other, "broken stream"))
+        }
+    }
+
+    #[test]
+    fn gzip_read_error_reported() {
+        let mut gzip_encoder =
+            flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gzip_encoder.write_all(LINES).unwrap();
+        let compressed = gzip_encoder.finish().unwrap();
+
+        // The stream fails in the middle of the compressed data
+        let stream = Read::chain(&compressed[..compressed.len() / 2], FailingReader);
+        let result = LinesReader::decode_stream(
+            stream,
+            CompressionFormat::Gzip,
+            |_| {},
+            |reason| GGCATError::InvalidInput {
+                file: PathBuf::from("stream.gz"),
+                line: None,
+                reason,
+            },
+        );
+
+        match result {
+            Err(GGCATError::InvalidInput { reason, .. }) => {
+                assert!(reason.contains("broken stream"), "{}", reason)
+            }
+            _ => panic!("expected a read error"),
+        }
     }
 }
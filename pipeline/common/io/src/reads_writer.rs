@@ -100,6 +100,13 @@ impl ReadsWriter {
         self.reads_count += 1;
     }
 
+    /// Writes a raw line, used for output formats other than FASTA/FASTQ
+    pub fn add_line(&mut self, line: &[u8]) {
+        let writer = self.writer.get_writer();
+        writer.write_all(line).unwrap();
+        writer.write_u8(b'\n').unwrap();
+    }
+
     #[allow(dead_code)]
     pub fn get_path(&self) -> PathBuf {
         self.path.clone()
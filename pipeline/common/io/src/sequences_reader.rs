@@ -1,18 +1,15 @@
-use crate::lines_reader::LinesReader;
+use crate::errors::GGCATError;
+use crate::lines_reader::{LinesReader, STDIN_PATH};
 use config::DEFAULT_OUTPUT_BUFFER_SIZE;
 use nightly_quirks::branch_pred::unlikely;
 use std::cmp::max;
+use std::fs::File;
 use std::path::Path;
 
 const IDENT_STATE: usize = 0;
 const SEQ_STATE: usize = 1;
 const QUAL_STATE: usize = 2;
 
-enum FileType {
-    Fasta,
-    Fastq,
-}
-
 #[derive(Copy, Clone)]
 pub struct FastaSequence<'a> {
     pub ident: &'a [u8],
@@ -46,56 +43,102 @@ impl SequencesReader {
         Self {}
     }
 
+    /// Checks that the file can be opened, its format is detected from the content when reading it
+    pub fn check_input_file(source: impl AsRef<Path>) -> Result<(), GGCATError> {
+        let source = source.as_ref();
+        if source.as_os_str() == STDIN_PATH {
+            return Ok(());
+        }
+
+        let open_error = |error| GGCATError::FileOpen {
+            file: source.to_path_buf(),
+            error,
+        };
+
+        if LinesReader::is_stream(source) {
+            // Named pipes cannot be opened twice
+            std::fs::metadata(source).map_err(open_error)?;
+        } else {
+            File::open(source).map_err(open_error)?;
+        }
+        Ok(())
+    }
+
+    /// Processes a fasta or fastq file, the format is detected from its first line
     pub fn process_file_extended<F: FnMut(FastaSequence)>(
         source: impl AsRef<Path>,
         func: F,
         line_split_copyback: Option<usize>,
         copy_ident: bool,
+        get_quality: bool,
         remove_file: bool,
-    ) {
-        const FASTQ_EXTS: &[&str] = &["fq", "fastq"];
-        const FASTA_EXTS: &[&str] = &["fa", "fasta", "fna", "ffn"];
-
-        let mut file_type = None;
-        let mut tmp = source.as_ref().file_name().unwrap().to_str().unwrap();
-        let mut path: &Path = tmp.as_ref();
-
-        while let Some(ext) = path.extension() {
-            if FASTQ_EXTS.contains(&ext.to_str().unwrap()) {
-                file_type = Some(FileType::Fastq);
-                break;
-            }
-            if FASTA_EXTS.contains(&ext.to_str().unwrap()) {
-                file_type = Some(FileType::Fasta);
-                break;
-            }
-            tmp = &tmp[0..tmp.len() - ext.len() - 1];
-            path = tmp.as_ref()
+    ) -> Result<(), GGCATError> {
+        let source = source.as_ref();
+        let mut func = Some(func);
+        let mut parse_error = None;
+        let mut format_error = None;
+
+        {
+            let mut parse_error_ref = Some(&mut parse_error);
+            let mut fasta_parser = None;
+            let mut fastq_parser = None;
+
+            LinesReader::process_lines(
+                source,
+                |line: &[u8], partial, finished| {
+                    if format_error.is_some() {
+                        return;
+                    }
+
+                    if fasta_parser.is_none() && fastq_parser.is_none() {
+                        match line.first() {
+                            Some(b'>') | Some(b';') => {
+                                fasta_parser = Some(Self::fasta_lines_parser(
+                                    func.take().unwrap(),
+                                    line_split_copyback,
+                                    copy_ident,
+                                ));
+                            }
+                            Some(b'@') => {
+                                fastq_parser = Some(Self::fastq_lines_parser(
+                                    source,
+                                    func.take().unwrap(),
+                                    get_quality,
+                                    parse_error_ref.take().unwrap(),
+                                ));
+                            }
+                            // Skip the empty lines at the beginning and empty inputs
+                            None => return,
+                            Some(_) => {
+                                format_error = Some(GGCATError::UnknownFileFormat {
+                                    file: source.to_path_buf(),
+                                });
+                                return;
+                            }
+                        }
+                    }
+
+                    if let Some(parser) = &mut fasta_parser {
+                        parser(line, partial, finished);
+                    } else if let Some(parser) = &mut fastq_parser {
+                        parser(line, partial, finished);
+                    }
+                },
+                remove_file,
+            )?;
         }
 
-        match file_type {
-            None => panic!(
-                "Cannot recognize file type of '{}'",
-                source.as_ref().display()
-            ),
-            Some(ftype) => match ftype {
-                FileType::Fasta => {
-                    Self::process_fasta(source, func, line_split_copyback, copy_ident, remove_file);
-                }
-                FileType::Fastq => {
-                    Self::process_fastq(source, func, false, remove_file);
-                }
-            },
+        match format_error.or(parse_error) {
+            None => Ok(()),
+            Some(err) => Err(err),
         }
     }
 
-    fn process_fasta(
-        source: impl AsRef<Path>,
+    fn fasta_lines_parser(
         mut func: impl FnMut(FastaSequence),
         line_split_copyback: Option<usize>,
         copy_ident: bool,
-        remove_file: bool,
-    ) {
+    ) -> impl FnMut(&[u8], bool, bool) {
         let mut intermediate = [Vec::new(), Vec::new()];
         let mut on_comment = false;
         let mut state = SEQ_STATE;
@@ -106,119 +149,293 @@ impl SequencesReader {
             line_split_copyback.unwrap_or(0) * 2,
         );
 
-        LinesReader::process_lines(
-            source,
-            |line: &[u8], partial, finished| {
-                if on_comment {
-                    on_comment = !partial;
-                }
-                // If a new ident line is found (or it's the last line)
-                else if finished || (new_line && line.len() > 0 && line[0] == b'>') {
-                    if intermediate[SEQ_STATE].len() > 0 {
-                        func(FastaSequence {
-                            ident: &intermediate[IDENT_STATE],
-                            seq: &intermediate[SEQ_STATE],
-                            qual: None,
-                        });
-                    }
-
-                    if copy_ident {
-                        intermediate[IDENT_STATE].clear();
-                        intermediate[IDENT_STATE].extend_from_slice(line);
-                    }
-                    intermediate[SEQ_STATE].clear();
-
-                    state = if partial { IDENT_STATE } else { SEQ_STATE };
-                } else if new_line && line.len() > 0 && line[0] == b';' {
-                    on_comment = true;
-                } else if state == IDENT_STATE {
-                    if copy_ident {
-                        intermediate[IDENT_STATE].extend_from_slice(line);
-                    }
-
-                    if !partial {
-                        state = SEQ_STATE;
-                    }
-                } else {
-                    intermediate[SEQ_STATE].extend_from_slice(line);
-                }
-
-                if let Some(copyback) = line_split_copyback &&
-                    (intermediate[SEQ_STATE].len() >= flush_size) {
-                    Self::normalize_sequence(&mut intermediate[SEQ_STATE]);
+        move |line: &[u8], partial, finished| {
+            if on_comment {
+                on_comment = !partial;
+            }
+            // If a new ident line is found (or it's the last line)
+            else if finished || (new_line && line.len() > 0 && line[0] == b'>') {
+                if intermediate[SEQ_STATE].len() > 0 {
                     func(FastaSequence {
                         ident: &intermediate[IDENT_STATE],
                         seq: &intermediate[SEQ_STATE],
                         qual: None,
                     });
-                    let copy_start = intermediate[SEQ_STATE].len() - copyback;
-                    intermediate[SEQ_STATE].copy_within(copy_start.., 0);
-                    intermediate[SEQ_STATE].truncate(copyback);
                 }
 
-                new_line = !partial;
-            },
-            remove_file,
-        );
+                if copy_ident {
+                    intermediate[IDENT_STATE].clear();
+                    intermediate[IDENT_STATE].extend_from_slice(line);
+                }
+                intermediate[SEQ_STATE].clear();
+
+                state = if partial { IDENT_STATE } else { SEQ_STATE };
+            } else if new_line && line.len() > 0 && line[0] == b';' {
+                on_comment = true;
+            } else if state == IDENT_STATE {
+                if copy_ident {
+                    intermediate[IDENT_STATE].extend_from_slice(line);
+                }
+
+                if !partial {
+                    state = SEQ_STATE;
+                }
+            } else {
+                intermediate[SEQ_STATE].extend_from_slice(line);
+            }
+
+            if let Some(copyback) = line_split_copyback &&
+                (intermediate[SEQ_STATE].len() >= flush_size) {
+                Self::normalize_sequence(&mut intermediate[SEQ_STATE]);
+                func(FastaSequence {
+                    ident: &intermediate[IDENT_STATE],
+                    seq: &intermediate[SEQ_STATE],
+                    qual: None,
+                });
+                let copy_start = intermediate[SEQ_STATE].len() - copyback;
+                intermediate[SEQ_STATE].copy_within(copy_start.., 0);
+                intermediate[SEQ_STATE].truncate(copyback);
+            }
+
+            new_line = !partial;
+        }
     }
 
-    fn process_fastq(
-        source: impl AsRef<Path>,
-        mut func: impl FnMut(FastaSequence),
+    fn fastq_lines_parser<'a>(
+        source: &'a Path,
+        mut func: impl FnMut(FastaSequence) + 'a,
         get_quality: bool,
-        remove_file: bool,
-    ) {
+        parse_error: &'a mut Option<GGCATError>,
+    ) -> impl FnMut(&[u8], bool, bool) + 'a {
         let mut state = IDENT_STATE;
         let mut skipped_plus = false;
+        let mut new_line = true;
+        let mut line_number = 1;
 
         let mut intermediate = [Vec::new(), Vec::new(), Vec::new()];
 
-        LinesReader::process_lines(
-            source,
-            |line: &[u8], partial, finished| {
-                if unlikely(finished) {
+        const QUALITY_LENGTH_ERROR: &str = "quality and sequence lengths differ";
+
+        let make_error = move |line_number: u64, reason: &str| {
+            Some(GGCATError::InvalidInput {
+                file: source.to_path_buf(),
+                line: Some(line_number),
+                reason: reason.to_string(),
+            })
+        };
+
+        move |line: &[u8], partial, finished| {
+            if unlikely(finished) {
+                if parse_error.is_some() {
                     return;
                 }
-
-                if state == QUAL_STATE {
-                    if !skipped_plus {
-                        if !partial {
-                            skipped_plus = true;
-                        }
+                if state == QUAL_STATE && skipped_plus {
+                    // Last quality line without a trailing newline
+                    if get_quality
+                        && intermediate[QUAL_STATE].len() != intermediate[SEQ_STATE].len()
+                    {
+                        *parse_error = make_error(line_number, QUALITY_LENGTH_ERROR);
                         return;
                     }
+                    Self::normalize_sequence(&mut intermediate[SEQ_STATE]);
+                    func(FastaSequence {
+                        ident: &intermediate[IDENT_STATE],
+                        seq: &intermediate[SEQ_STATE],
+                        qual: if get_quality {
+                            Some(&intermediate[QUAL_STATE])
+                        } else {
+                            None
+                        },
+                    });
+                } else if state != IDENT_STATE || intermediate[IDENT_STATE].len() > 0 {
+                    *parse_error = make_error(line_number, "truncated fastq record");
+                }
+                return;
+            }
 
-                    if get_quality {
-                        intermediate[state].extend_from_slice(line);
+            if unlikely(parse_error.is_some()) {
+                return;
+            }
+
+            if new_line {
+                match state {
+                    IDENT_STATE if line.len() == 0 && !partial => {
+                        // Skip empty lines between records
+                        line_number += 1;
+                        return;
+                    }
+                    IDENT_STATE if line[0] != b'@' => {
+                        *parse_error =
+                            make_error(line_number, "expected a fastq header starting with '@'");
+                        return;
                     }
+                    QUAL_STATE if !skipped_plus && line.first() != Some(&b'+') => {
+                        *parse_error = make_error(line_number, "expected a '+' separator line");
+                        return;
+                    }
+                    _ => {}
+                }
+            }
+            new_line = !partial;
 
+            if state == QUAL_STATE {
+                if !skipped_plus {
                     if !partial {
-                        Self::normalize_sequence(&mut intermediate[SEQ_STATE]);
-                        func(FastaSequence {
-                            ident: &intermediate[IDENT_STATE],
-                            seq: &intermediate[SEQ_STATE],
-                            qual: if get_quality {
-                                Some(&intermediate[QUAL_STATE])
-                            } else {
-                                None
-                            },
-                        });
-
-                        intermediate[IDENT_STATE].clear();
-                        intermediate[SEQ_STATE].clear();
-                        intermediate[QUAL_STATE].clear();
-
-                        skipped_plus = false;
+                        skipped_plus = true;
+                        line_number += 1;
                     }
-                } else {
+                    return;
+                }
+
+                if get_quality {
                     intermediate[state].extend_from_slice(line);
                 }
 
                 if !partial {
-                    state = (state + 1) % 3;
+                    if get_quality
+                        && intermediate[QUAL_STATE].len() != intermediate[SEQ_STATE].len()
+                    {
+                        *parse_error = make_error(line_number, QUALITY_LENGTH_ERROR);
+                        return;
+                    }
+                    Self::normalize_sequence(&mut intermediate[SEQ_STATE]);
+                    func(FastaSequence {
+                        ident: &intermediate[IDENT_STATE],
+                        seq: &intermediate[SEQ_STATE],
+                        qual: if get_quality {
+                            Some(&intermediate[QUAL_STATE])
+                        } else {
+                            None
+                        },
+                    });
+
+                    intermediate[IDENT_STATE].clear();
+                    intermediate[SEQ_STATE].clear();
+                    intermediate[QUAL_STATE].clear();
+
+                    skipped_plus = false;
                 }
-            },
-            remove_file,
+            } else {
+                intermediate[state].extend_from_slice(line);
+            }
+
+            if !partial {
+                state = (state + 1) % 3;
+                line_number += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::errors::GGCATError;
+    use crate::sequences_reader::SequencesReader;
+    use crate::test_utils::TestTempDir;
+
+    /// Reads the sequences and the qualities of a temporary file with the given content
+    fn read_sequences(
+        name: &str,
+        content: &[u8],
+        get_quality: bool,
+    ) -> Result<Vec<(Vec<u8>, Option<Vec<u8>>)>, GGCATError> {
+        let temp_dir = TestTempDir::new("sequences");
+        let file = temp_dir.file(name);
+        std::fs::write(&file, content).unwrap();
+
+        let mut sequences = Vec::new();
+        SequencesReader::process_file_extended(
+            &file,
+            |seq| sequences.push((seq.seq.to_vec(), seq.qual.map(|q| q.to_vec()))),
+            None,
+            false,
+            get_quality,
+            false,
+        )
+        .map(|_| sequences)
+    }
+
+    fn assert_error_line(result: Result<Vec<(Vec<u8>, Option<Vec<u8>>)>, GGCATError>, line: u64) {
+        match result {
+            Err(GGCATError::InvalidInput {
+                line: error_line, ..
+            }) => assert_eq!(error_line, Some(line)),
+            _ => panic!("expected an invalid input error at line {}", line),
+        }
+    }
+
+    #[test]
+    fn format_detected_from_content() {
+        let fasta = read_sequences("fasta.txt", b"\n>read1\nACGT\nACGN\n>read2\nTTT\n", false);
+        assert_eq!(
+            fasta.unwrap(),
+            vec![(b"ACGTACGN".to_vec(), None), (b"TTT".to_vec(), None)]
         );
+
+        let fastq = read_sequences("fastq.txt", b"@read1\nacgt\n+\nIIII\n", true);
+        assert_eq!(
+            fastq.unwrap(),
+            vec![(b"ACGT".to_vec(), Some(b"IIII".to_vec()))]
+        );
+
+        assert!(matches!(
+            read_sequences("unknown.fa", b"ACGT\n", false),
+            Err(GGCATError::UnknownFileFormat { .. })
+        ));
+    }
+
+    #[test]
+    fn fastq_errors_line() {
+        // Missing separator of the second record
+        assert_error_line(
+            read_sequences(
+                "no-plus.fq",
+                b"@r1\nACGT\n+\nIIII\n@r2\nACGT\nIIII\n",
+                false,
+            ),
+            7,
+        );
+        // Record not starting with a header
+        assert_error_line(
+            read_sequences("no-header.fq", b"@r1\nACGT\n+\nIIII\nACGT\n", false),
+            5,
+        );
+        assert_error_line(read_sequences("truncated.fq", b"@r1\nACGT\n", false), 3);
+
+        // The quality length is checked only when the qualities are used
+        assert_error_line(read_sequences("qual.fq", b"@r1\nACGT\n+\nIII\n", true), 4);
+        assert!(read_sequences("qual.fq", b"@r1\nACGT\n+\nIII\n", false).is_ok());
+    }
+
+    #[test]
+    fn input_errors() {
+        let temp_dir = TestTempDir::new("sequences-errors");
+        let missing_file = temp_dir.file("missing.fa");
+        match SequencesReader::process_file_extended(
+            &missing_file,
+            |_| {},
+            None,
+            false,
+            false,
+            false,
+        ) {
+            Err(GGCATError::FileOpen { file, .. }) => assert_eq!(file, missing_file),
+            _ => panic!("expected a file open error"),
+        }
+
+        assert!(matches!(
+            read_sequences("binary.fa", &[0x00, 0x01, 0x02, 0x03, b'\n'], false),
+            Err(GGCATError::UnknownFileFormat { .. })
+        ));
+    }
+
+    #[test]
+    fn missing_input_file() {
+        let temp_dir = TestTempDir::new("sequences-missing");
+        assert!(matches!(
+            SequencesReader::check_input_file(temp_dir.file("missing.fa")),
+            Err(GGCATError::FileOpen { .. })
+        ));
+        assert!(SequencesReader::check_input_file("-").is_ok());
     }
 }
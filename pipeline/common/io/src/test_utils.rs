@@ -0,0 +1,40 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Temporary directory for the files of a test, unique for each test and process so that
+/// concurrent test runs never share their files. It is removed with all its files when dropped,
+/// also when the test fails
+pub struct TestTempDir {
+    path: PathBuf,
+}
+
+impl TestTempDir {
+    pub fn new(test_name: &str) -> Self {
+        static DIRS_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+        let path = std::env::temp_dir().join(format!(
+            "ggcat-test-{}-{}-{}",
+            test_name,
+            std::process::id(),
+            DIRS_COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir_all(&path).unwrap();
+
+        Self { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the path of a file inside the directory
+    pub fn file(&self, name: impl AsRef<Path>) -> PathBuf {
+        self.path.join(name)
+    }
+}
+
+impl Drop for TestTempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
@@ -6,6 +6,8 @@ mod queue_data;
 mod reader;
 mod sequences_splitter;
 
+pub use crate::sequences_splitter::QualityFilter;
+
 use crate::counters_analyzer::CountersAnalyzer;
 use crate::queue_data::MinimizerBucketingQueueData;
 use crate::reader::MinimizerBucketingFilesReader;
@@ -20,7 +22,9 @@ use hashes::HashableSequence;
 use io::compressed_read::CompressedRead;
 use io::concurrent::temp_reads::creads_utils::CompressedReadsBucketHelper;
 use io::concurrent::temp_reads::extra_data::SequenceExtraData;
-use io::sequences_reader::FastaSequence;
+use io::errors::GGCATError;
+use io::lines_reader::STDIN_PATH;
+use io::sequences_reader::{FastaSequence, SequencesReader};
 use parallel_processor::buckets::concurrent::{BucketsThreadBuffer, BucketsThreadDispatcher};
 use parallel_processor::buckets::writers::compressed_binary_writer::CompressedBinaryWriter;
 use parallel_processor::buckets::MultiThreadBuckets;
@@ -32,8 +36,9 @@ use parallel_processor::execution_manager::executor_address::ExecutorAddress;
 use parallel_processor::execution_manager::memory_tracker::MemoryTracker;
 use parallel_processor::execution_manager::thread_pool::ExecThreadPool;
 use parallel_processor::execution_manager::units_io::{ExecutorInput, ExecutorInputAddressMode};
+use parallel_processor::memory_fs::{MemoryFs, RemoveFileMode};
 use parallel_processor::phase_times_monitor::PHASES_TIMES_MONITOR;
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 use std::cmp::max;
 use std::future::Future;
 use std::marker::PhantomData;
@@ -186,6 +191,10 @@ pub struct MinimizerBucketingExecutionContext<GlobalData> {
 
     pub partial_read_copyback: Option<usize>,
     pub copy_ident: bool,
+    pub quality_filter: QualityFilter,
+
+    /// The first error encountered while reading the input files
+    pub reading_error: Mutex<Option<GGCATError>>,
 }
 
 pub struct GenericMinimizerBucketing;
@@ -221,7 +230,8 @@ impl<E: MinimizerBucketingExecutorFactory + Sync + Send + 'static> MinimizerBuck
 
         while let Some(input_packet) = ops.receive_packet().await {
             let mut total_bases = 0;
-            let mut sequences_splitter = SequencesSplitter::new(context.common.k);
+            let mut sequences_splitter =
+                SequencesSplitter::new(context.common.k, &context.quality_filter);
             let mut buckets_processor = E::new(&context.common);
 
             let mut sequences_count = 0;
@@ -399,7 +409,8 @@ impl GenericMinimizerBucketing {
         global_data: E::GlobalData,
         partial_read_copyback: Option<usize>,
         copy_ident: bool,
-    ) -> (Vec<PathBuf>, PathBuf) {
+        quality_filter: QualityFilter,
+    ) -> Result<(Vec<PathBuf>, PathBuf), GGCATError> {
         let read_threads_count = max(1, threads_count / 2);
         let compute_threads_count = max(1, threads_count.saturating_sub(read_threads_count / 4));
 
@@ -413,11 +424,23 @@ impl GenericMinimizerBucketing {
             ),
         ));
 
-        input_files.sort_by_cached_key(|(file, _)| {
-            std::fs::metadata(file)
-                .expect(&format!("Error while opening file {}", file.display()))
-                .len()
-        });
+        for (file, _) in input_files.iter() {
+            SequencesReader::check_input_file(file)?;
+        }
+
+        if input_files
+            .iter()
+            .filter(|(file, _)| file.as_os_str() == STDIN_PATH)
+            .count()
+            > 1
+        {
+            return Err(GGCATError::InvalidConfig(
+                "the standard input can be used only once as input".to_string(),
+            ));
+        }
+
+        input_files
+            .sort_by_cached_key(|(file, _)| std::fs::metadata(file).map(|m| m.len()).unwrap_or(0));
         input_files.reverse();
 
         let second_buckets_count = max(
@@ -444,6 +467,8 @@ impl GenericMinimizerBucketing {
             partial_read_copyback,
             read_threads_count,
             copy_ident,
+            quality_filter,
+            reading_error: Mutex::new(None),
         });
 
         {
@@ -511,6 +536,14 @@ impl GenericMinimizerBucketing {
         let global_context = Arc::try_unwrap(global_context)
             .unwrap_or_else(|_| panic!("Cannot get execution context!"));
 
+        if let Some(err) = global_context.reading_error.into_inner() {
+            // The partially written buckets are not used
+            for bucket in global_context.buckets.finalize() {
+                let _ = MemoryFs::remove_file(&bucket, RemoveFileMode::Remove { remove_fs: true });
+            }
+            return Err(err);
+        }
+
         let common_context = Arc::try_unwrap(global_context.common)
             .unwrap_or_else(|_| panic!("Cannot get common execution context!"));
 
@@ -521,6 +554,6 @@ impl GenericMinimizerBucketing {
 
         counters_analyzer.serialize_to_file(&counters_file);
 
-        (global_context.buckets.finalize(), counters_file)
+        Ok((global_context.buckets.finalize(), counters_file))
     }
 }
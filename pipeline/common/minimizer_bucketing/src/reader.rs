@@ -33,6 +33,12 @@ impl<GlobalData: Sync + Send + 'static, FileInfo: Clone + Sync + Send + Default
         let packets_pool = ops.pool_alloc_await(0).await;
 
         while let Some(input_packet) = ops.receive_packet().await {
+            // The remaining files are skipped after the first error, as the bucketing fails anyway
+            if context.reading_error.lock().is_some() {
+                context.processed_files.fetch_add(1, Ordering::Relaxed);
+                continue;
+            }
+
             let mut data_packet = packets_pool.alloc_packet().await;
             let file_info = input_packet.1.clone();
 
@@ -46,20 +52,19 @@ impl<GlobalData: Sync + Send + 'static, FileInfo: Clone + Sync + Send + Default
 
             let mut max_len = 0;
 
-            SequencesReader::process_file_extended(
+            let read_result = SequencesReader::process_file_extended(
                 &input_packet.0,
                 |x| {
                     let mut data = data_packet.deref_mut();
 
-                    if x.seq.len() < context.common.k {
-                        return;
-                    }
-
                     max_len = max(
                         max_len,
                         x.ident.len() + x.seq.len() + x.qual.map(|q| q.len()).unwrap_or(0),
                     );
 
+                    // The sequences shorter than k are kept too, as the index of each sequence
+                    // in the packet must match its position in the file. They are then discarded
+                    // by the sequences splitter
                     if unlikely(!data.push_sequences(x)) {
                         assert!(
                             data.start_read_index as usize + data.sequences.len()
@@ -93,9 +98,17 @@ impl<GlobalData: Sync + Send + 'static, FileInfo: Clone + Sync + Send + Default
                 },
                 context.partial_read_copyback,
                 context.copy_ident,
+                context.quality_filter.is_enabled(),
                 false,
             );
 
+            if let Err(err) = read_result {
+                let mut reading_error = context.reading_error.lock();
+                if reading_error.is_none() {
+                    *reading_error = Some(err);
+                }
+            }
+
             if data_packet.sequences.len() > 0 {
                 ops.packet_send(
                     context
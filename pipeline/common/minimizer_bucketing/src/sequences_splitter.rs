@@ -1,14 +1,72 @@
 use io::sequences_reader::FastaSequence;
 use std::ops::Range;
 
+const PHRED_OFFSET: u8 = 33;
+
+/// Filter applied to the reads using the fastq quality scores, fasta files are not filtered
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct QualityFilter {
+    /// Bases with a lower Phred score are treated as unknown bases, splitting the reads
+    pub min_base_quality: Option<u8>,
+    /// Kmers with a lower probability of being correct are discarded
+    pub min_kmer_probability: Option<f64>,
+}
+
+impl QualityFilter {
+    pub fn is_enabled(&self) -> bool {
+        self.min_base_quality.is_some() || self.min_kmer_probability.is_some()
+    }
+}
+
+struct QualityThresholds {
+    min_quality_char: u8,
+    min_log_probability: f64,
+    /// Log-probability of each base being correct, indexed by quality character
+    log_probabilities: [f64; 256],
+}
+
+impl QualityThresholds {
+    fn new(filter: &QualityFilter) -> Self {
+        let mut log_probabilities = [0.0; 256];
+        for (qual_char, log_prob) in log_probabilities.iter_mut().enumerate() {
+            let phred = (qual_char as u8).saturating_sub(PHRED_OFFSET) as f64;
+            // Avoid infinite values, as they are subtracted when moving the kmer window
+            *log_prob = (1.0 - 10.0f64.powf(-phred / 10.0))
+                .max(f64::MIN_POSITIVE)
+                .ln();
+        }
+
+        Self {
+            min_quality_char: filter
+                .min_base_quality
+                .map(|q| q.saturating_add(PHRED_OFFSET))
+                .unwrap_or(0),
+            min_log_probability: filter
+                .min_kmer_probability
+                .map(|p| p.ln())
+                .unwrap_or(f64::NEG_INFINITY),
+            log_probabilities,
+        }
+    }
+}
+
 pub struct SequencesSplitter {
     k: usize,
+    quality_thresholds: Option<QualityThresholds>,
     pub valid_bases: u64,
 }
 
 impl SequencesSplitter {
-    pub fn new(k: usize) -> Self {
-        Self { k, valid_bases: 0 }
+    pub fn new(k: usize, quality_filter: &QualityFilter) -> Self {
+        Self {
+            k,
+            quality_thresholds: if quality_filter.is_enabled() {
+                Some(QualityThresholds::new(quality_filter))
+            } else {
+                None
+            },
+            valid_bases: 0,
+        }
     }
 
     #[inline]
@@ -33,9 +91,163 @@ impl SequencesSplitter {
             }
             // If the length of the read is long enough, return it
             if end - start >= self.k {
-                self.valid_bases += (end - start) as u64;
-                process_fn(&fasta_seq.seq[start..end], start..end);
+                match (&self.quality_thresholds, fasta_seq.qual) {
+                    (Some(thresholds), Some(qual)) => Self::split_by_quality(
+                        self.k,
+                        thresholds,
+                        &mut self.valid_bases,
+                        fasta_seq.seq,
+                        qual,
+                        start..end,
+                        &mut process_fn,
+                    ),
+                    _ => {
+                        self.valid_bases += (end - start) as u64;
+                        process_fn(&fasta_seq.seq[start..end], start..end);
+                    }
+                }
             }
         }
     }
+
+    /// Splits the sequence so that each returned part contains only kmers without low quality bases
+    /// and with a probability of being correct not lower than the threshold
+    fn split_by_quality(
+        k: usize,
+        thresholds: &QualityThresholds,
+        valid_bases: &mut u64,
+        seq: &[u8],
+        qual: &[u8],
+        range: Range<usize>,
+        process_fn: &mut impl FnMut(&[u8], Range<usize>),
+    ) {
+        let mut emit_part = |part: Range<usize>| {
+            *valid_bases += part.len() as u64;
+            process_fn(&seq[part.clone()], part);
+        };
+
+        // Start of the current stretch of bases with a good quality
+        let mut good_start = range.start;
+        // Start of the first kmer of the current stretch of valid kmers
+        let mut valid_start = None;
+        let mut log_probability = 0.0;
+
+        for pos in range.clone() {
+            let qual_char = qual[pos];
+
+            if qual_char < thresholds.min_quality_char {
+                if let Some(valid_start) = valid_start.take() {
+                    emit_part(valid_start..pos);
+                }
+                good_start = pos + 1;
+                log_probability = 0.0;
+                continue;
+            }
+
+            log_probability += thresholds.log_probabilities[qual_char as usize];
+            if pos + 1 - good_start > k {
+                log_probability -= thresholds.log_probabilities[qual[pos - k] as usize];
+            }
+
+            if pos + 1 - good_start >= k {
+                if log_probability >= thresholds.min_log_probability {
+                    if valid_start.is_none() {
+                        valid_start = Some(pos + 1 - k);
+                    }
+                } else if let Some(valid_start) = valid_start.take() {
+                    // The last valid kmer ends just before the current base
+                    emit_part(valid_start..pos);
+                }
+            }
+        }
+
+        if let Some(valid_start) = valid_start {
+            emit_part(valid_start..range.end);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sequences_splitter::{QualityFilter, SequencesSplitter};
+    use io::sequences_reader::FastaSequence;
+    use std::ops::Range;
+
+    fn split(
+        k: usize,
+        quality_filter: QualityFilter,
+        seq: &[u8],
+        qual: Option<&str>,
+    ) -> Vec<Range<usize>> {
+        let mut splitter = SequencesSplitter::new(k, &quality_filter);
+        let mut parts = Vec::new();
+        splitter.process_sequences(
+            &FastaSequence {
+                ident: b"",
+                seq,
+                qual: qual.map(str::as_bytes),
+            },
+            |part, range| {
+                assert_eq!(part, &seq[range.clone()]);
+                parts.push(range);
+            },
+        );
+        assert_eq!(
+            splitter.valid_bases,
+            parts.iter().map(|part| part.len() as u64).sum::<u64>()
+        );
+        parts
+    }
+
+    #[test]
+    fn unknown_bases_split() {
+        let no_filter = QualityFilter::default();
+        assert_eq!(
+            split(4, no_filter, b"ACGTNNACGTACGT", None),
+            vec![0..4, 6..14]
+        );
+        // Parts shorter than k are dropped
+        assert_eq!(split(3, no_filter, b"ACNGTNACG", None), vec![6..9]);
+    }
+
+    #[test]
+    fn low_quality_bases_split() {
+        let filter = QualityFilter {
+            min_base_quality: Some(20),
+            min_kmer_probability: None,
+        };
+        assert_eq!(
+            split(3, filter, b"ACGTACGTAC", Some("IIIII#IIII")),
+            vec![0..5, 6..10]
+        );
+        assert_eq!(
+            split(3, filter, b"ACGTACGTAC", Some("II#IIIIIII")),
+            vec![3..10]
+        );
+        // Fasta sequences are not filtered
+        assert_eq!(split(3, filter, b"ACGTACGTAC", None), vec![0..10]);
+    }
+
+    #[test]
+    fn low_probability_kmers_split() {
+        // Phred 40 bases are correct with probability 0.9999, phred 10 ones with probability 0.9
+        let filter = QualityFilter {
+            min_base_quality: None,
+            min_kmer_probability: Some(0.95),
+        };
+        // The kmers starting at 3, 4 and 5 contain the low quality base
+        assert_eq!(
+            split(3, filter, b"ACGTACGTAC", Some("IIIII+IIII")),
+            vec![0..5, 6..10]
+        );
+        assert_eq!(
+            split(3, filter, b"ACGTACGTAC", Some("IIIIIIIIII")),
+            vec![0..10]
+        );
+        // Phred 15 bases are accepted alone, but not two in the same kmer
+        assert_eq!(
+            split(3, filter, b"ACGTACGTAC", Some("IIII00IIII")),
+            vec![0..5, 5..10]
+        );
+    }
 }
@@ -3,9 +3,11 @@ use std::cell::Cell;
 use std::mem::size_of;
 
 const USED_MARKER: usize = usize::MAX >> FLAGS_COUNT;
-const FLAGS_COUNT: usize = 2;
+const FLAGS_COUNT: usize = 3;
 const FLAGS_SHIFT: usize = size_of::<usize>() * 8 - FLAGS_COUNT;
 const COUNTER_MASK: usize = (1 << FLAGS_SHIFT) - 1;
+const READ_FLAGS_MASK: usize = (READ_FLAG_INCL_BEGIN | READ_FLAG_INCL_END) as usize;
+const ALWAYS_KEPT_FLAG: usize = 1 << (FLAGS_SHIFT + 2);
 
 pub const COUNTER_BITS: usize = FLAGS_SHIFT;
 
@@ -57,7 +59,19 @@ impl<CHI> MapEntry<CHI> {
 
     #[inline(always)]
     pub fn get_flags(&self) -> u8 {
-        (self.count_flags.get() >> FLAGS_SHIFT) as u8
+        ((self.count_flags.get() >> FLAGS_SHIFT) & READ_FLAGS_MASK) as u8
+    }
+
+    /// Marks the kmer to be kept regardless of its multiplicity
+    #[inline(always)]
+    pub fn set_always_kept(&mut self) {
+        self.count_flags
+            .set(self.count_flags.get() | ALWAYS_KEPT_FLAG);
+    }
+
+    #[inline(always)]
+    pub fn is_always_kept(&self) -> bool {
+        self.count_flags.get() & ALWAYS_KEPT_FLAG != 0
     }
 
     pub fn get_kmer_multiplicity(&self) -> usize {
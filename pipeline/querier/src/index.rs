@@ -0,0 +1,101 @@
+use crate::pipeline::querier_minimizer_bucketing::minimizer_bucketing;
+use crate::pipeline::query_index::{build_query_index, QueryIndexInfo};
+use ::static_dispatch::static_dispatch;
+use colors::colors_manager::{ColorsManager, ColorsMergeManager};
+use hashes::{HashFunctionFactory, MinimizerHashFunctionFactory};
+use io::compute_buckets_log_from_input_files;
+use io::errors::GGCATError;
+use parallel_processor::phase_times_monitor::PHASES_TIMES_MONITOR;
+use std::fs::create_dir_all;
+use std::path::PathBuf;
+
+/// Builds a persistent index of the graph k-mers and their colors, that can be
+/// passed to `run_query` in place of the graph to avoid bucketing the graph for each query
+#[static_dispatch(BucketingHash = [
+    hashes::cn_nthash::CanonicalNtHashIteratorFactory,
+    #[cfg(not(feature = "devel-build"))]  hashes::fw_nthash::ForwardNtHashIteratorFactory
+], MergingHash = [
+    #[cfg(not(feature = "devel-build"))] hashes::fw_seqhash::u16::ForwardSeqHashFactory,
+    #[cfg(not(feature = "devel-build"))] hashes::fw_seqhash::u32::ForwardSeqHashFactory,
+    #[cfg(not(feature = "devel-build"))] hashes::fw_seqhash::u64::ForwardSeqHashFactory,
+    #[cfg(not(feature = "devel-build"))] hashes::fw_seqhash::u128::ForwardSeqHashFactory,
+    #[cfg(not(feature = "devel-build"))] hashes::fw_rkhash::u32::ForwardRabinKarpHashFactory,
+    #[cfg(not(feature = "devel-build"))] hashes::fw_rkhash::u64::ForwardRabinKarpHashFactory,
+    #[cfg(not(feature = "devel-build"))] hashes::fw_rkhash::u128::ForwardRabinKarpHashFactory,
+    #[cfg(not(feature = "devel-build"))] hashes::cn_seqhash::u16::CanonicalSeqHashFactory,
+    #[cfg(not(feature = "devel-build"))] hashes::cn_seqhash::u32::CanonicalSeqHashFactory,
+    hashes::cn_seqhash::u64::CanonicalSeqHashFactory,
+    #[cfg(not(feature = "devel-build"))] hashes::cn_seqhash::u128::CanonicalSeqHashFactory,
+    #[cfg(not(feature = "devel-build"))] hashes::cn_rkhash::u32::CanonicalRabinKarpHashFactory,
+    #[cfg(not(feature = "devel-build"))] hashes::cn_rkhash::u64::CanonicalRabinKarpHashFactory,
+    #[cfg(not(feature = "devel-build"))] hashes::cn_rkhash::u128::CanonicalRabinKarpHashFactory,
+], QuerierColorsManager = [
+    #[cfg(not(feature = "devel-build"))] colors::bundles::graph_querying::ColorBundleGraphQuerying,
+    colors::non_colored::NonColoredManager,
+])]
+pub fn run_index<
+    BucketingHash: MinimizerHashFunctionFactory,
+    MergingHash: HashFunctionFactory,
+    QuerierColorsManager: ColorsManager,
+>(
+    k: usize,
+    m: usize,
+    graph_input: PathBuf,
+    index_dir: PathBuf,
+    temp_dir: PathBuf,
+    buckets_count_log: Option<usize>,
+    threads_count: usize,
+) -> Result<(), GGCATError> {
+    PHASES_TIMES_MONITOR.write().init();
+
+    BucketingHash::initialize(k);
+    MergingHash::initialize(k);
+
+    create_dir_all(&index_dir).map_err(|error| GGCATError::FileOpen {
+        file: index_dir.clone(),
+        error,
+    })?;
+
+    if QuerierColorsManager::COLORS_ENABLED {
+        // Check the colormap before copying it into the index
+        let colormap_file = graph_input.with_extension("colors.dat");
+        QuerierColorsManager::ColorsMergeManagerType::<BucketingHash, MergingHash>::open_colors_table(
+            colormap_file.clone(),
+        )?;
+        std::fs::copy(&colormap_file, QueryIndexInfo::colormap_path(&index_dir)).map_err(
+            |error| GGCATError::FileOpen {
+                file: colormap_file,
+                error,
+            },
+        )?;
+    }
+
+    let buckets_count_log = match buckets_count_log {
+        Some(buckets_count_log) => buckets_count_log,
+        None => compute_buckets_log_from_input_files(&[graph_input.clone()])?,
+    };
+    let buckets_count = 1 << buckets_count_log;
+
+    let ((buckets, _), _) = minimizer_bucketing::<BucketingHash, QuerierColorsManager>(
+        Some(graph_input),
+        None,
+        temp_dir.as_path(),
+        buckets_count,
+        threads_count,
+        k,
+        m,
+    )?;
+
+    build_query_index::<BucketingHash, MergingHash, QuerierColorsManager>(buckets, &index_dir, k);
+
+    QueryIndexInfo::new::<BucketingHash, MergingHash, QuerierColorsManager>(k, m, buckets_count)
+        .save(&index_dir)?;
+
+    PHASES_TIMES_MONITOR
+        .write()
+        .print_stats("Index completed.".to_string());
+
+    println!("Index saved to: {}", index_dir.display());
+
+    Ok(())
+}
@@ -4,26 +4,37 @@
 #![feature(int_roundings)]
 
 use crate::pipeline::colored_query_output::colored_query_output;
+pub use crate::pipeline::colored_query_output::QueryMatchesFilter;
 use crate::pipeline::colormap_reading::colormap_reading;
 use crate::pipeline::counters_sorting::counters_sorting;
+use crate::pipeline::matching_unitigs_output::matching_unitigs_output;
 use crate::pipeline::parallel_kmers_query::parallel_kmers_counting;
+use crate::pipeline::positional_query_output::{positional_query_output, QueryHitsBuckets};
 use crate::pipeline::querier_minimizer_bucketing::minimizer_bucketing;
+use crate::pipeline::query_index::{build_query_index, index_kmers_counting, QueryIndexInfo};
 use ::static_dispatch::static_dispatch;
 use colors::colors_manager::{ColorMapReader, ColorsManager, ColorsMergeManager};
-use colors::DefaultColorsSerializer;
+use colors::storage::indexed_reader::ColorsIndexedReader;
+use config::{ColorIndexType, KEEP_FILES};
 use hashes::{HashFunctionFactory, MinimizerHashFunctionFactory};
+use io::errors::GGCATError;
+use io::lines_reader::LinesReader;
 use io::sequences_reader::SequencesReader;
 use io::{compute_buckets_log_from_input_files, generate_bucket_names};
+use parallel_processor::memory_fs::RemoveFileMode;
 use parallel_processor::phase_times_monitor::PHASES_TIMES_MONITOR;
-use std::fs::File;
-use std::io::{BufRead, BufReader};
-use std::path::PathBuf;
+use std::collections::HashSet;
+use std::fs::create_dir_all;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
 
+pub mod index;
 mod pipeline;
+pub mod server;
 mod sparse_fenwick;
 mod structs;
 
-#[derive(Debug, PartialOrd, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialOrd, PartialEq)]
 pub enum QuerierStartingStep {
     MinimizerBucketing = 0,
     KmersCounting = 1,
@@ -61,116 +72,655 @@ pub fn run_query<
     k: usize,
     m: usize,
     step: QuerierStartingStep,
-    graph_input: PathBuf,
+    graph_inputs: Vec<PathBuf>,
     query_input: PathBuf,
     output_file: PathBuf,
     temp_dir: PathBuf,
     buckets_count_log: Option<usize>,
     threads_count: usize,
-) {
+    numeric_ids: bool,
+    matches_filter: QueryMatchesFilter,
+    positional_hits: bool,
+    matching_unitigs: Option<u64>,
+) -> Result<(), GGCATError> {
+    matches_filter.validate()?;
+
+    if matching_unitigs == Some(0) {
+        return Err(GGCATError::InvalidConfig(
+            "the minimum number of kmers shared with the matching unitigs must be at least 1"
+                .to_string(),
+        ));
+    }
+
+    if graph_inputs.is_empty() {
+        return Err(GGCATError::InvalidConfig(
+            "at least one graph must be queried".to_string(),
+        ));
+    }
+
+    // The queries are read once to collect their headers and again while bucketing
+    if LinesReader::is_stream(&query_input) {
+        return Err(GGCATError::InvalidConfig(
+            "the queries cannot be read from the standard input or from a named pipe".to_string(),
+        ));
+    }
+
+    if graph_inputs.len() > 1 && step != QuerierStartingStep::MinimizerBucketing {
+        return Err(GGCATError::InvalidConfig(
+            "the starting step can be changed only when querying a single graph".to_string(),
+        ));
+    }
+
     PHASES_TIMES_MONITOR.write().init();
 
     BucketingHash::initialize(k);
     MergingHash::initialize(k);
 
-    let color_map = QuerierColorsManager::ColorsMergeManagerType::<BucketingHash, MergingHash>::open_colors_table(
-        graph_input.with_extension("colors.dat"),
-    );
+    let multiple_graphs = graph_inputs.len() > 1;
+    let mut graph_names = HashSet::new();
 
-    let buckets_count_log = buckets_count_log.unwrap_or_else(|| {
-        compute_buckets_log_from_input_files(&[graph_input.clone(), query_input.clone()])
-    });
-    let buckets_count = 1 << buckets_count_log;
+    let graphs = graph_inputs
+        .into_iter()
+        .map(|graph_input| {
+            // The results of each graph are written to a different file, tagged with the graph name
+            let graph_output_file = if multiple_graphs {
+                let graph_name = get_graph_name(&graph_input);
+                if !graph_names.insert(graph_name.clone()) {
+                    return Err(GGCATError::InvalidConfig(format!(
+                        "multiple queried graphs are named '{}'",
+                        graph_name
+                    )));
+                }
+                get_graph_output_file(&output_file, &graph_name)
+            } else {
+                output_file.clone()
+            };
 
-    let ((buckets, counters), queries_count) = if step <= QuerierStartingStep::MinimizerBucketing {
-        minimizer_bucketing::<BucketingHash, QuerierColorsManager>(
-            graph_input.clone(),
-            query_input.clone(),
-            temp_dir.as_path(),
-            buckets_count,
-            threads_count,
-            k,
-            m,
-        )
-    } else {
-        (
+            QueriedGraph::open::<BucketingHash, MergingHash, QuerierColorsManager>(
+                graph_input,
+                graph_output_file,
+                k,
+                m,
+            )
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // The unitigs are identified while bucketing the graph together with the queries
+    if matching_unitigs.is_some()
+        && (multiple_graphs || graphs.iter().any(|graph| graph.index.is_some()))
+    {
+        return Err(GGCATError::InvalidConfig(
+            "the matching unitigs can be written only when querying a single graph file"
+                .to_string(),
+        ));
+    }
+
+    // The query buckets must match the ones of the indices
+    let buckets_count = match (get_indices_buckets_count(&graphs)?, buckets_count_log) {
+        (Some(buckets_count), _) => buckets_count,
+        (None, Some(buckets_count_log)) => 1 << buckets_count_log,
+        (None, None) => {
+            let mut input_files: Vec<_> = graphs.iter().map(|graph| graph.input.clone()).collect();
+            input_files.push(query_input.clone());
+            1 << compute_buckets_log_from_input_files(&input_files)?
+        }
+    };
+
+    let queries = QuerySequences::read(query_input.clone(), k)?;
+
+    let output_settings = QueryOutputSettings {
+        k,
+        step,
+        temp_dir: temp_dir.clone(),
+        buckets_count,
+        numeric_ids,
+        matches_filter,
+        matching_unitigs,
+    };
+
+    let new_hits_buckets = |queries_count| {
+        if positional_hits {
+            Some(QueryHitsBuckets::new(
+                temp_dir.as_path(),
+                "query_hits",
+                buckets_count,
+                queries_count,
+            ))
+        } else {
+            None
+        }
+    };
+
+    if let [graph] = graphs.as_slice() {
+        let (buckets, counters) = if step <= QuerierStartingStep::MinimizerBucketing {
+            minimizer_bucketing::<BucketingHash, QuerierColorsManager>(
+                match &graph.index {
+                    Some(_) => None,
+                    None => Some(graph.input.clone()),
+                },
+                Some(query_input.clone()),
+                temp_dir.as_path(),
+                buckets_count,
+                threads_count,
+                k,
+                m,
+            )?
+            .0
+        } else {
             (
                 generate_bucket_names(temp_dir.join("bucket"), buckets_count, None),
                 temp_dir.join("buckets-counters.dat"),
-            ),
-            {
-                let queries_count = BufReader::new(File::open(&query_input).unwrap())
-                    .lines()
-                    .count() as u64
-                    / 2;
-                queries_count
-            },
-        )
-    };
+            )
+        };
 
-    let counters_buckets = if step <= QuerierStartingStep::KmersCounting {
-        parallel_kmers_counting::<BucketingHash, MergingHash, QuerierColorsManager, _>(
-            buckets,
-            counters,
-            buckets_count,
+        let (hits_buckets, unitigs_buckets) = if step <= QuerierStartingStep::KmersCounting {
+            (
+                new_hits_buckets(queries.count),
+                matching_unitigs.map(|_| {
+                    QueryHitsBuckets::new(
+                        temp_dir.as_path(),
+                        "query_unitigs",
+                        buckets_count,
+                        queries.count,
+                    )
+                }),
+            )
+        } else {
+            (None, None)
+        };
+
+        let counters_buckets = if step <= QuerierStartingStep::KmersCounting {
+            match &graph.index {
+                Some(index_info) => {
+                    index_kmers_counting::<BucketingHash, MergingHash, QuerierColorsManager>(
+                        buckets,
+                        index_info.kmers_buckets(&graph.input),
+                        temp_dir.as_path(),
+                        k,
+                        hits_buckets.as_ref(),
+                        RemoveFileMode::Remove {
+                            remove_fs: !KEEP_FILES.load(Ordering::Relaxed),
+                        },
+                        RemoveFileMode::Keep,
+                    )
+                }
+                None => {
+                    parallel_kmers_counting::<BucketingHash, MergingHash, QuerierColorsManager, _>(
+                        buckets,
+                        counters,
+                        buckets_count,
+                        temp_dir.as_path(),
+                        k,
+                        m,
+                        threads_count,
+                        hits_buckets.as_ref(),
+                        unitigs_buckets.as_ref(),
+                    )
+                }
+            }
+        } else {
+            generate_bucket_names(temp_dir.join("counters"), buckets_count, None)
+        };
+
+        let hits_buckets = match hits_buckets {
+            Some(hits_buckets) => Some(hits_buckets.finalize()),
+            None if positional_hits => Some(generate_bucket_names(
+                temp_dir.join("query_hits"),
+                buckets_count,
+                None,
+            )),
+            None => None,
+        };
+
+        let unitigs_buckets = match unitigs_buckets {
+            Some(unitigs_buckets) => Some(unitigs_buckets.finalize()),
+            None if matching_unitigs.is_some() => Some(generate_bucket_names(
+                temp_dir.join("query_unitigs"),
+                buckets_count,
+                None,
+            )),
+            None => None,
+        };
+
+        write_graph_results::<QuerierColorsManager>(
+            graph,
+            counters_buckets,
+            hits_buckets,
+            unitigs_buckets,
+            &queries,
+            &output_settings,
+        )?;
+    } else {
+        // The queries are bucketed only once, and each graph is joined with the query buckets
+        // as an index, so the query buckets are kept until the last graph is processed
+        let ((query_buckets, _), _) = minimizer_bucketing::<BucketingHash, QuerierColorsManager>(
+            None,
+            Some(query_input.clone()),
             temp_dir.as_path(),
+            buckets_count,
+            threads_count,
             k,
             m,
-            threads_count,
-        )
-    } else {
-        generate_bucket_names(temp_dir.join("counters"), buckets_count, None)
+        )?;
+
+        let remove_temp_files = RemoveFileMode::Remove {
+            remove_fs: !KEEP_FILES.load(Ordering::Relaxed),
+        };
+
+        for (graph_index, graph) in graphs.iter().enumerate() {
+            let (index_buckets, index_buckets_mode) = match &graph.index {
+                Some(index_info) => (index_info.kmers_buckets(&graph.input), RemoveFileMode::Keep),
+                None => {
+                    let graph_temp_dir = temp_dir.join(format!("graph{}", graph_index));
+                    create_dir_all(&graph_temp_dir).map_err(|error| GGCATError::FileOpen {
+                        file: graph_temp_dir.clone(),
+                        error,
+                    })?;
+
+                    let ((graph_buckets, _), _) =
+                        minimizer_bucketing::<BucketingHash, QuerierColorsManager>(
+                            Some(graph.input.clone()),
+                            None,
+                            graph_temp_dir.as_path(),
+                            buckets_count,
+                            threads_count,
+                            k,
+                            m,
+                        )?;
+
+                    (
+                        build_query_index::<BucketingHash, MergingHash, QuerierColorsManager>(
+                            graph_buckets,
+                            &graph_temp_dir,
+                            k,
+                        ),
+                        remove_temp_files,
+                    )
+                }
+            };
+
+            let hits_buckets = new_hits_buckets(queries.count);
+
+            let counters_buckets =
+                index_kmers_counting::<BucketingHash, MergingHash, QuerierColorsManager>(
+                    query_buckets.clone(),
+                    index_buckets,
+                    temp_dir.as_path(),
+                    k,
+                    hits_buckets.as_ref(),
+                    if graph_index == graphs.len() - 1 {
+                        remove_temp_files
+                    } else {
+                        RemoveFileMode::Keep
+                    },
+                    index_buckets_mode,
+                );
+
+            write_graph_results::<QuerierColorsManager>(
+                graph,
+                counters_buckets,
+                hits_buckets.map(|hits_buckets| hits_buckets.finalize()),
+                None,
+                &queries,
+                &output_settings,
+            )?;
+        }
+    }
+
+    PHASES_TIMES_MONITOR
+        .write()
+        .print_stats("Query completed.".to_string());
+
+    for graph in &graphs {
+        println!("Final output saved to: {}", graph.output_file.display());
+    }
+
+    Ok(())
+}
+
+/// A graph (or an index built by `run_index`) queried in the current run
+struct QueriedGraph {
+    input: PathBuf,
+    index: Option<QueryIndexInfo>,
+    colormap_file: PathBuf,
+    colors_count: u64,
+    output_file: PathBuf,
+}
+
+impl QueriedGraph {
+    fn open<
+        BucketingHash: MinimizerHashFunctionFactory,
+        MergingHash: HashFunctionFactory,
+        QuerierColorsManager: ColorsManager,
+    >(
+        input: PathBuf,
+        output_file: PathBuf,
+        k: usize,
+        m: usize,
+    ) -> Result<Self, GGCATError> {
+        // The graph input can also be an index directory built by run_index
+        let index = if QueryIndexInfo::is_index(&input) {
+            let index_info = QueryIndexInfo::load(&input)?;
+            index_info.check_compatible(
+                &input,
+                &QueryIndexInfo::new::<BucketingHash, MergingHash, QuerierColorsManager>(
+                    k,
+                    m,
+                    index_info.buckets_count,
+                ),
+            )?;
+            Some(index_info)
+        } else {
+            None
+        };
+
+        let colormap_file = match &index {
+            Some(_) => QueryIndexInfo::colormap_path(&input),
+            None => input.with_extension("colors.dat"),
+        };
+
+        let colors_count = QuerierColorsManager::ColorsMergeManagerType::<
+            BucketingHash,
+            MergingHash,
+        >::open_colors_table(colormap_file.clone())?
+        .colors_count();
+
+        Ok(Self {
+            input,
+            index,
+            colormap_file,
+            colors_count,
+            output_file,
+        })
+    }
+}
+
+/// Buckets count shared by all the queried indices, or None if no index is queried
+fn get_indices_buckets_count(graphs: &[QueriedGraph]) -> Result<Option<usize>, GGCATError> {
+    let mut indices = graphs
+        .iter()
+        .filter_map(|graph| graph.index.as_ref().map(|index| (graph, index)));
+
+    let first_index = match indices.next() {
+        None => return Ok(None),
+        Some((_, first_index)) => first_index,
     };
 
-    let colored_buckets_prefix = temp_dir.join("color_counters");
+    if let Some((graph, index_info)) =
+        indices.find(|(_, index_info)| index_info.buckets_count != first_index.buckets_count)
+    {
+        return Err(GGCATError::InvalidIndex {
+            dir: graph.input.clone(),
+            reason: format!(
+                "the index has {} buckets but another queried index has {}",
+                index_info.buckets_count, first_index.buckets_count
+            ),
+        });
+    }
+    Ok(Some(first_index.buckets_count))
+}
+
+/// The query sequences, shared by all the queried graphs
+struct QuerySequences {
+    input: PathBuf,
+    count: u64,
+    headers: Vec<String>,
+    kmers_count: Vec<u64>,
+}
 
-    let query_kmers_count = {
-        let mut sequences_lengths = vec![];
+impl QuerySequences {
+    /// Reads the headers and the kmers count of the queries. The queries shorter than k are kept
+    /// with no kmers, as their index is still the position in the file for the bucketing reader
+    fn read(input: PathBuf, k: usize) -> Result<Self, GGCATError> {
+        let mut headers = vec![];
+        let mut kmers_count = vec![];
         SequencesReader::process_file_extended(
-            &query_input,
+            &input,
             |seq| {
-                sequences_lengths.push((seq.seq.len() - k + 1) as u64);
+                // Skip the leading '>' or '@' of the ident line
+                headers.push(
+                    String::from_utf8_lossy(seq.ident.get(1..).unwrap_or(&[]))
+                        .trim_end()
+                        .to_string(),
+                );
+                kmers_count.push((seq.seq.len() + 1).saturating_sub(k) as u64);
             },
             None,
+            true,
             false,
             false,
-        );
-        sequences_lengths
-    };
+        )?;
 
-    let colored_buckets = if step <= QuerierStartingStep::CountersSorting {
+        Ok(Self {
+            input,
+            count: headers.len() as u64,
+            headers,
+            kmers_count,
+        })
+    }
+}
+
+struct QueryOutputSettings {
+    k: usize,
+    step: QuerierStartingStep,
+    temp_dir: PathBuf,
+    buckets_count: usize,
+    numeric_ids: bool,
+    matches_filter: QueryMatchesFilter,
+    /// Minimum number of kmers shared with a query to write a matching unitig
+    matching_unitigs: Option<u64>,
+}
+
+/// Name used to tag the results of a graph, the graph file name without extensions
+fn get_graph_name(graph_input: &Path) -> String {
+    graph_input
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .split('.')
+        .next()
+        .unwrap()
+        .to_string()
+}
+
+/// Adds the graph name to the output file name, before its extensions
+fn get_graph_output_file(output_file: &Path, graph_name: &str) -> PathBuf {
+    let file_name = output_file
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy();
+    output_file.with_file_name(match file_name.split_once('.') {
+        Some((name, extensions)) => format!("{}-{}.{}", name, graph_name, extensions),
+        None => format!("{}-{}", file_name, graph_name),
+    })
+}
+
+/// Sorts the query counters of a graph and writes its results
+fn write_graph_results<QuerierColorsManager: ColorsManager>(
+    graph: &QueriedGraph,
+    counters_buckets: Vec<PathBuf>,
+    hits_buckets: Option<Vec<PathBuf>>,
+    unitigs_buckets: Option<Vec<PathBuf>>,
+    queries: &QuerySequences,
+    settings: &QueryOutputSettings,
+) -> Result<(), GGCATError> {
+    let colored_buckets_prefix = settings.temp_dir.join("color_counters");
+
+    let colored_buckets = if settings.step <= QuerierStartingStep::CountersSorting {
         counters_sorting::<QuerierColorsManager>(
-            k,
-            query_input.clone(),
+            settings.k,
+            queries.input.clone(),
             counters_buckets,
             colored_buckets_prefix,
-            color_map.colors_count(),
-            output_file.clone(),
-            &query_kmers_count,
+            graph.colors_count,
+            graph.output_file.clone(),
+            &queries.kmers_count,
+            if settings.numeric_ids {
+                None
+            } else {
+                Some(queries.headers.as_slice())
+            },
         )
     } else {
-        generate_bucket_names(colored_buckets_prefix, buckets_count, None)
+        generate_bucket_names(colored_buckets_prefix, settings.buckets_count, None)
+    };
+
+    let color_names = if QuerierColorsManager::COLORS_ENABLED && !settings.numeric_ids {
+        Some(
+            ColorsIndexedReader::new(&graph.colormap_file)?
+                .color_names()
+                .to_vec(),
+        )
+    } else {
+        None
     };
 
     if QuerierColorsManager::COLORS_ENABLED {
-        let colormap_file = graph_input.with_extension("colors.dat");
-        let remapped_query_color_buckets = colormap_reading::<DefaultColorsSerializer>(
-            colormap_file,
+        let remapped_query_color_buckets = colormap_reading(
+            graph.colormap_file.clone(),
             colored_buckets,
-            temp_dir,
-            queries_count,
-        );
+            settings.temp_dir.clone(),
+            queries.count,
+        )?;
 
         colored_query_output::<QuerierColorsManager>(
             remapped_query_color_buckets,
-            output_file.clone(),
-            &query_kmers_count,
+            graph.output_file.clone(),
+            &queries.headers,
+            &queries.kmers_count,
+            color_names.as_deref(),
+            settings.matches_filter,
         );
     }
 
-    PHASES_TIMES_MONITOR
-        .write()
-        .print_stats("Query completed.".to_string());
+    if let Some(hits_buckets) = hits_buckets {
+        let positions_file = graph.output_file.with_extension("positions.jsonl");
+        if QuerierColorsManager::COLORS_ENABLED {
+            positional_query_output::<ColorIndexType>(
+                hits_buckets,
+                positions_file,
+                &queries.headers,
+                &queries.kmers_count,
+                Some(graph.colormap_file.clone()),
+                settings.numeric_ids,
+                color_names.as_deref(),
+            )?;
+        } else {
+            positional_query_output::<()>(
+                hits_buckets,
+                positions_file,
+                &queries.headers,
+                &queries.kmers_count,
+                None,
+                settings.numeric_ids,
+                None,
+            )?;
+        }
+    }
+
+    if let (Some(unitigs_buckets), Some(min_shared_kmers)) =
+        (unitigs_buckets, settings.matching_unitigs)
+    {
+        matching_unitigs_output(
+            unitigs_buckets,
+            &graph.input,
+            graph.output_file.with_extension("unitigs.fasta"),
+            &queries.headers,
+            settings.numeric_ids,
+            min_shared_kmers,
+        )?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pipeline::query_index::QueryIndexInfo;
+    use crate::{
+        get_graph_name, get_graph_output_file, get_indices_buckets_count, QueriedGraph,
+        QuerySequences,
+    };
+    use io::errors::GGCATError;
+    use io::test_utils::TestTempDir;
+    use std::fs::write;
+    use std::path::{Path, PathBuf};
 
-    println!("Final output saved to: {}", output_file.display());
+    fn queried_graph(input: &str, buckets_count: Option<usize>) -> QueriedGraph {
+        QueriedGraph {
+            input: PathBuf::from(input),
+            index: buckets_count.map(|buckets_count| QueryIndexInfo {
+                version: 1,
+                k: 31,
+                m: 12,
+                buckets_count,
+                bucketing_hash: String::new(),
+                merging_hash: String::new(),
+                colors: true,
+            }),
+            colormap_file: PathBuf::new(),
+            colors_count: 1,
+            output_file: PathBuf::new(),
+        }
+    }
+
+    #[test]
+    fn graphs_output_names() {
+        assert_eq!(
+            get_graph_name(Path::new("data/project_a.fasta.lz4")),
+            "project_a"
+        );
+        assert_eq!(get_graph_name(Path::new("indices/project_b")), "project_b");
+
+        assert_eq!(
+            get_graph_output_file(Path::new("results/output.jsonl.lz4"), "project_a"),
+            PathBuf::from("results/output-project_a.jsonl.lz4")
+        );
+        assert_eq!(
+            get_graph_output_file(Path::new("results/output"), "project_b"),
+            PathBuf::from("results/output-project_b")
+        );
+    }
+
+    #[test]
+    fn indices_buckets_count() {
+        assert_eq!(
+            get_indices_buckets_count(&[queried_graph("graph.fa", None)]).unwrap(),
+            None
+        );
+        assert_eq!(
+            get_indices_buckets_count(&[
+                queried_graph("graph.fa", None),
+                queried_graph("index_a", Some(256)),
+                queried_graph("index_b", Some(256)),
+            ])
+            .unwrap(),
+            Some(256)
+        );
+        match get_indices_buckets_count(&[
+            queried_graph("index_a", Some(256)),
+            queried_graph("index_b", Some(1024)),
+        ]) {
+            Err(GGCATError::InvalidIndex { dir, .. }) => assert_eq!(dir, PathBuf::from("index_b")),
+            _ => panic!("expected an invalid index error"),
+        }
+    }
+
+    #[test]
+    fn short_queries_kept() {
+        let temp_dir = TestTempDir::new("short-queries");
+        let query_file = temp_dir.file("queries.fa");
+        write(
+            &query_file,
+            ">first\nACGTACGTAC\n>short\nACG\n>last query\nACGTACGT\n",
+        )
+        .unwrap();
+
+        let queries = QuerySequences::read(query_file, 5).unwrap();
+
+        // The short query keeps its index, so the following ones are not shifted
+        assert_eq!(queries.count, 3);
+        assert_eq!(queries.headers, vec!["first", "short", "last query"]);
+        assert_eq!(queries.kmers_count, vec![6, 0, 4]);
+    }
 }
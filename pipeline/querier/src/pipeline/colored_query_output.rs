@@ -1,8 +1,9 @@
 use crate::structs::query_colored_counters::{ColorsRange, QueryColoredCounters};
 use colors::colors_manager::ColorsManager;
-use config::{DEFAULT_PREFETCH_AMOUNT, KEEP_FILES};
+use config::{ColorIndexType, DEFAULT_PREFETCH_AMOUNT, KEEP_FILES};
 use flate2::Compression;
 use hashbrown::HashMap;
+use io::errors::GGCATError;
 use io::get_bucket_index;
 use lz4::{BlockMode, BlockSize, ContentChecksum};
 use parallel_processor::buckets::readers::compressed_binary_reader::CompressedBinaryReader;
@@ -11,6 +12,8 @@ use parallel_processor::memory_fs::RemoveFileMode;
 use parallel_processor::phase_times_monitor::PHASES_TIMES_MONITOR;
 use parking_lot::{Condvar, Mutex};
 use rayon::prelude::*;
+use serde::{Serialize, Serializer};
+use std::collections::BTreeMap;
 use std::ffi::OsStr;
 use std::fs::File;
 use std::io::Write;
@@ -43,10 +46,98 @@ impl Write for QueryOutputFileWriter {
     }
 }
 
+/// Filters applied to the matches of each query, before writing them to the output
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct QueryMatchesFilter {
+    /// Colors matching a lower fraction of the query kmers are not reported
+    pub min_match_fraction: Option<f64>,
+    /// Only the colors with the highest match fractions are reported
+    pub top_n: Option<usize>,
+}
+
+impl QueryMatchesFilter {
+    pub fn validate(&self) -> Result<(), GGCATError> {
+        if let Some(min_match_fraction) = self.min_match_fraction {
+            if !(0.0..=1.0).contains(&min_match_fraction) {
+                return Err(GGCATError::InvalidConfig(format!(
+                    "the minimum match fraction must be between 0 and 1, found {}",
+                    min_match_fraction
+                )));
+            }
+        }
+        if self.top_n == Some(0) {
+            return Err(GGCATError::InvalidConfig(
+                "the number of top matches must be at least 1".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn apply(
+        &self,
+        fractions: impl Iterator<Item = (ColorIndexType, f64)>,
+    ) -> BTreeMap<ColorIndexType, f64> {
+        let mut fractions: Vec<_> = match self.min_match_fraction {
+            None => fractions.collect(),
+            Some(min_fraction) => fractions
+                .filter(|(_, fraction)| *fraction >= min_fraction)
+                .collect(),
+        };
+
+        if let Some(top_n) = self.top_n {
+            // Ties are broken by the color index, to keep the output deterministic
+            fractions.sort_unstable_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+            fractions.truncate(top_n);
+        }
+
+        fractions.into_iter().collect()
+    }
+}
+
+/// A single line of the colored query output, one for each query that has at least one match
+#[derive(Serialize)]
+pub(crate) struct QueryColoredOutputRecord<'a> {
+    /// Omitted when the queries are identified by their header
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query_index: Option<u64>,
+    pub query_header: &'a str,
+    pub query_kmers: u64,
+    /// Fraction of the query kmers that are present with each color
+    pub matches: QueryColorsMatches<'a>,
+}
+
+/// Match fractions keyed by the color indices, or by the color names if they are given
+pub(crate) struct QueryColorsMatches<'a> {
+    pub fractions: BTreeMap<ColorIndexType, f64>,
+    pub color_names: Option<&'a [String]>,
+}
+
+impl<'a> Serialize for QueryColorsMatches<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.color_names {
+            None => serializer.collect_map(self.fractions.iter()),
+            Some(color_names) => {
+                serializer.collect_map(self.fractions.iter().map(|(color, fraction)| {
+                    (
+                        color_names
+                            .get(*color as usize)
+                            .cloned()
+                            .unwrap_or_else(|| color.to_string()),
+                        fraction,
+                    )
+                }))
+            }
+        }
+    }
+}
+
 pub fn colored_query_output<CX: ColorsManager>(
     mut colored_query_buckets: Vec<PathBuf>,
     output_file: PathBuf,
+    query_headers: &[String],
     query_kmers_count: &[u64],
+    color_names: Option<&[String]>,
+    matches_filter: QueryMatchesFilter,
 ) {
     PHASES_TIMES_MONITOR
         .write()
@@ -145,23 +236,36 @@ pub fn colored_query_output<CX: ColorsManager>(
                     .unwrap();
 
                 for (query, result) in results {
-                    write!(compressed_stream, "{{query_index:{}, matches:{{", query).unwrap();
-                    let mut query_result = result.into_iter().collect::<Vec<_>>();
-                    query_result.sort_unstable_by_key(|r| r.0);
+                    // Query indices are 1-based in the buckets
+                    let query_index = query as usize - 1;
+                    let query_kmers = query_kmers_count[query_index];
 
-                    for (i, q) in query_result.into_iter().enumerate() {
-                        if i != 0 {
-                            write!(compressed_stream, ",").unwrap();
-                        }
-                        write!(
-                            compressed_stream,
-                            "'{}': {:.2}",
-                            q.0,
-                            (q.1 as f64) / (query_kmers_count[query as usize] as f64)
-                        )
-                        .unwrap();
+                    let fractions = matches_filter.apply(
+                        result
+                            .into_iter()
+                            .map(|(color, count)| (color, (count as f64) / (query_kmers as f64))),
+                    );
+
+                    // Skip the queries without any match left after the filtering
+                    if fractions.is_empty() {
+                        continue;
                     }
-                    writeln!(compressed_stream, "}}}}").unwrap();
+
+                    let record = QueryColoredOutputRecord {
+                        query_index: match color_names {
+                            None => Some(query_index as u64),
+                            Some(_) => None,
+                        },
+                        query_header: &query_headers[query_index],
+                        query_kmers,
+                        matches: QueryColorsMatches {
+                            fractions,
+                            color_names,
+                        },
+                    };
+
+                    serde_json::to_writer(&mut compressed_stream, &record).unwrap();
+                    writeln!(compressed_stream).unwrap();
                 }
 
                 let mut decompress_stream =
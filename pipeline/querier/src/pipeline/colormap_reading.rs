@@ -1,12 +1,12 @@
 use crate::pipeline::counters_sorting::CounterEntry;
 use crate::structs::query_colored_counters::{ColorsRange, QueryColorDesc, QueryColoredCounters};
-use colors::storage::deserializer::ColorsDeserializer;
-use colors::storage::ColorsSerializerTrait;
+use colors::storage::indexed_reader::ColorsIndexedReader;
 use config::{
     get_memory_mode, BucketIndexType, ColorIndexType, SwapPriority, DEFAULT_LZ4_COMPRESSION_LEVEL,
     DEFAULT_PER_CPU_BUFFER_SIZE, DEFAULT_PREFETCH_AMOUNT, KEEP_FILES,
     MINIMIZER_BUCKETS_CHECKPOINT_SIZE,
 };
+use io::errors::GGCATError;
 use parallel_processor::buckets::concurrent::{BucketsThreadBuffer, BucketsThreadDispatcher};
 use parallel_processor::buckets::readers::compressed_binary_reader::CompressedBinaryReader;
 use parallel_processor::buckets::readers::lock_free_binary_reader::LockFreeBinaryReader;
@@ -24,12 +24,12 @@ use std::path::PathBuf;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
 
-pub fn colormap_reading<CD: ColorsSerializerTrait>(
+pub fn colormap_reading(
     colormap_file: PathBuf,
     colored_query_buckets: Vec<PathBuf>,
     temp_dir: PathBuf,
     queries_count: u64,
-) -> Vec<PathBuf> {
+) -> Result<Vec<PathBuf>, GGCATError> {
     PHASES_TIMES_MONITOR
         .write()
         .start_phase("phase: colormap reading".to_string());
@@ -51,9 +51,8 @@ pub fn colormap_reading<CD: ColorsSerializerTrait>(
         BucketsThreadBuffer::new(DEFAULT_PER_CPU_BUFFER_SIZE, buckets_count)
     });
 
-    colored_query_buckets.par_iter().for_each(|input| {
-        let mut colormap_decoder = ColorsDeserializer::<CD>::new(&colormap_file);
-        let mut temp_colors_buffer = Vec::new();
+    colored_query_buckets.par_iter().try_for_each(|input| {
+        let mut colormap_decoder = ColorsIndexedReader::new(&colormap_file)?;
         let mut temp_queries_buffer = Vec::new();
         let mut temp_encoded_buffer = Vec::new();
 
@@ -94,15 +93,20 @@ pub fn colormap_reading<CD: ColorsSerializerTrait>(
 
         for queries_by_color in counters_vec.group_by_mut(|a, b| a.1 == b.1) {
             let color = queries_by_color[0].1;
-            temp_colors_buffer.clear();
-            colormap_decoder.get_color_mappings(color, &mut temp_colors_buffer);
+            let colors =
+                colormap_decoder
+                    .get(color)?
+                    .ok_or_else(|| GGCATError::InvalidColormap {
+                        file: colormap_file.clone(),
+                        reason: format!("missing color subset {} of the graph unitigs", color),
+                    })?;
 
             {
                 temp_encoded_buffer.clear();
                 let mut range_start = ColorIndexType::MAX;
                 let mut range_end = ColorIndexType::MAX;
 
-                for color in temp_colors_buffer.iter().copied() {
+                for color in colors.iter().copied() {
                     // Different range
                     if color != range_end {
                         if range_start != ColorIndexType::MAX {
@@ -129,7 +133,7 @@ pub fn colormap_reading<CD: ColorsSerializerTrait>(
             // println!(
             //     " Queries: {:?} Colors: {:?} Compressed: {:?}",
             //     queries_by_color.iter().map(|q| &q.0).collect::<Vec<_>>(),
-            //     temp_colors_buffer,
+            //     colors,
             //     temp_encoded_buffer
             // );
 
@@ -159,7 +163,8 @@ pub fn colormap_reading<CD: ColorsSerializerTrait>(
             }
         }
         thread_buffer.put_back(colored_buckets_writer.finalize().0);
-    });
+        Ok(())
+    })?;
 
-    correct_color_buckets.finalize()
+    Ok(correct_color_buckets.finalize())
 }
@@ -20,6 +20,7 @@ use parallel_processor::phase_times_monitor::PHASES_TIMES_MONITOR;
 use parallel_processor::utils::scoped_thread_local::ScopedThreadLocal;
 use rayon::iter::IntoParallelRefIterator;
 use rayon::iter::ParallelIterator;
+use std::cmp::max;
 use std::io::Read;
 use std::marker::PhantomData;
 use std::path::PathBuf;
@@ -83,6 +84,7 @@ pub fn counters_sorting<CX: ColorsManager>(
     colors_count: u64,
     output_file: PathBuf,
     query_kmers_count: &[u64],
+    query_headers: Option<&[String]>,
 ) -> Vec<PathBuf> {
     PHASES_TIMES_MONITOR
         .write()
@@ -197,7 +199,11 @@ pub fn counters_sorting<CX: ColorsManager>(
         let mut writer = csv::Writer::from_path(output_file).unwrap();
         writer
             .write_record(&[
-                "query_index",
+                if query_headers.is_some() {
+                    "query_header"
+                } else {
+                    "query_index"
+                },
                 "matched_kmers",
                 "query_kmers",
                 "match_percentage",
@@ -211,12 +217,16 @@ pub fn counters_sorting<CX: ColorsManager>(
         {
             writer
                 .write_record(&[
-                    query_index.to_string(),
+                    match query_headers {
+                        Some(headers) => headers[query_index].clone(),
+                        None => query_index.to_string(),
+                    },
                     counter.load(Ordering::Relaxed).to_string(),
                     info.to_string(),
                     format!(
                         "{:.2}",
-                        (counter.load(Ordering::Relaxed) as f64 / *info as f64)
+                        // The queries shorter than k have no kmers
+                        (counter.load(Ordering::Relaxed) as f64 / max(*info, 1) as f64)
                     ),
                 ])
                 .unwrap();
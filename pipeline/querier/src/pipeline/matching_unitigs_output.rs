@@ -0,0 +1,165 @@
+use byteorder::ReadBytesExt;
+use config::{DEFAULT_PREFETCH_AMOUNT, KEEP_FILES};
+use hashbrown::HashMap;
+use io::errors::GGCATError;
+use io::sequences_reader::SequencesReader;
+use io::varint::{decode_varint, encode_varint, VARINT_MAX_SIZE};
+use parallel_processor::buckets::bucket_writer::BucketItem;
+use parallel_processor::buckets::readers::lock_free_binary_reader::LockFreeBinaryReader;
+use parallel_processor::buckets::readers::BucketReader;
+use parallel_processor::memory_fs::RemoveFileMode;
+use parallel_processor::phase_times_monitor::PHASES_TIMES_MONITOR;
+use rayon::prelude::*;
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+
+/// Number of kmers shared by a query and a unitig of the graph
+#[derive(Debug, Clone)]
+pub struct QueryUnitigCounter {
+    pub query_index: u64,
+    pub unitig: u64,
+    pub shared_kmers: u64,
+}
+
+impl BucketItem for QueryUnitigCounter {
+    type ExtraData = ();
+    type ExtraDataBuffer = ();
+    type ReadBuffer = ();
+    type ReadType<'a> = Self;
+
+    #[inline(always)]
+    fn write_to(&self, bucket: &mut Vec<u8>, _: &Self::ExtraData, _: &Self::ExtraDataBuffer) {
+        encode_varint(|b| bucket.extend_from_slice(b), self.query_index);
+        encode_varint(|b| bucket.extend_from_slice(b), self.unitig);
+        encode_varint(|b| bucket.extend_from_slice(b), self.shared_kmers);
+    }
+
+    fn read_from<'a, S: Read>(
+        mut stream: S,
+        _read_buffer: &'a mut Self::ReadBuffer,
+        _: &mut Self::ExtraDataBuffer,
+    ) -> Option<Self::ReadType<'a>> {
+        Some(Self {
+            query_index: decode_varint(|| stream.read_u8().ok())?,
+            unitig: decode_varint(|| stream.read_u8().ok())?,
+            shared_kmers: decode_varint(|| stream.read_u8().ok())?,
+        })
+    }
+
+    #[inline(always)]
+    fn get_size(&self, _: &Self::ExtraData) -> usize {
+        VARINT_MAX_SIZE * 3
+    }
+}
+
+/// Writes, for each query, the unitigs of the graph sharing at least `min_shared_kmers` kmers with it.
+/// The unitig headers are copied from the graph, so they keep their colors annotations
+pub fn matching_unitigs_output(
+    unitigs_buckets: Vec<PathBuf>,
+    graph_input: &Path,
+    output_file: PathBuf,
+    query_headers: &[String],
+    numeric_ids: bool,
+    min_shared_kmers: u64,
+) -> Result<(), GGCATError> {
+    PHASES_TIMES_MONITOR
+        .write()
+        .start_phase("phase: matching unitigs output".to_string());
+
+    // The buckets hold contiguous ranges of queries, so the matches are sorted by query
+    let matches: Vec<Vec<(u64, u64, u64)>> = unitigs_buckets
+        .par_iter()
+        .map(|bucket| {
+            let mut counters: HashMap<(u64, u64), u64> = HashMap::new();
+
+            LockFreeBinaryReader::new(
+                bucket,
+                RemoveFileMode::Remove {
+                    remove_fs: !KEEP_FILES.load(Ordering::Relaxed),
+                },
+                DEFAULT_PREFETCH_AMOUNT,
+            )
+            .decode_all_bucket_items::<QueryUnitigCounter, _>(
+                (),
+                &mut (),
+                |counter, _| {
+                    *counters
+                        .entry((counter.query_index, counter.unitig))
+                        .or_insert(0) += counter.shared_kmers;
+                },
+            );
+
+            let mut matches: Vec<_> = counters
+                .into_iter()
+                .filter(|(_, shared_kmers)| *shared_kmers >= min_shared_kmers)
+                .map(|((query_index, unitig), shared_kmers)| (query_index, unitig, shared_kmers))
+                .collect();
+            matches.sort_unstable();
+            matches
+        })
+        .collect();
+
+    // Only the matching unitigs are kept in memory
+    let mut unitigs: HashMap<u64, (Vec<u8>, Vec<u8>)> = matches
+        .iter()
+        .flatten()
+        .map(|(_, unitig, _)| (*unitig, (Vec::new(), Vec::new())))
+        .collect();
+
+    let mut unitig_index = 0;
+    SequencesReader::process_file_extended(
+        graph_input,
+        |seq| {
+            // Same numbering of the graph bucketing, where each sequence is indexed by its position
+            if let Some((ident, sequence)) = unitigs.get_mut(&unitig_index) {
+                ident.extend_from_slice(seq.ident);
+                sequence.extend_from_slice(seq.seq);
+            }
+            unitig_index += 1;
+        },
+        None,
+        true,
+        false,
+        false,
+    )?;
+
+    let mut output =
+        BufWriter::new(
+            File::create(&output_file).map_err(|error| GGCATError::FileOpen {
+                file: output_file.clone(),
+                error,
+            })?,
+        );
+
+    for (query_index, unitig, shared_kmers) in matches.iter().flatten() {
+        // Query indices are 1-based in the buckets
+        let query_index = *query_index as usize - 1;
+        let (ident, sequence) = &unitigs[unitig];
+
+        output.write_all(ident)?;
+        if numeric_ids {
+            write!(output, " QR:Z:{}", query_index)?;
+        } else {
+            // Only the first word of the header, to keep the tag well formed
+            write!(
+                output,
+                " QR:Z:{}",
+                query_headers[query_index]
+                    .split_whitespace()
+                    .next()
+                    .unwrap_or_default()
+            )?;
+        }
+        writeln!(output, " SK:i:{}", shared_kmers)?;
+        output.write_all(sequence)?;
+        writeln!(output)?;
+    }
+
+    output.flush()?;
+
+    println!("Matching unitigs saved to: {}", output_file.display());
+
+    Ok(())
+}
@@ -1,5 +1,8 @@
 pub mod colored_query_output;
 pub mod colormap_reading;
 pub mod counters_sorting;
+pub mod matching_unitigs_output;
 pub mod parallel_kmers_query;
+pub mod positional_query_output;
 pub mod querier_minimizer_bucketing;
+pub mod query_index;
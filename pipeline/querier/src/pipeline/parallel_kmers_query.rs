@@ -1,4 +1,6 @@
 use crate::pipeline::counters_sorting::CounterEntry;
+use crate::pipeline::matching_unitigs_output::QueryUnitigCounter;
+use crate::pipeline::positional_query_output::{QueryHitsBuckets, QueryKmerHit};
 use crate::pipeline::querier_minimizer_bucketing::{
     QuerierMinimizerBucketingExecutorFactory, QuerierMinimizerBucketingGlobalData,
 };
@@ -45,8 +47,10 @@ use std::sync::Arc;
 
 #[derive(Copy, Clone, Debug, Ord, PartialOrd, Eq, PartialEq)]
 pub enum QueryKmersReferenceData<CX: MinimizerBucketingSeqColorData> {
-    Graph(CX),
-    Query(NonZeroU64),
+    /// Graph sequence colors, with the index of the unitig in the graph
+    Graph { color: CX, unitig: u64 },
+    /// Query sequence index, with the position of the first kmer of the sequence in the query
+    Query { index: NonZeroU64, offset: u64 },
 }
 
 impl<CX: MinimizerBucketingSeqColorData> SequenceExtraDataTempBufferManagement<(CX::TempBuffer,)>
@@ -69,10 +73,13 @@ impl<CX: MinimizerBucketingSeqColorData> SequenceExtraDataTempBufferManagement<(
     #[inline(always)]
     fn copy_extra_from(extra: Self, src: &(CX::TempBuffer,), dst: &mut (CX::TempBuffer,)) -> Self {
         match extra {
-            QueryKmersReferenceData::Graph(color) => {
-                QueryKmersReferenceData::Graph(CX::copy_extra_from(color, &src.0, &mut dst.0))
+            QueryKmersReferenceData::Graph { color, unitig } => QueryKmersReferenceData::Graph {
+                color: CX::copy_extra_from(color, &src.0, &mut dst.0),
+                unitig,
+            },
+            QueryKmersReferenceData::Query { index, offset } => {
+                QueryKmersReferenceData::Query { index, offset }
             }
-            QueryKmersReferenceData::Query(index) => QueryKmersReferenceData::Query(index),
         }
     }
 }
@@ -83,23 +90,29 @@ impl<CX: MinimizerBucketingSeqColorData> SequenceExtraData for QueryKmersReferen
     #[inline(always)]
     fn decode_extended(buffer: &mut Self::TempBuffer, reader: &mut impl Read) -> Option<Self> {
         match reader.read_u8().ok()? {
-            0 => Some(Self::Graph(CX::decode_extended(&mut buffer.0, reader)?)),
-            _ => Some(Self::Query(
-                NonZeroU64::new(decode_varint(|| reader.read_u8().ok())? + 1).unwrap(),
-            )),
+            0 => Some(Self::Graph {
+                color: CX::decode_extended(&mut buffer.0, reader)?,
+                unitig: decode_varint(|| reader.read_u8().ok())?,
+            }),
+            _ => Some(Self::Query {
+                index: NonZeroU64::new(decode_varint(|| reader.read_u8().ok())? + 1).unwrap(),
+                offset: decode_varint(|| reader.read_u8().ok())?,
+            }),
         }
     }
 
     #[inline(always)]
     fn encode_extended(&self, buffer: &Self::TempBuffer, writer: &mut impl Write) {
         match self {
-            Self::Graph(cx) => {
+            Self::Graph { color, unitig } => {
                 writer.write_u8(0).unwrap();
-                CX::encode_extended(cx, &buffer.0, writer);
+                CX::encode_extended(color, &buffer.0, writer);
+                encode_varint(|bytes| writer.write_all(bytes), *unitig).unwrap();
             }
-            Self::Query(val) => {
+            Self::Query { index, offset } => {
                 writer.write_u8(1).unwrap();
-                encode_varint(|bytes| writer.write_all(bytes), val.get() - 1).unwrap();
+                encode_varint(|bytes| writer.write_all(bytes), index.get() - 1).unwrap();
+                encode_varint(|bytes| writer.write_all(bytes), *offset).unwrap();
             }
         }
     }
@@ -107,8 +120,8 @@ impl<CX: MinimizerBucketingSeqColorData> SequenceExtraData for QueryKmersReferen
     #[inline(always)]
     fn max_size(&self) -> usize {
         match self {
-            Self::Graph(cx) => cx.max_size() + 1,
-            Self::Query(_) => 10 + 1,
+            Self::Graph { color, .. } => color.max_size() + 10 + 1,
+            Self::Query { .. } => 10 * 2 + 1,
         }
     }
 }
@@ -117,6 +130,8 @@ struct GlobalQueryMergeData {
     k: usize,
     m: usize,
     counters_buckets: Arc<MultiThreadBuckets<LockFreeBinaryWriter>>,
+    hits_buckets: Option<QueryHitsBuckets>,
+    unitigs_buckets: Option<QueryHitsBuckets>,
     global_resplit_data: Arc<MinimizerBucketingCommonData<QuerierMinimizerBucketingGlobalData>>,
 }
 
@@ -173,7 +188,16 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
                 &global_data.counters_buckets,
                 counters_buffers,
             ),
+            hits_tmp: global_data
+                .hits_buckets
+                .as_ref()
+                .map(|hits_buckets| hits_buckets.new_thread_dispatcher()),
+            unitigs_tmp: global_data
+                .unitigs_buckets
+                .as_ref()
+                .map(|unitigs_buckets| unitigs_buckets.new_thread_dispatcher()),
             query_map: HashMap::new(),
+            unitigs_map: HashMap::new(),
             _phantom: PhantomData,
         }
     }
@@ -216,8 +240,10 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
 }
 
 struct ParallelKmersQueryMapPacket<MH: HashFunctionFactory, CX: Sync + Send + 'static> {
-    phmap: HashMap<MH::HashTypeUnextendable, CX>,
-    query_reads: Vec<(u64, MH::HashTypeUnextendable)>,
+    /// Color and unitig index of each graph kmer
+    phmap: HashMap<MH::HashTypeUnextendable, (CX, u64)>,
+    /// Query index and kmer position in the query of each query kmer
+    query_reads: Vec<(u64, u64, MH::HashTypeUnextendable)>,
 }
 
 impl<MH: HashFunctionFactory, CX: Sync + Send + 'static> PoolObjectTrait
@@ -259,7 +285,7 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
     for ParallelKmersQueryMapProcessor<H, MH, CX>
 {
     type MapStruct = ParallelKmersQueryMapPacket<MH, SingleKmerColorDataType<CX>>;
-    const MAP_SIZE: usize = size_of::<MH::HashTypeUnextendable>() + 8;
+    const MAP_SIZE: usize = size_of::<MH::HashTypeUnextendable>() + 16;
 
     fn process_group_start(
         &mut self,
@@ -291,19 +317,21 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
             kmers_count += (read.bases_count() - k + 1) as u64;
 
             match sequence_type {
-                QueryKmersReferenceData::Graph(col_info) => {
-                    for (hash, color) in hashes
-                        .iter()
-                        .zip(col_info.get_iterator(&extra_data_buffer.0))
+                QueryKmersReferenceData::Graph { color, unitig } => {
+                    for (hash, color) in hashes.iter().zip(color.get_iterator(&extra_data_buffer.0))
                     {
-                        map_packet.phmap.insert(hash.to_unextendable(), color);
+                        map_packet
+                            .phmap
+                            .insert(hash.to_unextendable(), (color, *unitig));
                     }
                 }
-                QueryKmersReferenceData::Query(index) => {
-                    for hash in hashes.iter() {
-                        map_packet
-                            .query_reads
-                            .push((index.get(), hash.to_unextendable()));
+                QueryKmersReferenceData::Query { index, offset } => {
+                    for (position, hash) in hashes.iter().enumerate() {
+                        map_packet.query_reads.push((
+                            index.get(),
+                            offset + position as u64,
+                            hash.to_unextendable(),
+                        ));
                     }
                 }
             }
@@ -329,7 +357,11 @@ struct ParallelKmersQueryFinalExecutor<
     CX: ColorsManager,
 > {
     counters_tmp: BucketsThreadDispatcher<LockFreeBinaryWriter>,
+    hits_tmp: Option<BucketsThreadDispatcher<LockFreeBinaryWriter>>,
+    unitigs_tmp: Option<BucketsThreadDispatcher<LockFreeBinaryWriter>>,
     query_map: HashMap<(u64, SingleKmerColorDataType<CX>), u64>,
+    /// Shared kmers count of each (query, unitig) pair
+    unitigs_map: HashMap<(u64, u64), u64>,
     _phantom: PhantomData<(H, MH, CX)>,
 }
 
@@ -341,17 +373,51 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
 
     fn process_map(
         &mut self,
-        _global_data: &GlobalQueryMergeData,
+        global_data: &GlobalQueryMergeData,
         map_struct: Packet<Self::MapStruct>,
     ) -> Packet<ParallelKmersQueryMapPacket<MH, SingleKmerColorDataType<CX>>> {
         let map_struct_ref = map_struct.deref();
 
-        for (query_index, kmer_hash) in &map_struct_ref.query_reads {
-            if let Some(entry_color) = map_struct_ref.phmap.get(&kmer_hash) {
+        for (query_index, position, kmer_hash) in &map_struct_ref.query_reads {
+            if let Some((entry_color, unitig)) = map_struct_ref.phmap.get(&kmer_hash) {
                 *self
                     .query_map
                     .entry((*query_index, entry_color.clone()))
                     .or_insert(0) += 1;
+
+                if let (Some(hits_tmp), Some(hits_buckets)) =
+                    (&mut self.hits_tmp, &global_data.hits_buckets)
+                {
+                    hits_tmp.add_element(
+                        hits_buckets.get_bucket(*query_index),
+                        entry_color,
+                        &QueryKmerHit {
+                            query_index: *query_index,
+                            position: *position,
+                            _phantom: PhantomData,
+                        },
+                    );
+                }
+
+                if self.unitigs_tmp.is_some() {
+                    *self.unitigs_map.entry((*query_index, *unitig)).or_insert(0) += 1;
+                }
+            }
+        }
+
+        if let (Some(unitigs_tmp), Some(unitigs_buckets)) =
+            (&mut self.unitigs_tmp, &global_data.unitigs_buckets)
+        {
+            for ((query_index, unitig), shared_kmers) in self.unitigs_map.drain() {
+                unitigs_tmp.add_element(
+                    unitigs_buckets.get_bucket(query_index),
+                    &(),
+                    &QueryUnitigCounter {
+                        query_index,
+                        unitig,
+                        shared_kmers,
+                    },
+                );
             }
         }
 
@@ -372,6 +438,12 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
 
     fn finalize(self, _global_data: &GlobalQueryMergeData) {
         self.counters_tmp.finalize();
+        if let Some(hits_tmp) = self.hits_tmp {
+            hits_tmp.finalize();
+        }
+        if let Some(unitigs_tmp) = self.unitigs_tmp {
+            unitigs_tmp.finalize();
+        }
     }
 }
 
@@ -388,6 +460,8 @@ pub fn parallel_kmers_counting<
     k: usize,
     m: usize,
     threads_count: usize,
+    hits_buckets: Option<&QueryHitsBuckets>,
+    unitigs_buckets: Option<&QueryHitsBuckets>,
 ) -> Vec<PathBuf> {
     PHASES_TIMES_MONITOR
         .write()
@@ -406,6 +480,8 @@ pub fn parallel_kmers_counting<
         k,
         m,
         counters_buckets,
+        hits_buckets: hits_buckets.cloned(),
+        unitigs_buckets: unitigs_buckets.cloned(),
         global_resplit_data: Arc::new(MinimizerBucketingCommonData::new(
             k,
             if k > RESPLITTING_MAX_K_M_DIFFERENCE + 1 {
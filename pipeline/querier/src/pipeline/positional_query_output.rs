@@ -0,0 +1,354 @@
+use byteorder::ReadBytesExt;
+use colors::storage::indexed_reader::ColorsIndexedReader;
+use config::{
+    get_memory_mode, BucketIndexType, ColorIndexType, SwapPriority, DEFAULT_PER_CPU_BUFFER_SIZE,
+    DEFAULT_PREFETCH_AMOUNT, KEEP_FILES,
+};
+use io::concurrent::temp_reads::extra_data::SequenceExtraData;
+use io::errors::GGCATError;
+use io::varint::{decode_varint, encode_varint, VARINT_MAX_SIZE};
+use parallel_processor::buckets::bucket_writer::BucketItem;
+use parallel_processor::buckets::concurrent::{BucketsThreadBuffer, BucketsThreadDispatcher};
+use parallel_processor::buckets::readers::lock_free_binary_reader::LockFreeBinaryReader;
+use parallel_processor::buckets::readers::BucketReader;
+use parallel_processor::buckets::writers::lock_free_binary_writer::LockFreeBinaryWriter;
+use parallel_processor::buckets::MultiThreadBuckets;
+use parallel_processor::memory_fs::RemoveFileMode;
+use parallel_processor::phase_times_monitor::PHASES_TIMES_MONITOR;
+use rayon::prelude::*;
+use serde::Serialize;
+use std::cmp::{max, min};
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+/// A query kmer found in the graph, the color of the graph kmer is stored as extra data
+#[derive(Debug, Clone)]
+pub struct QueryKmerHit<CX: SequenceExtraData<TempBuffer = ()>> {
+    pub query_index: u64,
+    pub position: u64,
+    pub _phantom: PhantomData<CX>,
+}
+
+impl<CX: SequenceExtraData<TempBuffer = ()>> BucketItem for QueryKmerHit<CX> {
+    type ExtraData = CX;
+    type ExtraDataBuffer = ();
+    type ReadBuffer = ();
+    type ReadType<'a> = (Self, CX);
+
+    #[inline(always)]
+    fn write_to(
+        &self,
+        bucket: &mut Vec<u8>,
+        extra_data: &Self::ExtraData,
+        _: &Self::ExtraDataBuffer,
+    ) {
+        encode_varint(|b| bucket.extend_from_slice(b), self.query_index);
+        encode_varint(|b| bucket.extend_from_slice(b), self.position);
+        extra_data.encode(bucket);
+    }
+
+    fn read_from<'a, S: Read>(
+        mut stream: S,
+        _read_buffer: &'a mut Self::ReadBuffer,
+        _: &mut Self::ExtraDataBuffer,
+    ) -> Option<Self::ReadType<'a>> {
+        let query_index = decode_varint(|| stream.read_u8().ok())?;
+        let position = decode_varint(|| stream.read_u8().ok())?;
+        let color = CX::decode(&mut stream)?;
+        Some((
+            Self {
+                query_index,
+                position,
+                _phantom: PhantomData,
+            },
+            color,
+        ))
+    }
+
+    #[inline(always)]
+    fn get_size(&self, data: &Self::ExtraData) -> usize {
+        VARINT_MAX_SIZE * 2 + data.max_size()
+    }
+}
+
+/// Buckets of the query kmers found in the graph (or of other per-query results), each bucket holds
+/// a contiguous range of queries so that the output can be written in the queries order
+#[derive(Clone)]
+pub struct QueryHitsBuckets {
+    pub buckets: Arc<MultiThreadBuckets<LockFreeBinaryWriter>>,
+    queries_count: u64,
+}
+
+impl QueryHitsBuckets {
+    pub fn new(out_directory: &Path, name: &str, buckets_count: usize, queries_count: u64) -> Self {
+        Self {
+            buckets: Arc::new(MultiThreadBuckets::<LockFreeBinaryWriter>::new(
+                buckets_count,
+                out_directory.join(name),
+                &(
+                    get_memory_mode(SwapPriority::QueryCounters),
+                    LockFreeBinaryWriter::CHECKPOINT_SIZE_UNLIMITED,
+                ),
+            )),
+            queries_count,
+        }
+    }
+
+    pub fn new_thread_dispatcher(&self) -> BucketsThreadDispatcher<LockFreeBinaryWriter> {
+        BucketsThreadDispatcher::new(
+            &self.buckets,
+            BucketsThreadBuffer::new(DEFAULT_PER_CPU_BUFFER_SIZE, self.buckets.count()),
+        )
+    }
+
+    #[inline(always)]
+    pub fn get_bucket(&self, query_index: u64) -> BucketIndexType {
+        let buckets_count = self.buckets.count() as u64;
+        // Query indices are 1-based in the buckets
+        min(
+            buckets_count - 1,
+            (query_index - 1) * buckets_count / max(self.queries_count, 1),
+        ) as BucketIndexType
+    }
+
+    pub fn finalize(self) -> Vec<PathBuf> {
+        self.buckets.finalize()
+    }
+}
+
+/// Color of a query hit, resolved to the colors list only if the graph is colored
+pub trait QueryHitColor: SequenceExtraData<TempBuffer = ()> + Copy + PartialEq {
+    fn colors_subset(&self) -> Option<ColorIndexType>;
+}
+
+impl QueryHitColor for ColorIndexType {
+    fn colors_subset(&self) -> Option<ColorIndexType> {
+        Some(*self)
+    }
+}
+
+impl QueryHitColor for () {
+    fn colors_subset(&self) -> Option<ColorIndexType> {
+        None
+    }
+}
+
+/// Run of consecutive query kmers found in the graph with the same colors, `end` is exclusive
+#[derive(Serialize)]
+struct QueryHitsInterval {
+    start: u64,
+    end: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    colors: Option<Vec<serde_json::Value>>,
+}
+
+#[derive(Serialize)]
+struct QueryPositionalOutputRecord<'a> {
+    /// Omitted when the queries are identified by their header
+    #[serde(skip_serializing_if = "Option::is_none")]
+    query_index: Option<u64>,
+    query_header: &'a str,
+    query_kmers: u64,
+    hits: Vec<QueryHitsInterval>,
+}
+
+/// Joins the hits of a query, sorted by position, in intervals of consecutive positions
+/// with the same color, as `(start, end, color)` with `end` exclusive
+fn get_hits_intervals<C: QueryHitColor>(query_hits: &[(QueryKmerHit<C>, C)]) -> Vec<(u64, u64, C)> {
+    let mut intervals: Vec<(u64, u64, C)> = Vec::new();
+    for (hit, color) in query_hits {
+        match intervals.last_mut() {
+            Some((_, end, last_color)) if *end == hit.position && last_color == color => {
+                *end += 1;
+            }
+            _ => intervals.push((hit.position, hit.position + 1, *color)),
+        }
+    }
+    intervals
+}
+
+/// Writes, for each query with at least one kmer found in the graph, the intervals
+/// of the query kmer positions present in the graph along with their colors
+pub fn positional_query_output<C: QueryHitColor>(
+    hits_buckets: Vec<PathBuf>,
+    output_file: PathBuf,
+    query_headers: &[String],
+    query_kmers_count: &[u64],
+    colormap_file: Option<PathBuf>,
+    numeric_ids: bool,
+    color_names: Option<&[String]>,
+) -> Result<(), GGCATError> {
+    PHASES_TIMES_MONITOR
+        .write()
+        .start_phase("phase: positional query output".to_string());
+
+    let mut output =
+        BufWriter::new(
+            File::create(&output_file).map_err(|error| GGCATError::FileOpen {
+                file: output_file.clone(),
+                error,
+            })?,
+        );
+
+    let color_to_json = |color: ColorIndexType| match color_names {
+        None => serde_json::Value::from(color),
+        Some(color_names) => serde_json::Value::from(
+            color_names
+                .get(color as usize)
+                .cloned()
+                .unwrap_or_else(|| color.to_string()),
+        ),
+    };
+
+    // Process a batch of buckets in parallel, then write them in order
+    for buckets_batch in hits_buckets.chunks(rayon::current_num_threads()) {
+        let batch_outputs = buckets_batch
+            .par_iter()
+            .map(|bucket| -> Result<Vec<u8>, GGCATError> {
+                let mut colormap = match &colormap_file {
+                    None => None,
+                    Some(colormap_file) => Some(ColorsIndexedReader::new(colormap_file)?),
+                };
+
+                let mut hits: Vec<(QueryKmerHit<C>, C)> = Vec::new();
+                LockFreeBinaryReader::new(
+                    bucket,
+                    RemoveFileMode::Remove {
+                        remove_fs: !KEEP_FILES.load(Ordering::Relaxed),
+                    },
+                    DEFAULT_PREFETCH_AMOUNT,
+                )
+                .decode_all_bucket_items::<QueryKmerHit<C>, _>(
+                    (),
+                    &mut (),
+                    |hit, _| hits.push(hit),
+                );
+
+                hits.sort_unstable_by_key(|(hit, _)| (hit.query_index, hit.position));
+
+                let mut bucket_output = Vec::new();
+
+                for query_hits in hits.group_by(|a, b| a.0.query_index == b.0.query_index) {
+                    // Query indices are 1-based in the buckets
+                    let query_index = query_hits[0].0.query_index as usize - 1;
+
+                    let mut hits = Vec::new();
+                    for (start, end, color) in get_hits_intervals(query_hits) {
+                        let colors = match (&mut colormap, &colormap_file, color.colors_subset()) {
+                            (Some(colormap), Some(colormap_file), Some(subset)) => {
+                                let colors = colormap.get(subset)?.ok_or_else(|| {
+                                    GGCATError::InvalidColormap {
+                                        file: colormap_file.clone(),
+                                        reason: format!(
+                                            "missing color subset {} of the graph unitigs",
+                                            subset
+                                        ),
+                                    }
+                                })?;
+                                Some(colors.iter().map(|color| color_to_json(*color)).collect())
+                            }
+                            _ => None,
+                        };
+                        hits.push(QueryHitsInterval { start, end, colors });
+                    }
+
+                    let record = QueryPositionalOutputRecord {
+                        query_index: if numeric_ids {
+                            Some(query_index as u64)
+                        } else {
+                            None
+                        },
+                        query_header: &query_headers[query_index],
+                        query_kmers: query_kmers_count[query_index],
+                        hits,
+                    };
+
+                    serde_json::to_writer(&mut bucket_output, &record).unwrap();
+                    writeln!(bucket_output).unwrap();
+                }
+
+                Ok(bucket_output)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for bucket_output in batch_outputs {
+            output.write_all(&bucket_output)?;
+        }
+    }
+
+    output.flush()?;
+
+    println!("Positional hits saved to: {}", output_file.display());
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pipeline::positional_query_output::{
+        get_hits_intervals, QueryHitColor, QueryKmerHit,
+    };
+    use config::ColorIndexType;
+    use parallel_processor::buckets::bucket_writer::BucketItem;
+    use std::marker::PhantomData;
+
+    fn query_hits<C: QueryHitColor>(hits: &[(u64, C)]) -> Vec<(QueryKmerHit<C>, C)> {
+        hits.iter()
+            .map(|(position, color)| {
+                (
+                    QueryKmerHit {
+                        query_index: 1,
+                        position: *position,
+                        _phantom: PhantomData,
+                    },
+                    *color,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn hits_intervals() {
+        // Gaps of missing kmers end the intervals
+        assert_eq!(
+            get_hits_intervals(&query_hits(&[(0, ()), (1, ()), (2, ()), (5, ()), (6, ())])),
+            vec![(0, 3, ()), (5, 7, ())]
+        );
+
+        // A change of colors starts a new interval
+        assert_eq!(
+            get_hits_intervals(&query_hits::<ColorIndexType>(&[
+                (0, 4),
+                (1, 4),
+                (2, 7),
+                (3, 4)
+            ])),
+            vec![(0, 2, 4), (2, 3, 7), (3, 4, 4)]
+        );
+
+        assert_eq!(get_hits_intervals::<()>(&[]), vec![]);
+    }
+
+    #[test]
+    fn query_hits_encoding() {
+        let hit = QueryKmerHit::<ColorIndexType> {
+            query_index: 12345,
+            position: 678,
+            _phantom: PhantomData,
+        };
+        let mut buffer = Vec::new();
+        hit.write_to(&mut buffer, &42, &());
+        assert!(buffer.len() <= hit.get_size(&42));
+
+        let (decoded, color) =
+            QueryKmerHit::<ColorIndexType>::read_from(buffer.as_slice(), &mut (), &mut ()).unwrap();
+        assert_eq!(
+            (decoded.query_index, decoded.position, color),
+            (12345, 678, 42)
+        );
+    }
+}
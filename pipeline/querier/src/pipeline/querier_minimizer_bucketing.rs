@@ -11,11 +11,12 @@ use hashes::MinimizerHashFunctionFactory;
 use io::concurrent::temp_reads::extra_data::{
     SequenceExtraData, SequenceExtraDataTempBufferManagement,
 };
+use io::errors::GGCATError;
 use io::sequences_reader::FastaSequence;
 use io::varint::{decode_varint, encode_varint, VARINT_MAX_SIZE};
 use minimizer_bucketing::{
     GenericMinimizerBucketing, MinimizerBucketingCommonData, MinimizerBucketingExecutor,
-    MinimizerBucketingExecutorFactory, MinimizerInputSequence,
+    MinimizerBucketingExecutorFactory, MinimizerInputSequence, QualityFilter,
 };
 use parallel_processor::phase_times_monitor::PHASES_TIMES_MONITOR;
 use std::io::{Read, Write};
@@ -50,8 +51,12 @@ pub struct ReadTypeBuffered<CX: ColorsManager> {
 pub enum ReadType<CX: ColorsManager> {
     Graph {
         color: MinimizerBucketingSeqColorDataType<CX>,
+        unitig: u64,
+    },
+    Query {
+        index: NonZeroU64,
+        offset: u64,
     },
-    Query(NonZeroU64),
 }
 
 impl<CX: ColorsManager> Default for ReadTypeBuffered<CX> {
@@ -59,7 +64,10 @@ impl<CX: ColorsManager> Default for ReadTypeBuffered<CX> {
         Self {
             colors_buffer:
                 QueryKmersReferenceData::<MinimizerBucketingSeqColorDataType<CX>>::new_temp_buffer(),
-            read_type: ReadType::Query(NonZeroU64::new(1).unwrap()),
+            read_type: ReadType::Query {
+                index: NonZeroU64::new(1).unwrap(),
+                offset: 0,
+            },
         }
     }
 }
@@ -157,14 +165,20 @@ impl<H: MinimizerHashFunctionFactory, CX: ColorsManager>
                     );
                 }
 
-                ReadType::Graph { color }
+                ReadType::Graph {
+                    color,
+                    unitig: read_index,
+                }
             }
             FileType::Query => {
                 self.global_data
                     .global_data
                     .queries_count
                     .fetch_add(1, Ordering::Relaxed);
-                ReadType::Query(NonZeroU64::new(read_index + 1).unwrap())
+                ReadType::Query {
+                    index: NonZeroU64::new(read_index + 1).unwrap(),
+                    offset: 0,
+                }
             }
         }
     }
@@ -183,10 +197,14 @@ impl<H: MinimizerHashFunctionFactory, CX: ColorsManager>
         );
 
         preprocess_info.read_type = match extra_data {
-            QueryKmersReferenceData::Graph(color) => ReadType::Graph {
+            QueryKmersReferenceData::Graph { color, unitig } => ReadType::Graph {
                 color: color.clone(),
+                unitig: *unitig,
+            },
+            QueryKmersReferenceData::Query { index, offset } => ReadType::Query {
+                index: *index,
+                offset: *offset,
             },
-            QueryKmersReferenceData::Query(query) => ReadType::Query(*query),
         }
     }
 
@@ -197,7 +215,7 @@ impl<H: MinimizerHashFunctionFactory, CX: ColorsManager>
         &mut self,
         preprocess_info: &<QuerierMinimizerBucketingExecutorFactory<H, CX> as MinimizerBucketingExecutorFactory>::PreprocessInfo,
         sequence: S,
-        _range: Range<usize>,
+        range: Range<usize>,
         used_bits: usize,
         first_bits: usize,
         second_bits: usize,
@@ -220,11 +238,15 @@ impl<H: MinimizerHashFunctionFactory, CX: ColorsManager>
                     sequence.get_subslice(last_index..(index + self.global_data.k)),
                     0,
                     match &preprocess_info.read_type {
-                        ReadType::Graph { color } => QueryKmersReferenceData::Graph(
-                            color.get_subslice(last_index..(index + 1)), // FIXME: Check if the subslice is correct,
-                        ),
-
-                        ReadType::Query(val) => QueryKmersReferenceData::Query(*val),
+                        ReadType::Graph { color, unitig } => QueryKmersReferenceData::Graph {
+                            color: color.get_subslice(last_index..(index + 1)), // FIXME: Check if the subslice is correct,
+                            unitig: *unitig,
+                        },
+
+                        ReadType::Query { index, offset } => QueryKmersReferenceData::Query {
+                            index: *index,
+                            offset: offset + (range.start + last_index) as u64,
+                        },
                     },
                     &preprocess_info.colors_buffer,
                 );
@@ -240,39 +262,53 @@ impl<H: MinimizerHashFunctionFactory, CX: ColorsManager>
             sequence.get_subslice(last_index..sequence.seq_len()),
             0,
             match &preprocess_info.read_type {
-                ReadType::Graph { color } => {
-                    QueryKmersReferenceData::Graph(
-                        color.get_subslice(
+                ReadType::Graph { color, unitig } => {
+                    QueryKmersReferenceData::Graph {
+                        color: color.get_subslice(
                             last_index..(sequence.seq_len() + 1 - self.global_data.k),
                         ), // FIXME: Check if the subslice is correct,
-                    )
+                        unitig: *unitig,
+                    }
                 }
 
-                ReadType::Query(val) => QueryKmersReferenceData::Query(*val),
+                ReadType::Query { index, offset } => QueryKmersReferenceData::Query {
+                    index: *index,
+                    offset: offset + (range.start + last_index) as u64,
+                },
             },
             &preprocess_info.colors_buffer,
         );
     }
 }
 
+/// Buckets the graph and/or the query sequences, the graph is omitted when querying a prebuilt index
 pub fn minimizer_bucketing<H: MinimizerHashFunctionFactory, CX: ColorsManager>(
-    graph_file: PathBuf,
-    query_file: PathBuf,
+    graph_file: Option<PathBuf>,
+    query_file: Option<PathBuf>,
     output_path: &Path,
     buckets_count: usize,
     threads_count: usize,
     k: usize,
     m: usize,
-) -> ((Vec<PathBuf>, PathBuf), u64) {
-    PHASES_TIMES_MONITOR
-        .write()
-        .start_phase("phase: graph + query bucketing".to_string());
+) -> Result<((Vec<PathBuf>, PathBuf), u64), GGCATError> {
+    PHASES_TIMES_MONITOR.write().start_phase(
+        match (&graph_file, &query_file) {
+            (Some(_), Some(_)) => "phase: graph + query bucketing",
+            (Some(_), None) => "phase: graph bucketing",
+            (None, _) => "phase: query bucketing",
+        }
+        .to_string(),
+    );
 
-    let input_files = vec![(graph_file, FileType::Graph), (query_file, FileType::Query)];
+    let input_files: Vec<_> = graph_file
+        .map(|file| (file, FileType::Graph))
+        .into_iter()
+        .chain(query_file.map(|file| (file, FileType::Query)))
+        .collect();
 
     let queries_count = Arc::new(AtomicUsize::new(0));
 
-    (
+    let buckets =
         GenericMinimizerBucketing::do_bucketing::<QuerierMinimizerBucketingExecutorFactory<H, CX>>(
             input_files,
             output_path,
@@ -285,7 +321,8 @@ pub fn minimizer_bucketing<H: MinimizerHashFunctionFactory, CX: ColorsManager>(
             },
             None,
             CX::COLORS_ENABLED,
-        ),
-        queries_count.load(Ordering::Relaxed) as u64,
-    )
+            QualityFilter::default(),
+        )?;
+
+    Ok((buckets, queries_count.load(Ordering::Relaxed) as u64))
 }
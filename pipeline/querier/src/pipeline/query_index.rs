@@ -0,0 +1,591 @@
+use crate::pipeline::counters_sorting::CounterEntry;
+use crate::pipeline::parallel_kmers_query::QueryKmersReferenceData;
+use crate::pipeline::positional_query_output::{QueryHitsBuckets, QueryKmerHit};
+use bincode::{deserialize_from, serialize_into};
+use colors::colors_manager::color_types::{
+    MinimizerBucketingSeqColorDataType, SingleKmerColorDataType,
+};
+use colors::colors_manager::{ColorsManager, MinimizerBucketingSeqColorData};
+use config::{
+    get_memory_mode, BucketIndexType, SwapPriority, DEFAULT_LZ4_COMPRESSION_LEVEL,
+    DEFAULT_PER_CPU_BUFFER_SIZE, DEFAULT_PREFETCH_AMOUNT, KEEP_FILES,
+    MINIMIZER_BUCKETS_CHECKPOINT_SIZE, USE_SECOND_BUCKET,
+};
+use hashbrown::HashMap;
+use hashes::{
+    ExtendableHashTraitType, HashFunction, HashFunctionFactory, MinimizerHashFunctionFactory,
+    UnextendableHashTraitType,
+};
+use io::concurrent::temp_reads::creads_utils::CompressedReadsBucketHelper;
+use io::concurrent::temp_reads::extra_data::{
+    SequenceExtraData, SequenceExtraDataOwned, SequenceExtraDataTempBufferManagement,
+};
+use io::errors::GGCATError;
+use io::generate_bucket_names;
+use parallel_processor::buckets::bucket_writer::BucketItem;
+use parallel_processor::buckets::concurrent::{BucketsThreadBuffer, BucketsThreadDispatcher};
+use parallel_processor::buckets::readers::compressed_binary_reader::CompressedBinaryReader;
+use parallel_processor::buckets::readers::BucketReader;
+use parallel_processor::buckets::writers::compressed_binary_writer::CompressedBinaryWriter;
+use parallel_processor::buckets::writers::lock_free_binary_writer::LockFreeBinaryWriter;
+use parallel_processor::buckets::MultiThreadBuckets;
+use parallel_processor::memory_fs::file::internal::MemoryFileMode;
+use parallel_processor::memory_fs::{MemoryFs, RemoveFileMode};
+use parallel_processor::phase_times_monitor::PHASES_TIMES_MONITOR;
+use parallel_processor::utils::scoped_thread_local::ScopedThreadLocal;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read};
+use std::marker::PhantomData;
+use std::mem::size_of;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+const INDEX_INFO_FILE: &str = "index-info.json";
+const INDEX_KMERS_PREFIX: &str = "kmers";
+const INDEX_COLORMAP_FILE: &str = "colors.dat";
+/// Version of the index files format, increased when it changes in an incompatible way
+const INDEX_FORMAT_VERSION: u32 = 1;
+
+/// Settings used to build a query index, the queries must be run with the same ones
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct QueryIndexInfo {
+    pub version: u32,
+    pub k: usize,
+    pub m: usize,
+    pub buckets_count: usize,
+    pub bucketing_hash: String,
+    pub merging_hash: String,
+    pub colors: bool,
+}
+
+impl QueryIndexInfo {
+    pub fn new<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager>(
+        k: usize,
+        m: usize,
+        buckets_count: usize,
+    ) -> Self {
+        Self {
+            version: INDEX_FORMAT_VERSION,
+            k,
+            m,
+            buckets_count,
+            bucketing_hash: Self::hash_id::<H>(),
+            merging_hash: Self::hash_id::<MH>(),
+            colors: CX::COLORS_ENABLED,
+        }
+    }
+
+    fn hash_id<H: HashFunctionFactory>() -> String {
+        format!("{}_{}", H::NAME, size_of::<H::HashTypeUnextendable>() * 8)
+    }
+
+    /// Returns true if the path is a directory containing a query index
+    pub fn is_index(path: &Path) -> bool {
+        path.join(INDEX_INFO_FILE).is_file()
+    }
+
+    pub fn colormap_path(index_dir: &Path) -> PathBuf {
+        index_dir.join(INDEX_COLORMAP_FILE)
+    }
+
+    pub fn kmers_buckets(&self, index_dir: &Path) -> Vec<PathBuf> {
+        generate_bucket_names(index_dir.join(INDEX_KMERS_PREFIX), self.buckets_count, None)
+    }
+
+    pub fn load(index_dir: &Path) -> Result<Self, GGCATError> {
+        let info_file = index_dir.join(INDEX_INFO_FILE);
+        let file = File::open(&info_file).map_err(|error| GGCATError::FileOpen {
+            file: info_file,
+            error,
+        })?;
+
+        let invalid_index = |reason: String| GGCATError::InvalidIndex {
+            dir: index_dir.to_path_buf(),
+            reason,
+        };
+
+        // The version is checked first, as the other settings may change between versions
+        let info: serde_json::Value = serde_json::from_reader(BufReader::new(file))
+            .map_err(|err| invalid_index(err.to_string()))?;
+        match info.get("version").and_then(|version| version.as_u64()) {
+            Some(version) if version == INDEX_FORMAT_VERSION as u64 => {}
+            version => {
+                return Err(invalid_index(format!(
+                    "unsupported index format version {}, expected {}",
+                    version.map_or("none".to_string(), |version| version.to_string()),
+                    INDEX_FORMAT_VERSION
+                )))
+            }
+        }
+
+        serde_json::from_value(info).map_err(|err| invalid_index(err.to_string()))
+    }
+
+    pub fn save(&self, index_dir: &Path) -> Result<(), GGCATError> {
+        let file = File::create(index_dir.join(INDEX_INFO_FILE))?;
+        serde_json::to_writer_pretty(BufWriter::new(file), self)
+            .map_err(|err| GGCATError::Io(err.into()))
+    }
+
+    /// Checks that the index was built with the same k-mers, minimizers, hashes and colors settings
+    pub fn check_compatible(&self, index_dir: &Path, expected: &Self) -> Result<(), GGCATError> {
+        let mismatch = |setting: &str, index: String, query: String| {
+            Err(GGCATError::InvalidIndex {
+                dir: index_dir.to_path_buf(),
+                reason: format!(
+                    "the index was built with {} {} but the query uses {}",
+                    setting, index, query
+                ),
+            })
+        };
+
+        if self.k != expected.k {
+            return mismatch("k", self.k.to_string(), expected.k.to_string());
+        }
+        if self.m != expected.m {
+            return mismatch("m", self.m.to_string(), expected.m.to_string());
+        }
+        if self.bucketing_hash != expected.bucketing_hash {
+            return mismatch(
+                "minimizer hash",
+                self.bucketing_hash.clone(),
+                expected.bucketing_hash.clone(),
+            );
+        }
+        if self.merging_hash != expected.merging_hash {
+            return mismatch(
+                "k-mers hash",
+                self.merging_hash.clone(),
+                expected.merging_hash.clone(),
+            );
+        }
+        if self.colors != expected.colors {
+            return mismatch(
+                "colors",
+                self.colors.to_string(),
+                expected.colors.to_string(),
+            );
+        }
+        Ok(())
+    }
+}
+
+/// A k-mer of the indexed graph, stored with its color as extra data
+pub struct QueryIndexEntry<H: UnextendableHashTraitType, CX: SequenceExtraData<TempBuffer = ()>> {
+    pub hash: H,
+    pub _phantom: PhantomData<CX>,
+}
+
+impl<H: UnextendableHashTraitType, CX: SequenceExtraData<TempBuffer = ()>> BucketItem
+    for QueryIndexEntry<H, CX>
+{
+    type ExtraData = CX;
+    type ExtraDataBuffer = ();
+    type ReadBuffer = ();
+    type ReadType<'a> = (H, CX);
+
+    #[inline(always)]
+    fn write_to(
+        &self,
+        bucket: &mut Vec<u8>,
+        extra_data: &Self::ExtraData,
+        _: &Self::ExtraDataBuffer,
+    ) {
+        serialize_into(&mut *bucket, &self.hash).unwrap();
+        extra_data.encode(bucket);
+    }
+
+    fn read_from<'a, S: Read>(
+        mut stream: S,
+        _read_buffer: &'a mut Self::ReadBuffer,
+        _: &mut Self::ExtraDataBuffer,
+    ) -> Option<Self::ReadType<'a>> {
+        let hash = deserialize_from(&mut stream).ok()?;
+        let color = CX::decode(&mut stream)?;
+        Some((hash, color))
+    }
+
+    #[inline(always)]
+    fn get_size(&self, data: &Self::ExtraData) -> usize {
+        size_of::<H>() + data.max_size()
+    }
+}
+
+/// Hashes the k-mers of the bucketed graph and stores them, sorted and with their colors, in the index directory
+pub fn build_query_index<
+    H: MinimizerHashFunctionFactory,
+    MH: HashFunctionFactory,
+    CX: ColorsManager,
+>(
+    graph_buckets: Vec<PathBuf>,
+    index_dir: &Path,
+    k: usize,
+) -> Vec<PathBuf> {
+    PHASES_TIMES_MONITOR
+        .write()
+        .start_phase("phase: index building".to_string());
+
+    let buckets_count = graph_buckets.len();
+
+    // The index must be persisted, so it is always written directly to disk
+    let index_buckets = Arc::new(MultiThreadBuckets::<CompressedBinaryWriter>::new(
+        buckets_count,
+        index_dir.join(INDEX_KMERS_PREFIX),
+        &(
+            MemoryFileMode::DiskOnly,
+            MINIMIZER_BUCKETS_CHECKPOINT_SIZE,
+            DEFAULT_LZ4_COMPRESSION_LEVEL,
+        ),
+    ));
+
+    let thread_buffers = ScopedThreadLocal::new(move || {
+        BucketsThreadBuffer::new(DEFAULT_PER_CPU_BUFFER_SIZE, buckets_count)
+    });
+
+    graph_buckets
+        .par_iter()
+        .enumerate()
+        .for_each(|(bucket_index, input)| {
+            let mut thread_buffer = thread_buffers.get();
+            let mut index_writer =
+                BucketsThreadDispatcher::new(&index_buckets, thread_buffer.take());
+
+            let mut kmers: Vec<(MH::HashTypeUnextendable, SingleKmerColorDataType<CX>)> =
+                Vec::new();
+
+            let mut colors_buffer =
+                QueryKmersReferenceData::<MinimizerBucketingSeqColorDataType<CX>>::new_temp_buffer(
+                );
+
+            CompressedBinaryReader::new(
+                input,
+                RemoveFileMode::Remove {
+                    remove_fs: !KEEP_FILES.load(Ordering::Relaxed),
+                },
+                DEFAULT_PREFETCH_AMOUNT,
+            )
+            .decode_all_bucket_items::<CompressedReadsBucketHelper<
+                QueryKmersReferenceData<MinimizerBucketingSeqColorDataType<CX>>,
+                typenum::U0,
+                { USE_SECOND_BUCKET },
+            >, _>(
+                Vec::new(),
+                &mut colors_buffer,
+                |(_, _, read_type, read), colors_buffer| {
+                    if let QueryKmersReferenceData::Graph {
+                        color: col_info, ..
+                    } = read_type
+                    {
+                        let hashes = MH::new(read, k);
+                        kmers.extend(
+                            hashes
+                                .iter()
+                                .map(|hash| hash.to_unextendable())
+                                .zip(col_info.get_iterator(&colors_buffer.0)),
+                        );
+                    }
+                },
+            );
+
+            kmers.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+            kmers.dedup_by(|a, b| a.0 == b.0);
+
+            for (hash, color) in kmers {
+                index_writer.add_element(
+                    bucket_index as BucketIndexType,
+                    &color,
+                    &QueryIndexEntry {
+                        hash,
+                        _phantom: PhantomData,
+                    },
+                );
+            }
+
+            thread_buffer.put_back(index_writer.finalize().0);
+        });
+
+    let index_buckets = index_buckets.finalize();
+    for bucket in &index_buckets {
+        MemoryFs::ensure_flushed(bucket);
+    }
+    index_buckets
+}
+
+/// Counts the query k-mers matching each color by joining the query buckets with the sorted index buckets,
+/// the query buckets can be kept to join them with multiple indices
+pub fn index_kmers_counting<
+    H: MinimizerHashFunctionFactory,
+    MH: HashFunctionFactory,
+    CX: ColorsManager,
+>(
+    query_buckets: Vec<PathBuf>,
+    index_buckets: Vec<PathBuf>,
+    out_directory: &Path,
+    k: usize,
+    hits_buckets: Option<&QueryHitsBuckets>,
+    query_buckets_mode: RemoveFileMode,
+    index_buckets_mode: RemoveFileMode,
+) -> Vec<PathBuf> {
+    PHASES_TIMES_MONITOR
+        .write()
+        .start_phase("phase: kmers counting".to_string());
+
+    let buckets_count = index_buckets.len();
+
+    let counters_buckets = Arc::new(MultiThreadBuckets::<LockFreeBinaryWriter>::new(
+        buckets_count,
+        out_directory.join("counters"),
+        &(
+            get_memory_mode(SwapPriority::QueryCounters),
+            LockFreeBinaryWriter::CHECKPOINT_SIZE_UNLIMITED,
+        ),
+    ));
+
+    let thread_buffers = ScopedThreadLocal::new(move || {
+        BucketsThreadBuffer::new(DEFAULT_PER_CPU_BUFFER_SIZE, buckets_count)
+    });
+
+    let hits_buckets_count = hits_buckets.map_or(0, |hits_buckets| hits_buckets.buckets.count());
+    let hits_thread_buffers = ScopedThreadLocal::new(move || {
+        BucketsThreadBuffer::new(DEFAULT_PER_CPU_BUFFER_SIZE, hits_buckets_count)
+    });
+
+    query_buckets
+        .par_iter()
+        .zip(index_buckets.par_iter())
+        .for_each(|(query_bucket, index_bucket)| {
+            // Hash, query index and kmer position in the query of each query kmer
+            let mut query_kmers: Vec<(MH::HashTypeUnextendable, u64, u64)> = Vec::new();
+
+            let mut colors_buffer =
+                QueryKmersReferenceData::<MinimizerBucketingSeqColorDataType<CX>>::new_temp_buffer(
+                );
+
+            CompressedBinaryReader::new(query_bucket, query_buckets_mode, DEFAULT_PREFETCH_AMOUNT)
+                .decode_all_bucket_items::<CompressedReadsBucketHelper<
+                QueryKmersReferenceData<MinimizerBucketingSeqColorDataType<CX>>,
+                typenum::U0,
+                { USE_SECOND_BUCKET },
+            >, _>(
+                Vec::new(),
+                &mut colors_buffer,
+                |(_, _, read_type, read), _| {
+                    if let QueryKmersReferenceData::Query { index, offset } = read_type {
+                        let hashes = MH::new(read, k);
+                        query_kmers.extend(hashes.iter().enumerate().map(|(position, hash)| {
+                            (
+                                hash.to_unextendable(),
+                                index.get(),
+                                offset + position as u64,
+                            )
+                        }));
+                    }
+                },
+            );
+
+            if query_kmers.is_empty() {
+                MemoryFs::remove_file(index_bucket, index_buckets_mode).unwrap();
+                return;
+            }
+
+            query_kmers.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+
+            let mut query_map: HashMap<(u64, SingleKmerColorDataType<CX>), u64> = HashMap::new();
+            let mut query_position = 0;
+
+            let mut hits_thread_buffer = hits_thread_buffers.get();
+            let mut hits_writer = hits_buckets.map(|hits_buckets| {
+                (
+                    hits_buckets,
+                    BucketsThreadDispatcher::new(&hits_buckets.buckets, hits_thread_buffer.take()),
+                )
+            });
+
+            // Both the query k-mers and the index entries are sorted by hash
+            CompressedBinaryReader::new(index_bucket, index_buckets_mode, DEFAULT_PREFETCH_AMOUNT)
+                .decode_all_bucket_items::<QueryIndexEntry<
+                    MH::HashTypeUnextendable,
+                    SingleKmerColorDataType<CX>,
+                >, _>((), &mut (), |(hash, color), _| {
+                    while query_position < query_kmers.len() && query_kmers[query_position].0 < hash
+                    {
+                        query_position += 1;
+                    }
+                    while query_position < query_kmers.len()
+                        && query_kmers[query_position].0 == hash
+                    {
+                        let (_, query_index, position) = query_kmers[query_position];
+                        *query_map
+                            .entry((query_index, color.clone()))
+                            .or_insert(0) += 1;
+
+                        if let Some((hits_buckets, hits_writer)) = &mut hits_writer {
+                            hits_writer.add_element(
+                                hits_buckets.get_bucket(query_index),
+                                &color,
+                                &QueryKmerHit {
+                                    query_index,
+                                    position,
+                                    _phantom: PhantomData,
+                                },
+                            );
+                        }
+                        query_position += 1;
+                    }
+                });
+
+            let mut thread_buffer = thread_buffers.get();
+            let mut counters_writer =
+                BucketsThreadDispatcher::new(&counters_buckets, thread_buffer.take());
+
+            for ((query_index, color_index), counter) in query_map.drain() {
+                counters_writer.add_element(
+                    (query_index % 0xFF) as BucketIndexType,
+                    &color_index,
+                    &CounterEntry {
+                        query_index,
+                        counter,
+                        _phantom: PhantomData,
+                    },
+                )
+            }
+
+            thread_buffer.put_back(counters_writer.finalize().0);
+            if let Some((_, hits_writer)) = hits_writer {
+                hits_thread_buffer.put_back(hits_writer.finalize().0);
+            }
+        });
+
+    counters_buckets.finalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pipeline::query_index::{QueryIndexEntry, QueryIndexInfo, INDEX_FORMAT_VERSION};
+    use colors::non_colored::NonColoredManager;
+    use config::ColorIndexType;
+    use hashes::cn_nthash::CanonicalNtHashIteratorFactory;
+    use hashes::cn_seqhash::u64::CanonicalSeqHashFactory;
+    use io::errors::GGCATError;
+    use io::test_utils::TestTempDir;
+    use parallel_processor::buckets::bucket_writer::BucketItem;
+    use std::marker::PhantomData;
+
+    fn index_info() -> QueryIndexInfo {
+        QueryIndexInfo {
+            version: INDEX_FORMAT_VERSION,
+            k: 31,
+            m: 12,
+            buckets_count: 256,
+            bucketing_hash: "canonical_nthash_64".to_string(),
+            merging_hash: "canonical_seqhash_64".to_string(),
+            colors: true,
+        }
+    }
+
+    #[test]
+    fn index_info_saved() {
+        let temp_dir = TestTempDir::new("index-info");
+        let index_dir = temp_dir.file("index");
+        std::fs::create_dir_all(&index_dir).unwrap();
+
+        index_info().save(&index_dir).unwrap();
+        assert!(QueryIndexInfo::is_index(&index_dir));
+        assert_eq!(QueryIndexInfo::load(&index_dir).unwrap(), index_info());
+
+        std::fs::write(index_dir.join("index-info.json"), "{\"k\": 31}").unwrap();
+        assert!(matches!(
+            QueryIndexInfo::load(&index_dir),
+            Err(GGCATError::InvalidIndex { .. })
+        ));
+
+        let mut info = serde_json::to_value(index_info()).unwrap();
+        info["version"] = (INDEX_FORMAT_VERSION + 1).into();
+        std::fs::write(index_dir.join("index-info.json"), info.to_string()).unwrap();
+        match QueryIndexInfo::load(&index_dir) {
+            Err(GGCATError::InvalidIndex { reason, .. }) => assert!(reason.contains("version")),
+            _ => panic!("expected an unsupported version error"),
+        }
+
+        std::fs::remove_dir_all(&index_dir).unwrap();
+        assert!(!QueryIndexInfo::is_index(&index_dir));
+        assert!(matches!(
+            QueryIndexInfo::load(&index_dir),
+            Err(GGCATError::FileOpen { .. })
+        ));
+    }
+
+    #[test]
+    fn stable_hash_ids() {
+        let info = QueryIndexInfo::new::<
+            CanonicalNtHashIteratorFactory,
+            CanonicalSeqHashFactory,
+            NonColoredManager,
+        >(31, 12, 256);
+        assert_eq!(info.bucketing_hash, "canonical_nthash_64");
+        assert_eq!(info.merging_hash, "canonical_seqhash_64");
+        assert!(!info.colors);
+    }
+
+    #[test]
+    fn index_compatibility() {
+        let temp_dir = TestTempDir::new("index-compatibility");
+        let index_dir = temp_dir.file("index");
+        let index = index_info();
+        assert!(index.check_compatible(&index_dir, &index_info()).is_ok());
+
+        let mismatches = [
+            QueryIndexInfo {
+                k: 27,
+                ..index_info()
+            },
+            QueryIndexInfo {
+                m: 10,
+                ..index_info()
+            },
+            QueryIndexInfo {
+                bucketing_hash: "forward_nthash_64".to_string(),
+                ..index_info()
+            },
+            QueryIndexInfo {
+                merging_hash: "canonical_seqhash_128".to_string(),
+                ..index_info()
+            },
+            QueryIndexInfo {
+                colors: false,
+                ..index_info()
+            },
+        ];
+        for query in mismatches {
+            assert!(matches!(
+                index.check_compatible(&index_dir, &query),
+                Err(GGCATError::InvalidIndex { .. })
+            ));
+        }
+
+        // The buckets count of the query follows the one of the index
+        let query = QueryIndexInfo {
+            buckets_count: 1024,
+            ..index_info()
+        };
+        assert!(index.check_compatible(&index_dir, &query).is_ok());
+    }
+
+    #[test]
+    fn index_entries_encoding() {
+        let entry = QueryIndexEntry::<u64, ColorIndexType> {
+            hash: 0x1234_5678_9abc,
+            _phantom: PhantomData,
+        };
+        let mut buffer = Vec::new();
+        entry.write_to(&mut buffer, &7, &());
+        assert!(buffer.len() <= entry.get_size(&7));
+
+        let decoded =
+            QueryIndexEntry::<u64, ColorIndexType>::read_from(buffer.as_slice(), &mut (), &mut ());
+        assert_eq!(decoded, Some((0x1234_5678_9abc, 7)));
+    }
+}
@@ -0,0 +1,385 @@
+use crate::pipeline::colored_query_output::{
+    QueryColoredOutputRecord, QueryColorsMatches, QueryMatchesFilter,
+};
+use crate::pipeline::query_index::{QueryIndexEntry, QueryIndexInfo};
+use ::static_dispatch::static_dispatch;
+use colors::bundles::graph_querying::ColorBundleGraphQuerying;
+use colors::colors_manager::color_types::MinimizerBucketingSeqColorDataType;
+use colors::colors_manager::MinimizerBucketingSeqColorData;
+use colors::parsers::SingleSequenceInfo;
+use colors::storage::indexed_reader::ColorsIndexedReader;
+use config::{ColorIndexType, DEFAULT_PREFETCH_AMOUNT};
+use hashbrown::HashMap;
+use hashes::{
+    ExtendableHashTraitType, HashFunction, HashFunctionFactory, MinimizerHashFunctionFactory,
+};
+use io::concurrent::temp_reads::extra_data::SequenceExtraDataTempBufferManagement;
+use io::errors::GGCATError;
+use io::sequences_reader::SequencesReader;
+use parallel_processor::buckets::readers::compressed_binary_reader::CompressedBinaryReader;
+use parallel_processor::buckets::readers::BucketReader;
+use parallel_processor::memory_fs::RemoveFileMode;
+use parking_lot::Mutex;
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Colored graph k-mers kept in memory to answer the queries without reloading the graph
+struct QueryServer<MH: HashFunctionFactory> {
+    k: usize,
+    kmers: HashMap<MH::HashTypeUnextendable, ColorIndexType>,
+    colormap: Mutex<ColorsIndexedReader>,
+    /// Used to report the matches by color name instead of color index
+    color_names: Option<Vec<String>>,
+    matches_filter: QueryMatchesFilter,
+}
+
+impl<MH: HashFunctionFactory> QueryServer<MH> {
+    fn load_graph(&mut self, graph_input: &Path) -> Result<(), GGCATError> {
+        let mut colors_buffer =
+            MinimizerBucketingSeqColorDataType::<ColorBundleGraphQuerying>::new_temp_buffer();
+
+        SequencesReader::process_file_extended(
+            graph_input,
+            |seq| {
+                MinimizerBucketingSeqColorDataType::<ColorBundleGraphQuerying>::clear_temp_buffer(
+                    &mut colors_buffer,
+                );
+                let color = MinimizerBucketingSeqColorDataType::<ColorBundleGraphQuerying>::create(
+                    SingleSequenceInfo {
+                        file_index: 0,
+                        sequence_ident: seq.ident,
+                    },
+                    &mut colors_buffer,
+                );
+
+                if seq.seq.len() < self.k {
+                    return;
+                }
+
+                let hashes = MH::new(seq.seq, self.k);
+                for (hash, color) in hashes.iter().zip(color.get_iterator(&colors_buffer)) {
+                    self.kmers.insert(hash.to_unextendable(), color);
+                }
+            },
+            None,
+            true,
+            false,
+            false,
+        )
+    }
+
+    fn load_index(&mut self, index_dir: &Path, index_info: &QueryIndexInfo) {
+        for bucket in index_info.kmers_buckets(index_dir) {
+            CompressedBinaryReader::new(bucket, RemoveFileMode::Keep, DEFAULT_PREFETCH_AMOUNT)
+                .decode_all_bucket_items::<QueryIndexEntry<MH::HashTypeUnextendable, ColorIndexType>, _>(
+                    (),
+                    &mut (),
+                    |(hash, color), _| {
+                        self.kmers.insert(hash, color);
+                    },
+                );
+        }
+    }
+
+    fn query_sequence<'a>(
+        &'a self,
+        query_index: u64,
+        query_header: &'a str,
+        sequence: &[u8],
+    ) -> Result<QueryColoredOutputRecord<'a>, GGCATError> {
+        let query_kmers = (sequence.len() + 1).saturating_sub(self.k) as u64;
+        let sequence = sequence.to_ascii_uppercase();
+
+        let mut subsets_counts: HashMap<ColorIndexType, u64> = HashMap::new();
+
+        // Skip the k-mers with unknown bases, as in the query bucketing
+        for part in sequence
+            .split(|base| !matches!(base, b'A' | b'C' | b'G' | b'T'))
+            .filter(|part| part.len() >= self.k)
+        {
+            for hash in MH::new(part, self.k).iter() {
+                if let Some(subset) = self.kmers.get(&hash.to_unextendable()) {
+                    *subsets_counts.entry(*subset).or_insert(0) += 1;
+                }
+            }
+        }
+
+        let subsets: Vec<_> = subsets_counts.keys().copied().collect();
+
+        let mut colors_counts: BTreeMap<ColorIndexType, u64> = BTreeMap::new();
+        self.colormap.lock().get_many(&subsets, |subset, colors| {
+            let count = subsets_counts[&subset];
+            for color in colors {
+                *colors_counts.entry(*color).or_insert(0) += count;
+            }
+        })?;
+
+        Ok(QueryColoredOutputRecord {
+            query_index: match self.color_names {
+                None => Some(query_index),
+                Some(_) => None,
+            },
+            query_header,
+            query_kmers,
+            matches: QueryColorsMatches {
+                fractions: self.matches_filter.apply(
+                    colors_counts
+                        .into_iter()
+                        .map(|(color, count)| (color, (count as f64) / (query_kmers as f64))),
+                ),
+                color_names: self.color_names.as_deref(),
+            },
+        })
+    }
+
+    /// Answers each line, formatted as `sequence` or `header<TAB>sequence`, with a json line
+    fn serve_lines(&self, input: impl BufRead, mut output: impl Write) -> Result<(), GGCATError> {
+        for (query_index, line) in input.lines().enumerate() {
+            let line = line?;
+            let (query_header, sequence) = match line.trim_end().split_once('\t') {
+                Some((header, sequence)) => (header, sequence),
+                None => ("", line.trim_end()),
+            };
+
+            let record =
+                self.query_sequence(query_index as u64, query_header, sequence.as_bytes())?;
+
+            serde_json::to_writer(&mut output, &record).map_err(std::io::Error::from)?;
+            writeln!(output)?;
+            output.flush()?;
+        }
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    fn serve_socket(self: Arc<Self>, socket_path: PathBuf) -> Result<(), GGCATError> {
+        use std::os::unix::net::UnixListener;
+
+        let listener = UnixListener::bind(&socket_path).map_err(|error| GGCATError::FileOpen {
+            file: socket_path.clone(),
+            error,
+        })?;
+
+        eprintln!("Listening on: {}", socket_path.display());
+
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(err) => {
+                    eprintln!("WARN: Cannot accept connection: {}", err);
+                    continue;
+                }
+            };
+
+            let server = self.clone();
+            std::thread::spawn(move || {
+                let result = stream
+                    .try_clone()
+                    .map_err(GGCATError::from)
+                    .and_then(|input| {
+                        server.serve_lines(BufReader::new(input), BufWriter::new(stream))
+                    });
+                if let Err(err) = result {
+                    eprintln!("WARN: Connection closed with error: {}", err);
+                }
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Loads a colored graph (or an index built by `run_index`) in memory and answers the queries
+/// received from the standard input, or from the connections to a unix socket if specified.
+/// The answers contain the same per-color match fractions of the colored `run_query` output
+#[static_dispatch(BucketingHash = [
+    hashes::cn_nthash::CanonicalNtHashIteratorFactory,
+    #[cfg(not(feature = "devel-build"))]  hashes::fw_nthash::ForwardNtHashIteratorFactory
+], MergingHash = [
+    #[cfg(not(feature = "devel-build"))] hashes::fw_seqhash::u16::ForwardSeqHashFactory,
+    #[cfg(not(feature = "devel-build"))] hashes::fw_seqhash::u32::ForwardSeqHashFactory,
+    #[cfg(not(feature = "devel-build"))] hashes::fw_seqhash::u64::ForwardSeqHashFactory,
+    #[cfg(not(feature = "devel-build"))] hashes::fw_seqhash::u128::ForwardSeqHashFactory,
+    #[cfg(not(feature = "devel-build"))] hashes::fw_rkhash::u32::ForwardRabinKarpHashFactory,
+    #[cfg(not(feature = "devel-build"))] hashes::fw_rkhash::u64::ForwardRabinKarpHashFactory,
+    #[cfg(not(feature = "devel-build"))] hashes::fw_rkhash::u128::ForwardRabinKarpHashFactory,
+    #[cfg(not(feature = "devel-build"))] hashes::cn_seqhash::u16::CanonicalSeqHashFactory,
+    #[cfg(not(feature = "devel-build"))] hashes::cn_seqhash::u32::CanonicalSeqHashFactory,
+    hashes::cn_seqhash::u64::CanonicalSeqHashFactory,
+    #[cfg(not(feature = "devel-build"))] hashes::cn_seqhash::u128::CanonicalSeqHashFactory,
+    #[cfg(not(feature = "devel-build"))] hashes::cn_rkhash::u32::CanonicalRabinKarpHashFactory,
+    #[cfg(not(feature = "devel-build"))] hashes::cn_rkhash::u64::CanonicalRabinKarpHashFactory,
+    #[cfg(not(feature = "devel-build"))] hashes::cn_rkhash::u128::CanonicalRabinKarpHashFactory,
+])]
+pub fn run_query_server<
+    BucketingHash: MinimizerHashFunctionFactory,
+    MergingHash: HashFunctionFactory,
+>(
+    k: usize,
+    m: usize,
+    graph_input: PathBuf,
+    socket_path: Option<PathBuf>,
+    numeric_ids: bool,
+    matches_filter: QueryMatchesFilter,
+) -> Result<(), GGCATError> {
+    matches_filter.validate()?;
+
+    BucketingHash::initialize(k);
+    MergingHash::initialize(k);
+
+    let query_index = if QueryIndexInfo::is_index(&graph_input) {
+        let index_info = QueryIndexInfo::load(&graph_input)?;
+        index_info.check_compatible(
+            &graph_input,
+            &QueryIndexInfo::new::<BucketingHash, MergingHash, ColorBundleGraphQuerying>(
+                k,
+                m,
+                index_info.buckets_count,
+            ),
+        )?;
+        Some(index_info)
+    } else {
+        None
+    };
+
+    let colormap_file = match &query_index {
+        Some(_) => QueryIndexInfo::colormap_path(&graph_input),
+        None => graph_input.with_extension("colors.dat"),
+    };
+
+    let colormap = ColorsIndexedReader::new(colormap_file)?;
+
+    let mut server = QueryServer::<MergingHash> {
+        k,
+        kmers: HashMap::new(),
+        color_names: if numeric_ids {
+            None
+        } else {
+            Some(colormap.color_names().to_vec())
+        },
+        colormap: Mutex::new(colormap),
+        matches_filter,
+    };
+
+    match &query_index {
+        Some(index_info) => server.load_index(&graph_input, index_info),
+        None => server.load_graph(&graph_input)?,
+    }
+
+    eprintln!("Loaded {} kmers, ready for queries", server.kmers.len());
+
+    match socket_path {
+        #[cfg(unix)]
+        Some(socket_path) => Arc::new(server).serve_socket(socket_path),
+        #[cfg(not(unix))]
+        Some(_) => Err(GGCATError::InvalidConfig(
+            "unix sockets are not supported on this platform".to_string(),
+        )),
+        None => server.serve_lines(std::io::stdin().lock(), std::io::stdout().lock()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pipeline::colored_query_output::QueryMatchesFilter;
+    use crate::server::QueryServer;
+    use colors::storage::indexed_reader::ColorsIndexedReader;
+    use colors::storage::run_length::RunLengthColorsSerializer;
+    use colors::storage::serializer::ColorsSerializer;
+    use hashbrown::HashMap;
+    use hashes::cn_seqhash::u64::CanonicalSeqHashFactory;
+    use hashes::{ExtendableHashTraitType, HashFunction, HashFunctionFactory};
+    use io::test_utils::TestTempDir;
+    use parking_lot::Mutex;
+    use serde_json::{json, Value};
+
+    const GRAPH_SEQUENCE: &[u8] = b"ACCTGAATC";
+
+    fn create_server(
+        temp_dir: &TestTempDir,
+        numeric_ids: bool,
+    ) -> QueryServer<CanonicalSeqHashFactory> {
+        let k = 5;
+        CanonicalSeqHashFactory::initialize(k);
+
+        let colormap_file = temp_dir.file("graph.colors.dat");
+        {
+            let serializer = ColorsSerializer::<RunLengthColorsSerializer>::new(
+                &colormap_file,
+                vec!["sample1".to_string(), "sample2".to_string()],
+            );
+            serializer.serialize_colors(&[0, 1]);
+        }
+        let colormap = ColorsIndexedReader::new(&colormap_file).unwrap();
+
+        let mut kmers = HashMap::new();
+        for hash in CanonicalSeqHashFactory::new(GRAPH_SEQUENCE, k).iter() {
+            kmers.insert(hash.to_unextendable(), 0);
+        }
+
+        QueryServer {
+            k,
+            kmers,
+            color_names: if numeric_ids {
+                None
+            } else {
+                Some(colormap.color_names().to_vec())
+            },
+            colormap: Mutex::new(colormap),
+            matches_filter: QueryMatchesFilter::default(),
+        }
+    }
+
+    fn serve(server: &QueryServer<CanonicalSeqHashFactory>, input: &str) -> Vec<Value> {
+        let mut output = Vec::new();
+        server.serve_lines(input.as_bytes(), &mut output).unwrap();
+        output
+            .split(|c| *c == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_slice(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn server_lines_protocol() {
+        let temp_dir = TestTempDir::new("server-lines");
+        let server = create_server(&temp_dir, false);
+        let answers = serve(&server, "q1\tACCTGAATC\nacctgaaNNN\r\nq3\tGGGGGGGG\n");
+
+        assert_eq!(
+            answers,
+            vec![
+                json!({
+                    "query_header": "q1",
+                    "query_kmers": 5,
+                    "matches": {"sample1": 1.0, "sample2": 1.0}
+                }),
+                // Lines without a tab are sequences without a header
+                json!({
+                    "query_header": "",
+                    "query_kmers": 6,
+                    "matches": {"sample1": 0.5, "sample2": 0.5}
+                }),
+                json!({"query_header": "q3", "query_kmers": 4, "matches": {}}),
+            ]
+        );
+    }
+
+    #[test]
+    fn server_numeric_ids() {
+        let temp_dir = TestTempDir::new("server-numeric-ids");
+        let server = create_server(&temp_dir, true);
+        let answers = serve(&server, "q1\tACCTGAATC\n");
+
+        assert_eq!(
+            answers,
+            vec![json!({
+                "query_index": 0,
+                "query_header": "q1",
+                "query_kmers": 5,
+                "matches": {"0": 1.0, "1": 1.0}
+            })]
+        );
+    }
+}